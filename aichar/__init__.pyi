@@ -1,5 +1,308 @@
 import typing
 
+class Diagnostic:
+    """Single validation finding
+
+    :ivar rule_id: Stable rule identifier, e.g. 'empty-name'
+    :ivar severity: 'error' or 'warning'
+    :ivar field: Affected field, if any
+    :ivar message: Human readable description
+    """
+
+    @property
+    def rule_id(self) -> str: ...
+    @property
+    def severity(self) -> str: ...
+    @property
+    def field(self) -> typing.Optional[str]: ...
+    @property
+    def message(self) -> str: ...
+
+class FieldChange:
+    """Change of a single field between two characters
+
+    :ivar field: Field name, lorebook entries use 'character_book.entries[<key>]' where
+                 the key is 'id:<id>', 'name:<name>' or 'idx:<position>' (position among the
+                 old book's entries without id or name, matched by keys and content first)
+                 or 'new:<hash>' for added entries without id or name
+    :ivar kind: 'added', 'removed' or 'modified'
+    :ivar old: Previous value as text
+    :ivar new: New value as text
+    :ivar added: Items added to a list field (tags, alternate_greetings)
+    :ivar removed: Items removed from a list field
+    """
+
+    @property
+    def field(self) -> str: ...
+    @property
+    def kind(self) -> str: ...
+    @property
+    def old(self) -> typing.Optional[str]: ...
+    @property
+    def new(self) -> typing.Optional[str]: ...
+    @property
+    def added(self) -> typing.List[str]: ...
+    @property
+    def removed(self) -> typing.List[str]: ...
+    def line_diff(self) -> typing.List[typing.Tuple[str, str]]:
+        """Line-level diff as ('-' | '+' | ' ', line) pairs"""
+
+class CharacterDiff:
+    """Structured diff between two characters"""
+
+    @property
+    def changes(self) -> typing.List[FieldChange]: ...
+    def is_empty(self) -> bool: ...
+    def unified(self, context: int = 3) -> str:
+        """Render as unified diff, one section per changed field"""
+    def __len__(self) -> int: ...
+
+class MergeConflict:
+    """Field changed differently on both sides of a merge"""
+
+    @property
+    def field(self) -> str: ...
+    @property
+    def base(self) -> typing.Optional[str]: ...
+    @property
+    def ours(self) -> typing.Optional[str]: ...
+    @property
+    def theirs(self) -> typing.Optional[str]: ...
+
+class MergeResult:
+    """Result of aichar.merge()
+
+    :ivar character: Merged character
+    :ivar conflicts: Unresolved conflicts
+    :ivar merged_fields: Fields merged automatically
+    """
+
+    @property
+    def character(self) -> "CharacterClass": ...
+    @property
+    def conflicts(self) -> typing.List[MergeConflict]: ...
+    @property
+    def merged_fields(self) -> typing.List[str]: ...
+    def has_conflicts(self) -> bool: ...
+
+class ToolInfo:
+    """Tool that created or edited the character"""
+
+    @property
+    def name(self) -> str: ...
+    @property
+    def version(self) -> str: ...
+    @property
+    def url(self) -> str: ...
+
+class Revision:
+    """Entry of the character revision history
+
+    :ivar author: Optional author name
+    :ivar timestamp: Unix timestamp in milliseconds
+    :ivar summary: Short description of the change
+    :ivar hash: SHA-256 hash of every content field, ignoring image path, timestamps, source, tools and history
+    """
+
+    @property
+    def author(self) -> typing.Optional[str]: ...
+    @property
+    def timestamp(self) -> int: ...
+    @property
+    def summary(self) -> str: ...
+    @property
+    def hash(self) -> str: ...
+
+class DuplicateGroup:
+    """Group of duplicated character files
+
+    :ivar kind: 'exact' (same fingerprint) or 'near' (similar text)
+    :ivar paths: Paths of the grouped files
+    :ivar similarity: Estimated similarity (1.0 for exact groups)
+    """
+
+    @property
+    def kind(self) -> str: ...
+    @property
+    def paths(self) -> typing.List[str]: ...
+    @property
+    def similarity(self) -> float: ...
+
+class LibraryEntry:
+    """Indexed character file of a CharacterLibrary"""
+
+    @property
+    def path(self) -> str: ...
+    @property
+    def name(self) -> str: ...
+    @property
+    def tags(self) -> typing.List[str]: ...
+    @property
+    def fingerprint(self) -> str: ...
+    @property
+    def modified_time(self) -> int:
+        """File modification time in milliseconds"""
+
+class CharacterLibrary:
+    """Index of a directory of character files backed by SQLite
+
+    :param directory: Directory with PNG cards, JSON, YAML, TOML, Markdown, .byaf and .charx files
+    :param database_path: SQLite database path
+                          (default: <directory>/aichar_library.db)
+    """
+
+    def __init__(self, directory: str, database_path: typing.Optional[str] = None) -> None: ...
+    def scan(self) -> typing.Dict[str, int]:
+        """Index new and changed files, drop deleted ones
+
+        Files with unchanged modification time and size are skipped.
+        Returns counts of 'added', 'updated', 'removed', 'unchanged'
+        and 'failed' files.
+        """
+    def search(
+        self,
+        query: typing.Optional[str] = None,
+        tags: typing.Optional[typing.List[str]] = None,
+        limit: typing.Optional[int] = None,
+    ) -> typing.List[LibraryEntry]:
+        """Search indexed characters
+
+        :param query: SQLite FTS5 full-text query over personality and scenario,
+                      text that isn't valid FTS5 syntax is searched word by word
+        :param tags: Only return characters having all of these tags
+        :param limit: Maximum number of results
+        """
+    def tags(self) -> typing.List[str]:
+        """All tags in the library"""
+    def load(self, path: str) -> "CharacterClass":
+        """Load character of an indexed file"""
+    def __len__(self) -> int: ...
+
+class ConversionResult:
+    """Result of converting a single file with convert_many()"""
+
+    @property
+    def input(self) -> str: ...
+    @property
+    def output(self) -> typing.Optional[str]: ...
+    @property
+    def error(self) -> typing.Optional[str]: ...
+    def ok(self) -> bool: ...
+
+class LoadLimits:
+    """Size limits applied while reading PNG character cards from untrusted sources. Set a limit to None to disable it.
+
+    Loaders called without limits use the defaults of this class.
+    """
+    max_file_size: typing.Optional[int]
+    """Maximum total size of the PNG file in bytes"""
+    max_text_chunk_size: typing.Optional[int]
+    """Maximum size of a character or asset tEXt/zTXt/iTXt chunk, also applied after decompression"""
+    max_pixels: typing.Optional[int]
+    """Maximum width * height declared in the IHDR chunk"""
+    max_json_depth: typing.Optional[int]
+    """Maximum nesting depth of the embedded JSON data"""
+    def __init__(
+        self,
+        max_file_size: typing.Optional[int] = 67108864,
+        max_text_chunk_size: typing.Optional[int] = 16777216,
+        max_pixels: typing.Optional[int] = 100000000,
+        max_json_depth: typing.Optional[int] = 64,
+    ) -> None: ...
+
+class CardInfo:
+    """Lightweight card summary returned by peek_card()"""
+
+    @property
+    def path(self) -> str: ...
+    @property
+    def name(self) -> str: ...
+    @property
+    def tags(self) -> typing.List[str]: ...
+    @property
+    def spec_version(self) -> typing.Optional[str]:
+        """Card spec version (e.g. "2.0"), None for V1 cards"""
+
+class ChubInfo:
+    """Typed view of the 'chub' card extension written by character hubs"""
+    full_path: typing.Optional[str]
+    """Hub path of the character (e.g. "author/character-name")"""
+    id: typing.Optional[int]
+    related_lorebooks: typing.List[int]
+    """Ids of lorebooks linked to the character on the hub"""
+    alt_expressions: typing.Dict[str, str]
+    """Expression name to image URL"""
+    def __init__(
+        self,
+        full_path: typing.Optional[str] = None,
+        id: typing.Optional[int] = None,
+        related_lorebooks: typing.List[int] = [],
+        alt_expressions: typing.Dict[str, str] = {},
+    ) -> None: ...
+    @property
+    def url(self) -> typing.Optional[str]:
+        """Character page URL built from full_path"""
+    def __eq__(self, other: object) -> bool: ...
+
+class ChatMessage:
+    """Single message of a chat log"""
+    speaker: str
+    text: str
+    is_user: bool
+    is_system: bool
+    timestamp: typing.Optional[int]
+    """Send time in milliseconds"""
+    swipes: typing.List[str]
+    """Alternative responses (the current text is one of them)"""
+    @property
+    def metadata(self) -> typing.Dict[str, typing.Any]:
+        """SillyTavern message fields with no attribute of their own (extra, swipe_info, force_avatar...), written back on export"""
+    @metadata.setter
+    def metadata(self, metadata: typing.Dict[str, typing.Any]) -> None: ...
+    def __init__(
+        self,
+        speaker: str,
+        text: str,
+        is_user: bool = False,
+        is_system: bool = False,
+        timestamp: typing.Optional[int] = None,
+        swipes: typing.List[str] = [],
+    ) -> None: ...
+    def __eq__(self, other: object) -> bool: ...
+
+class ChatLog:
+    """Conversation with a character, linked to it by character_name"""
+    character_name: str
+    user_name: str
+    messages: typing.List[ChatMessage]
+    """Copy of the message list, assign a new list to change it"""
+    created_time: typing.Optional[int]
+    def __init__(
+        self,
+        character_name: str = "",
+        user_name: str = "User",
+        messages: typing.List[ChatMessage] = [],
+        created_time: typing.Optional[int] = None,
+    ) -> None: ...
+    @property
+    def metadata(self) -> typing.Dict[str, typing.Any]:
+        """SillyTavern chat_metadata (author's note, bookmarks...)"""
+    @metadata.setter
+    def metadata(self, metadata: typing.Dict[str, typing.Any]) -> None: ...
+    def append(self, message: ChatMessage) -> None: ...
+    def is_for(self, character: "CharacterClass") -> bool:
+        """Whether character_name matches the character's name (case-insensitive)"""
+    def link(self, character: "CharacterClass") -> None:
+        """Set character_name to the character's name and rename its messages"""
+    def export_sillytavern(self) -> str:
+        """Export as SillyTavern .jsonl chat"""
+    def export_sillytavern_file(self, path: str) -> None: ...
+    def export_textgen(self) -> str:
+        """Export as TextGenerationWebUI history.json (system messages are left out)"""
+    def export_textgen_file(self, path: str) -> None: ...
+    def __len__(self) -> int: ...
+    def __eq__(self, other: object) -> bool: ...
+
 class CharacterClass:
     """Character card representation
 
@@ -10,6 +313,26 @@ class CharacterClass:
     :ivar greeting_message: Initial greeting
     :ivar example_messages: Sample conversation history
     :ivar image_path: Optional PNG image path
+    :ivar tags: Character tags
+    :ivar alternate_greetings: Additional greeting messages
+    :ivar group_only_greetings: Greetings used only in group chats (V3)
+    :ivar nickname: Name used in place of the character name (V3)
+    :ivar creator: Card creator (V2)
+    :ivar character_version: Card version (V2)
+    :ivar creator_notes: Notes for users, not sent to the model (V2)
+    :ivar system_prompt: System prompt override (V2)
+    :ivar post_history_instructions: Instructions inserted after the chat history (V2)
+    :ivar character_book: Optional lorebook (V2 character_book structure)
+    :ivar extensions: Card extensions (e.g. RisuAI data under "risuai"), kept as-is
+    :ivar assets: Card assets (V3 assets list, e.g. RisuAI emotion images)
+    :ivar embedded_assets: Asset files carried inside the card, keyed by the URI that assets point to ("__asset:0", "embeded://assets/icon.png")
+    :ivar history: Revision history stored in the card metadata
+    :ivar revision_author: Author of the revisions appended automatically on export
+    :ivar created_time: Creation timestamp (ms) from metadata
+    :ivar modified_time: Modification timestamp (ms) from metadata
+    :ivar metadata_version: Metadata version
+    :ivar source: Where the character came from
+    :ivar tools: Tools that created or edited the character
     """
 
     @property
@@ -41,60 +364,308 @@ class CharacterClass:
     @image_path.setter
     def image_path(self, image_path: str) -> typing.Optional[Exception]: ...
     @property
+    def tags(self) -> typing.List[str]: ...
+    @tags.setter
+    def tags(self, tags: typing.List[str]) -> None: ...
+    @property
+    def alternate_greetings(self) -> typing.List[str]: ...
+    @alternate_greetings.setter
+    def alternate_greetings(self, alternate_greetings: typing.List[str]) -> None: ...
+    @property
+    def group_only_greetings(self) -> typing.List[str]: ...
+    @group_only_greetings.setter
+    def group_only_greetings(self, group_only_greetings: typing.List[str]) -> None: ...
+    @property
+    def nickname(self) -> typing.Optional[str]: ...
+    @nickname.setter
+    def nickname(self, nickname: typing.Optional[str]) -> None: ...
+    @property
+    def creator(self) -> str: ...
+    @creator.setter
+    def creator(self, creator: str) -> None: ...
+    @property
+    def character_version(self) -> str: ...
+    @character_version.setter
+    def character_version(self, character_version: str) -> None: ...
+    @property
+    def creator_notes(self) -> str: ...
+    @creator_notes.setter
+    def creator_notes(self, creator_notes: str) -> None: ...
+    @property
+    def system_prompt(self) -> str: ...
+    @system_prompt.setter
+    def system_prompt(self, system_prompt: str) -> None: ...
+    @property
+    def post_history_instructions(self) -> str: ...
+    @post_history_instructions.setter
+    def post_history_instructions(self, post_history_instructions: str) -> None: ...
+    @property
+    def character_book(self) -> typing.Optional[typing.Dict[str, typing.Any]]: ...
+    @character_book.setter
+    def character_book(self, character_book: typing.Optional[typing.Dict[str, typing.Any]]) -> typing.Optional[Exception]: ...
+    @property
+    def extensions(self) -> typing.Dict[str, typing.Any]: ...
+    @extensions.setter
+    def extensions(self, extensions: typing.Dict[str, typing.Any]) -> None: ...
+    @property
+    def chub(self) -> typing.Optional[ChubInfo]:
+        """Typed copy of extensions['chub'], None if the card has none"""
+    @chub.setter
+    def chub(self, chub: typing.Optional[ChubInfo]) -> None:
+        """Write typed fields back into extensions['chub'] (other keys are kept), None removes it"""
+    @property
+    def assets(self) -> typing.List[typing.Dict[str, typing.Any]]: ...
+    @assets.setter
+    def assets(self, assets: typing.List[typing.Dict[str, typing.Any]]) -> None: ...
+    @property
+    def embedded_assets(self) -> typing.Dict[str, bytes]: ...
+    @embedded_assets.setter
+    def embedded_assets(self, embedded_assets: typing.Dict[str, bytes]) -> None: ...
+    def apply_risu_module(self, module: bytes) -> None:
+        """Merge a RisuAI module (.risum) into this character: lorebook entries, regex and trigger scripts, background HTML and assets; the rest of the module, and values that conflict with the character's, go to extensions["risuai"]["modules"]
+        :raises ValueError: Invalid or RPack-encoded module
+        """
+    @property
+    def created_time(self) -> typing.Optional[int]: ...
+    @property
+    def modified_time(self) -> typing.Optional[int]: ...
+    @property
+    def metadata_version(self) -> typing.Optional[int]: ...
+    @property
+    def source(self) -> typing.Optional[str]: ...
+    @source.setter
+    def source(self, source: typing.Optional[str]) -> None: ...
+    @property
+    def tools(self) -> typing.List[ToolInfo]: ...
+    @property
+    def history(self) -> typing.List[Revision]: ...
+    @property
+    def revision_author(self) -> typing.Optional[str]: ...
+    @revision_author.setter
+    def revision_author(self, revision_author: typing.Optional[str]) -> None: ...
+    @property
     def data_summary(self) -> str:
         """Get all character data as formatted string"""
 
-    def export_json(self, format_type: str) -> str:
+    def token_counts(self, tokenizer_path: str) -> typing.Dict[str, int]:
+        """Count tokens per field using a local HuggingFace tokenizer
+
+        Returns counts for every text field (alternate greetings summed) plus
+        'permanent' (personality and scenario) and 'total'.
+
+        :param tokenizer_path: Path to a tokenizer.json file
+        :raises ValueError: Tokenizer could not be loaded
+        """
+
+    def add_revision(self, summary: str, author: typing.Optional[str] = None) -> None:
+        """Append a revision for the current character content to history"""
+
+    def fingerprint(self) -> str:
+        """Canonical SHA-256 hash of normalized character content
+
+        Ignores timestamps, tool metadata, revision history and whitespace
+        or letter case differences.
+        """
+
+    def validate(self) -> typing.List[Diagnostic]:
+        """Check character data for common authoring mistakes
+
+        Characters loaded from a card also have the card's declared
+        spec_version checked.
+        """
+
+    def to_dict(self, format_type: typing.Optional[str] = None, metadata_mode: str = "update", reproducible: bool = False, timestamp: typing.Optional[int] = None) -> typing.Dict[str, typing.Any]:
+        """Export character as a Python dict, same structure as export_json()
+
+        :param format_type: Any export_json() format, None or "neutral" for the neutral format
+        :raises ValueError: Unsupported format or metadata mode
+        """
+
+    @property
+    def personality_attributes(self) -> typing.Dict[str, typing.List[str]]:
+        """Personality parsed from W++, square bracket (sbf) or bracketed Boostyle format, empty for plain prose"""
+    def set_personality_attributes(self, attributes: typing.Dict[str, typing.List[str]], style: str = "wpp") -> None:
+        """Write personality from an attribute map
+
+        :param style: 'wpp', 'sbf', 'boostyle' or 'prose'
+        :raises ValueError: Unsupported style, or a name, key or value contains double quotes in a structured style
+        """
+    def personality_as(self, style: str) -> str:
+        """Personality converted to another style ('wpp', 'sbf', 'boostyle' or 'prose')
+
+        :raises ValueError: Personality is not structured, style is unsupported or contains double quotes
+        """
+
+    def __init__(
+        self,
+        name: str = "",
+        summary: str = "",
+        personality: str = "",
+        scenario: str = "",
+        greeting_message: str = "",
+        example_messages: str = "",
+        image_path: typing.Optional[str] = None,
+        tags: typing.Optional[typing.List[str]] = None,
+        alternate_greetings: typing.Optional[typing.List[str]] = None,
+        character_book: typing.Optional[typing.Dict[str, typing.Any]] = None,
+        source: typing.Optional[str] = None,
+        extensions: typing.Optional[typing.Dict[str, typing.Any]] = None,
+        assets: typing.Optional[typing.List[typing.Dict[str, typing.Any]]] = None,
+        group_only_greetings: typing.Optional[typing.List[str]] = None,
+        nickname: typing.Optional[str] = None,
+        creator: str = "",
+        character_version: str = "",
+        creator_notes: str = "",
+        system_prompt: str = "",
+        post_history_instructions: str = "",
+    ) -> None:
+        """Create a character, every field is optional
+
+        :param image_path: PNG image path, validated like the image_path setter ("" means no image)
+        :raises ValueError: Invalid image path or character book
+        """
+    def __repr__(self) -> str: ...
+    def __eq__(self, other: object) -> bool:
+        """Compare all fields, including image path and metadata"""
+    def __hash__(self) -> int:
+        """Hash of the fields __eq__ compares; changing a character changes its hash, so don't modify characters kept in sets or used as dict keys"""
+    def __copy__(self) -> "CharacterClass": ...
+    def __deepcopy__(self, memo: typing.Any) -> "CharacterClass": ...
+    def __getstate__(self) -> str:
+        """Pickle state: neutral JSON with exact metadata and image path"""
+    def __setstate__(self, state: str) -> None: ...
+
+    def export_json(self, format_type: str, metadata_mode: str = "update", reproducible: bool = False, timestamp: typing.Optional[int] = None) -> str:
         """Export character as JSON string
 
         :param format_type: Target format ('tavernai', 'sillytavern',
-                            'textgenerationwebui', 'pygmalion', 'aicompanion')
+                            'textgenerationwebui', 'pygmalion', 'aicompanion',
+                            'chara_card_v2', 'risuai',
+                            'agnaistic' (or 'agnai'), 'backyard' (or 'faraday'),
+                            'koboldai' (or 'kobold', 'koboldcpp'))
+        :param metadata_mode: 'update' (set modified time and tool to aichar),
+                              'keep' (keep original modified time and tool) or
+                              'append' (add aichar as a new tool entry)
+        :param reproducible: Produce identical output for identical characters
+                             (modified/created fall back to the loaded
+                             timestamps instead of the current time)
+        :param timestamp: Use this timestamp (ms) instead of the current time
         """
 
-    def export_json_file(self, format_type: str, export_json_path: str) -> None:
+    def export_json_file(self, format_type: str, export_json_path: str, metadata_mode: str = "update", reproducible: bool = False, timestamp: typing.Optional[int] = None) -> None:
         """Export character to JSON file
 
         :param format_type: Target format
         :param export_json_path: Output file path
         """
 
-    def export_neutral_json(self) -> str:
+    def export_neutral_json(self, metadata_mode: str = "update", reproducible: bool = False, timestamp: typing.Optional[int] = None) -> str:
         """Export as cross-compatible JSON"""
 
-    def export_neutral_json_file(self, export_json_path: str) -> None:
+    def export_neutral_json_file(self, export_json_path: str, metadata_mode: str = "update", reproducible: bool = False, timestamp: typing.Optional[int] = None) -> None:
         """Export cross-compatible JSON to file"""
 
-    def export_yaml(self, format_type: str) -> str:
+    def export_yaml(self, format_type: str, metadata_mode: str = "update", reproducible: bool = False, timestamp: typing.Optional[int] = None) -> str:
         """Export character as YAML string
 
         :param format_type: Target format
         """
 
-    def export_yaml_file(self, format_type: str, export_yaml_path: str) -> None:
+    def export_yaml_file(self, format_type: str, export_yaml_path: str, metadata_mode: str = "update", reproducible: bool = False, timestamp: typing.Optional[int] = None) -> None:
         """Export character to YAML file"""
 
-    def export_neutral_yaml(self) -> str:
+    def export_neutral_yaml(self, metadata_mode: str = "update", reproducible: bool = False, timestamp: typing.Optional[int] = None) -> str:
         """Export as cross-compatible YAML"""
 
-    def export_neutral_yaml_file(self, export_yaml_path: str) -> None:
+    def export_neutral_yaml_file(self, export_yaml_path: str, metadata_mode: str = "update", reproducible: bool = False, timestamp: typing.Optional[int] = None) -> None:
         """Export cross-compatible YAML to file"""
 
-    def export_card(self, format_type: str) -> bytes:
+    def export_toml(self, format_type: str, metadata_mode: str = "update", reproducible: bool = False, timestamp: typing.Optional[int] = None) -> str:
+        """Export character as TOML string (empty fields are left out)
+
+        :param format_type: Target format
+        :raises ValueError: Extensions, assets or character book extensions contain a null value
+        """
+
+    def export_toml_file(self, format_type: str, export_toml_path: str, metadata_mode: str = "update", reproducible: bool = False, timestamp: typing.Optional[int] = None) -> None:
+        """Export character to TOML file"""
+
+    def export_neutral_toml(self, metadata_mode: str = "update", reproducible: bool = False, timestamp: typing.Optional[int] = None) -> str:
+        """Export as cross-compatible TOML"""
+
+    def export_neutral_toml_file(self, export_toml_path: str, metadata_mode: str = "update", reproducible: bool = False, timestamp: typing.Optional[int] = None) -> None:
+        """Export cross-compatible TOML to file"""
+
+    def export_markdown(self, metadata_mode: str = "update", reproducible: bool = False, timestamp: typing.Optional[int] = None) -> str:
+        """Export as human-readable Markdown (loadable with load_character_markdown)"""
+
+    def export_markdown_file(self, export_markdown_path: str, metadata_mode: str = "update", reproducible: bool = False, timestamp: typing.Optional[int] = None) -> None:
+        """Export Markdown to file"""
+
+    def export_dataset(
+        self,
+        logs: typing.List[ChatLog],
+        format_type: str,
+        macros: typing.Optional[typing.Dict[str, str]] = None,
+        substitute_macros: bool = True,
+        include_examples: bool = False,
+    ) -> str:
+        """Convert chat logs of this character into a fine-tuning dataset
+
+        The system message is built from summary, personality and scenario.
+        Consecutive messages of the same side are merged, system messages and
+        logs without messages are left out.
+
+        :param format_type: 'sharegpt' (JSON), 'openai' (chat JSONL) or 'alpaca' (instruction JSONL)
+        :param macros: Extra or overriding macro values, e.g. {"user": "Alex"} replaces {{user}}
+                       ({{char}} and {{user}} default to the character and log user names)
+        :param substitute_macros: Replace {{macros}} (and <USER>/<BOT>) in all texts
+        :param include_examples: Add the example dialogue to the system message
+        :raises ValueError: Unsupported format or a log belongs to another character
+        """
+
+    def export_dataset_file(
+        self,
+        logs: typing.List[ChatLog],
+        format_type: str,
+        export_dataset_path: str,
+        macros: typing.Optional[typing.Dict[str, str]] = None,
+        substitute_macros: bool = True,
+        include_examples: bool = False,
+    ) -> None:
+        """Write a fine-tuning dataset to file"""
+
+    def export_card(self, format_type: str, metadata_mode: str = "update", reproducible: bool = False, timestamp: typing.Optional[int] = None) -> bytes:
         """Export as character card (PNG bytes)
 
         :param format_type: Target format or 'neutral'
         :raises ValueError: Missing PNG image path
         """
 
-    def export_card_file(self, format_type: str, export_card_path: str) -> None:
+    def export_card_file(self, format_type: str, export_card_path: str, metadata_mode: str = "update", reproducible: bool = False, timestamp: typing.Optional[int] = None) -> None:
         """Export character card to PNG file"""
 
-    def export_neutral_card(self) -> bytes:
+    def export_neutral_card(self, metadata_mode: str = "update", reproducible: bool = False, timestamp: typing.Optional[int] = None) -> bytes:
         """Export cross-compatible character card"""
 
-    def export_neutral_card_file(self, export_card_path: str) -> None:
+    def export_neutral_card_file(self, export_card_path: str, metadata_mode: str = "update", reproducible: bool = False, timestamp: typing.Optional[int] = None) -> None:
         """Export cross-compatible card to file"""
 
+    def export_byaf(self, metadata_mode: str = "update", reproducible: bool = False, timestamp: typing.Optional[int] = None) -> bytes:
+        """Export as Backyard AI archive (.byaf zip bytes)
+
+        The image at image_path (if set) is stored as the character avatar.
+        """
+
+    def export_byaf_file(self, export_byaf_path: str, metadata_mode: str = "update", reproducible: bool = False, timestamp: typing.Optional[int] = None) -> None:
+        """Export Backyard AI archive to file"""
+
+    def export_losses(self, format_type: str) -> typing.List[str]:
+        """List fields with data that an export to format_type would drop
+
+        :param format_type: Target format, 'byaf' or 'neutral'
+        """
+
 def create_character(
     name: str,
     summary: str,
@@ -112,14 +683,35 @@ def create_character(
     :param scenario: Situation context
     :param greeting_message: Initial greeting
     :param example_messages: Sample conversation
-    :param image_path: PNG image path
+    :param image_path: PNG image path, "" for no image
+    :raises ValueError: Image path is not an existing .png file
     """
 
-def load_character_json(json: str) -> CharacterClass:
-    """Load character from JSON string"""
+def load_character_json(json: str, strict: bool = False, format_type: typing.Optional[str] = None) -> CharacterClass:
+    """Load character from JSON string
+
+    :param strict: Reject documents that don't match the JSON Schema
+    :param format_type: Format whose schema strict mode checks, any json_schema() format.
+                        None checks cards with a 'spec' against that spec's schema and
+                        other documents against every supported format
+    :raises ValueError: Document does not match the schema (strict mode)
+    """
 
-def load_character_json_file(path: str) -> CharacterClass:
-    """Load character from JSON file"""
+def load_character_json_file(path: str, strict: bool = False, format_type: typing.Optional[str] = None) -> CharacterClass:
+    """Load character from JSON file
+
+    :param strict: Reject documents that don't match the JSON Schema
+    :param format_type: Format whose schema strict mode checks, see load_character_json()
+    :raises ValueError: Document does not match the schema (strict mode)
+    """
+
+def json_schema(format_type: str) -> str:
+    """Get JSON Schema (draft-07) of an export format as JSON string
+
+    :param format_type: 'tavernai', 'sillytavern', 'textgenerationwebui',
+                        'pygmalion', 'aicompanion', 'chara_card_v2', 'risuai', 'agnaistic', 'backyard',
+                        'koboldai' or 'neutral'
+    """
 
 def load_character_yaml(yaml: str) -> CharacterClass:
     """Load character from YAML string"""
@@ -127,15 +719,151 @@ def load_character_yaml(yaml: str) -> CharacterClass:
 def load_character_yaml_file(path: str) -> CharacterClass:
     """Load character from YAML file"""
 
-def load_character_card(bytes: bytes) -> CharacterClass:
+def load_character_toml(toml: str) -> CharacterClass:
+    """Load character from TOML string
+    :raises ValueError: Invalid TOML
+    """
+
+def load_character_toml_file(path: str) -> CharacterClass:
+    """Load character from TOML file
+    :raises ValueError: Invalid TOML
+    """
+
+def load_character_markdown(markdown: str) -> CharacterClass:
+    """Load character from Markdown written by CharacterClass.export_markdown()
+    :raises ValueError: Unknown section or invalid front matter/YAML block
+    """
+
+def load_character_markdown_file(path: str) -> CharacterClass:
+    """Load character from Markdown file
+    :raises ValueError: Unknown section or invalid front matter/YAML block
+    """
+
+def load_character_card(bytes: bytes, limits: typing.Optional[LoadLimits] = None) -> CharacterClass:
     """Load character from card bytes
     :raises ValueError: Invalid character card format
     """
 
-def load_character_card_file(path: str) -> CharacterClass:
+def load_character_card_file(path: str, limits: typing.Optional[LoadLimits] = None) -> CharacterClass:
     """Load character from PNG card file
     :raises ValueError: Invalid character card format
     """
 
+def load_character_card_stream(stream: typing.BinaryIO, limits: typing.Optional[LoadLimits] = None) -> CharacterClass:
+    """Load character from a binary file-like object, reading one PNG chunk at a time
+    :raises ValueError: Invalid character card format or a limit was exceeded
+    """
+
+def load_character_byaf(bytes: bytes, limits: typing.Optional[LoadLimits] = None) -> CharacterClass:
+    """Load character from Backyard AI archive (.byaf) bytes
+    :raises ValueError: Invalid archive or a limit was exceeded
+    """
+
+def load_character_byaf_file(path: str, limits: typing.Optional[LoadLimits] = None) -> CharacterClass:
+    """Load character from Backyard AI archive (.byaf) file
+    :raises ValueError: Invalid archive or a limit was exceeded
+    """
+
+def load_character_charx(bytes: bytes, limits: typing.Optional[LoadLimits] = None) -> CharacterClass:
+    """Load character from RisuAI .charx archive bytes (card.json, module.risum and assets)
+    :raises ValueError: Invalid archive or a limit was exceeded
+    """
+
+def load_character_charx_file(path: str, limits: typing.Optional[LoadLimits] = None) -> CharacterClass:
+    """Load character from RisuAI .charx archive file
+    :raises ValueError: Invalid archive or a limit was exceeded
+    """
+
+def load_risu_module(bytes: bytes) -> CharacterClass:
+    """Load a RisuAI module (.risum) as a character holding only the module's lorebook, scripts and assets
+    :raises ValueError: Invalid or RPack-encoded module
+    """
+
+def from_dict(mapping: typing.Mapping[str, typing.Any]) -> CharacterClass:
+    """Load character from a Python mapping in any supported format (same as load_character_json)
+    :raises ValueError: Mapping does not contain correct character data
+    """
+
+def peek_card(path: str, limits: typing.Optional[LoadLimits] = None) -> CardInfo:
+    """Read only name, tags and spec version of a PNG card
+
+    Reading stops at the character chunk, or at the image data after a 'chara' chunk.
+    A numeric spec_version is returned as text, e.g. '2.0'.
+
+    :param limits: Size limits, defaults to LoadLimits()
+    :raises ValueError: Invalid character card format or a limit is exceeded
+    """
+
+def load_chat_sillytavern(jsonl: str, character: typing.Optional[CharacterClass] = None) -> ChatLog:
+    """Load SillyTavern .jsonl chat, linking it to character if given
+    :raises ValueError: A line does not contain correct JSON
+    """
+
+def load_chat_sillytavern_file(path: str, character: typing.Optional[CharacterClass] = None) -> ChatLog:
+    """Load SillyTavern .jsonl chat file"""
+
+def load_chat_textgen(json: str, character: typing.Optional[CharacterClass] = None, user_name: str = "You") -> ChatLog:
+    """Load TextGenerationWebUI history.json (which has no speaker names)
+
+    :param character: Character that speaks the bot messages
+    :param user_name: Speaker name of the user messages
+    :raises ValueError: Invalid history JSON
+    """
+
+def load_chat_textgen_file(path: str, character: typing.Optional[CharacterClass] = None, user_name: str = "You") -> ChatLog:
+    """Load TextGenerationWebUI history.json file"""
+
+def diff(a: CharacterClass, b: CharacterClass) -> CharacterDiff:
+    """Compare two characters field by field"""
+
+def merge(base: CharacterClass, ours: CharacterClass, theirs: CharacterClass) -> MergeResult:
+    """Three-way merge of character edits
+
+    Fields and lorebook entries changed on only one side (or identically
+    on both) are merged automatically, others are reported as conflicts
+    and keep their base value in the merged character.
+    """
+
+def find_duplicates(paths: typing.List[str], threshold: float = 0.8) -> typing.Tuple[typing.List[DuplicateGroup], typing.List[typing.Tuple[str, str]]]:
+    """Group exact and near-duplicate character files
+
+    Near duplicates are found with MinHash over word shingles (character
+    shingles for short texts) of personality and greeting message.
+
+    :param paths: PNG card, JSON, YAML, TOML, Markdown, .byaf or .charx file paths
+    :param threshold: Minimum estimated similarity for near duplicates
+    :return: Duplicate groups and a (path, error) pair for every file that can't be loaded
+    """
+
+def convert_many(
+    inputs: typing.List[str],
+    output_dir: str,
+    format_type: str,
+    output_kind: str,
+    metadata_mode: str = "update",
+    reproducible: bool = False,
+    timestamp: typing.Optional[int] = None,
+) -> typing.List[ConversionResult]:
+    """Convert many character files in parallel (the GIL is released)
+
+    Output files are named after the input files. Errors are reported
+    per file instead of failing the whole batch.
+
+    :param inputs: PNG card, JSON, YAML, TOML, Markdown or .byaf file paths
+    :param output_dir: Output directory (created if missing)
+    :param format_type: Target format or 'neutral'
+    :param output_kind: 'json', 'yaml', 'toml' or 'card' (PNG inputs only)
+    :param metadata_mode: 'update', 'keep' or 'append', see CharacterClass.export_json()
+    :param reproducible: Produce identical output for identical characters
+    :param timestamp: Use this timestamp (ms) instead of the current time
+    :raises ValueError: Unsupported output kind or metadata mode
+    """
+
+def lint(path: str) -> typing.List[Diagnostic]:
+    """Validate a character file (PNG card, JSON, YAML, TOML, Markdown, .byaf or .charx)
+
+    Loads the file and runs the same checks as CharacterClass.validate().
+    """
+
 def license() -> str:
     """Get license text"""
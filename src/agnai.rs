@@ -0,0 +1,212 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use schemars::JsonSchema;
+use serde::de::{MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{CharacterBook, LorebookEntry};
+use crate::persona;
+
+#[derive(Deserialize)]
+pub struct AgnaiPersona {
+    #[serde(default)]
+    pub kind: String,
+    #[serde(default)]
+    pub attributes: PersonaAttributes,
+}
+
+#[derive(Default)]
+pub struct PersonaAttributes(pub persona::Attributes);
+
+impl<'de> Deserialize<'de> for PersonaAttributes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct AttributesVisitor;
+
+        impl<'de> Visitor<'de> for AttributesVisitor {
+            type Value = PersonaAttributes;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a map of attribute names to lists of values")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut attributes = Vec::new();
+                while let Some((key, values)) = map.next_entry::<String, Vec<String>>()? {
+                    attributes.push((key, values));
+                }
+                Ok(PersonaAttributes(attributes))
+            }
+        }
+
+        deserializer.deserialize_map(AttributesVisitor)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AgnaiMemoryBook {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub entries: Vec<AgnaiMemoryEntry>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct AgnaiMemoryEntry {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub entry: String,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    #[serde(default)]
+    pub priority: i64,
+    #[serde(default)]
+    pub weight: i64,
+    #[serde(default = "crate::default_true")]
+    pub enabled: bool,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct ExportAgnaiMemoryBook<'a> {
+    kind: &'static str,
+    name: &'a str,
+    description: &'a str,
+    entries: Vec<AgnaiMemoryEntry>,
+}
+
+pub fn render_persona(name: &str, persona: &AgnaiPersona) -> String {
+    let attributes = &persona.attributes.0;
+    match persona.kind.as_str() {
+        // Structured styles can't hold double quotes, so those personas are kept as plain attribute lines instead.
+        "wpp" | "sbf" | "boostyle" => persona::render_attributes(name, attributes, &persona.kind).unwrap_or_else(|_| {
+            attributes.iter().map(|(key, values)| format!("{}: {}", key, values.join(" + "))).collect::<Vec<String>>().join("\n")
+        }),
+        _ => {
+            attributes.iter().find(|(key, _)| key == "text")
+                .or(attributes.first())
+                .and_then(|(_, values)| values.first().cloned())
+                .unwrap_or_default()
+        },
+    }
+}
+
+pub fn book_from_memory(memory: AgnaiMemoryBook) -> CharacterBook {
+    CharacterBook {
+        name: memory.name.filter(|name| !name.is_empty()),
+        description: memory.description.filter(|description| !description.is_empty()),
+        entries: memory.entries.into_iter().map(|entry| LorebookEntry {
+            keys: entry.keywords,
+            content: entry.entry,
+            enabled: entry.enabled,
+            insertion_order: entry.weight,
+            name: Some(entry.name).filter(|name| !name.is_empty()),
+            priority: Some(entry.priority),
+            ..LorebookEntry::default()
+        }).collect(),
+        ..CharacterBook::default()
+    }
+}
+
+pub fn memory_from_book(book: &CharacterBook) -> ExportAgnaiMemoryBook<'_> {
+    ExportAgnaiMemoryBook {
+        kind: "memory",
+        name: book.name.as_deref().unwrap_or_default(),
+        description: book.description.as_deref().unwrap_or_default(),
+        entries: book.entries.iter().map(|entry| AgnaiMemoryEntry {
+            name: entry.name.clone().unwrap_or_default(),
+            entry: entry.content.clone(),
+            keywords: entry.keys.clone(),
+            priority: entry.priority.unwrap_or_default(),
+            weight: entry.insertion_order,
+            enabled: entry.enabled,
+        }).collect(),
+    }
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct ExportAgnaiPersona {
+    kind: &'static str,
+    #[serde(serialize_with = "serialize_attributes")]
+    #[schemars(with = "BTreeMap<String, Vec<String>>")]
+    attributes: persona::Attributes,
+}
+
+fn serialize_attributes<S: Serializer>(attributes: &persona::Attributes, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(attributes.len()))?;
+    for (key, values) in attributes {
+        map.serialize_entry(key, values)?;
+    }
+    map.end()
+}
+
+pub fn export_persona(personality: &str) -> ExportAgnaiPersona {
+    match persona::detect_style(personality) {
+        Some(kind) => ExportAgnaiPersona { kind, attributes: persona::parse_attributes(personality) },
+        None => ExportAgnaiPersona { kind: "text", attributes: vec![("text".to_string(), vec![personality.to_string()])] },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export(personality: &str) -> serde_json::Value {
+        serde_json::to_value(export_persona(personality)).unwrap()
+    }
+
+    #[test]
+    fn structured_personas_keep_their_kind() {
+        let wpp = export("[character(\"Ann\") {\nMind(\"kind\" + \"shy\")\nLikes(\"cats\")\n}]");
+        assert_eq!(wpp, serde_json::json!({"kind": "wpp", "attributes": {"Mind": ["kind", "shy"], "Likes": ["cats"]}}));
+        let sbf = export("[ character: \"Ann\"; mind: \"kind\", \"shy\" ]");
+        assert_eq!(sbf, serde_json::json!({"kind": "sbf", "attributes": {"mind": ["kind", "shy"]}}));
+        let boostyle = export("[kind + shy + curious]");
+        assert_eq!(boostyle, serde_json::json!({"kind": "boostyle", "attributes": {"traits": ["kind", "shy", "curious"]}}));
+    }
+
+    #[test]
+    fn prose_falls_back_to_text() {
+        for personality in ["Ann is kind and shy.", "", "Likes: cats", "She is kind.\nShe likes cats + dogs."] {
+            assert_eq!(export(personality), serde_json::json!({"kind": "text", "attributes": {"text": [personality]}}), "{}", personality);
+        }
+    }
+
+    #[test]
+    fn personas_render_by_kind() {
+        let persona: AgnaiPersona = serde_json::from_str(r#"{"kind": "boostyle", "attributes": {"a": ["kind", "shy"], "b": ["curious"]}}"#).unwrap();
+        assert_eq!(render_persona("Ann", &persona), "[a: kind + shy\nb: curious]");
+        let persona: AgnaiPersona = serde_json::from_str(r#"{"kind": "text", "attributes": {"text": ["Ann is kind."]}}"#).unwrap();
+        assert_eq!(render_persona("Ann", &persona), "Ann is kind.");
+        assert!(serde_json::from_str::<AgnaiPersona>(r#"{"kind": "wpp", "attributes": {"Mind": "kind"}}"#).is_err());
+    }
+
+    #[test]
+    fn quoted_personas_keep_their_attributes() {
+        let persona: AgnaiPersona = serde_json::from_str(r#"{"kind": "wpp", "attributes": {"Mind": ["kind", "shy"], "Likes": ["cats"]}}"#).unwrap();
+        assert_eq!(render_persona("Ann \"The Blade\"", &persona), "Mind: kind + shy\nLikes: cats");
+        let persona: AgnaiPersona = serde_json::from_str(r#"{"kind": "sbf", "attributes": {"Says": ["\"hi\"", "bye"]}}"#).unwrap();
+        assert_eq!(render_persona("Ann", &persona), "Says: \"hi\" + bye");
+    }
+
+    #[test]
+    fn boostyle_persona_round_trip() {
+        let persona: AgnaiPersona = serde_json::from_str(r#"{"kind": "boostyle", "attributes": {"Mind": ["kind", "shy"], "Likes": ["cats", "dogs"]}}"#).unwrap();
+        let exported = serde_json::to_value(export_persona(&render_persona("Ann", &persona))).unwrap();
+        assert_eq!(exported, serde_json::json!({"kind": "boostyle", "attributes": {"Mind": ["kind", "shy"], "Likes": ["cats", "dogs"]}}));
+    }
+
+    #[test]
+    fn memory_book_round_trip() {
+        let memory: AgnaiMemoryBook = serde_json::from_str(r#"{"name": "World", "entries": [{"name": "Town", "entry": "A town", "keywords": ["town"], "priority": 2, "weight": 5}]}"#).unwrap();
+        let book = book_from_memory(memory);
+        assert_eq!(book.entries[0].insertion_order, 5);
+        assert!(book.entries[0].enabled);
+        let exported = serde_json::to_value(memory_from_book(&book)).unwrap();
+        assert_eq!(exported, serde_json::json!({"kind": "memory", "name": "World", "description": "", "entries": [
+            {"name": "Town", "entry": "A town", "keywords": ["town"], "priority": 2, "weight": 5, "enabled": true}
+        ]}));
+    }
+}
@@ -0,0 +1,423 @@
+use std::io::{Cursor, Write};
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use pyo3::prelude::*;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{CharacterBook, CharacterClass, ExportOptions, LorebookEntry, history};
+use crate::card_reader::{LoadLimits, check_json_depth, read_archive_file};
+
+const SAMPLER_SETTINGS: [&str; 9] = ["minP", "minPEnabled", "temperature", "repeatPenalty", "repeatLastN", "topK", "topP", "promptTemplate", "grammar"];
+
+pub const DEFAULT_BASE_PROMPT: &str = "Text transcript of a never-ending conversation between {user} and {character}. In the transcript, gestures and other non-verbal actions are written between asterisks (for example, *waves hello* or *moves closer*).";
+
+#[derive(Deserialize)]
+pub struct BackyardLoreItem {
+    #[serde(default)]
+    key: String,
+    #[serde(default)]
+    value: String,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct ExportBackyardLoreItem<'a> {
+    key: String,
+    value: &'a str,
+}
+
+#[derive(Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportBackyardCharacter<'a> {
+    ai_name: &'a str,
+    ai_display_name: &'a str,
+    ai_persona: &'a str,
+    base_prompt: &'a str,
+    custom_dialogue: &'a str,
+    first_message: &'a str,
+    scenario: &'a str,
+    lore_items: Vec<ExportBackyardLoreItem<'a>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ByafManifest {
+    character: String,
+    #[serde(default)]
+    scenarios: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ByafCharacter {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    display_name: String,
+    #[serde(default)]
+    persona: String,
+    created_at: Option<String>,
+    updated_at: Option<String>,
+    #[serde(default)]
+    lore_items: Vec<BackyardLoreItem>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ByafScenario {
+    #[serde(default)]
+    narrative: String,
+    formatting_instructions: Option<String>,
+    #[serde(default)]
+    first_messages: Vec<ByafMessage>,
+    #[serde(default)]
+    example_messages: Vec<ByafMessage>,
+    #[serde(flatten)]
+    other: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct ByafMessage {
+    #[serde(default)]
+    text: String,
+}
+
+pub fn base_prompt(character: &CharacterClass) -> &str {
+    character.extensions.get("backyard")
+        .and_then(|backyard| backyard.get("basePrompt"))
+        .and_then(|prompt| prompt.as_str())
+        .unwrap_or(DEFAULT_BASE_PROMPT)
+}
+
+pub fn backyard_character(character: &CharacterClass) -> ExportBackyardCharacter<'_> {
+    ExportBackyardCharacter {
+        ai_name: &character.name,
+        ai_display_name: &character.name,
+        ai_persona: &character.personality,
+        base_prompt: base_prompt(character),
+        custom_dialogue: &character.example_messages,
+        first_message: &character.greeting_message,
+        scenario: &character.scenario,
+        lore_items: character.character_book.iter()
+            .flat_map(|book| &book.entries)
+            .map(|entry| ExportBackyardLoreItem { key: entry.keys.join(", "), value: &entry.content })
+            .collect(),
+    }
+}
+
+pub fn book_from_lore_items(items: Vec<BackyardLoreItem>) -> Option<CharacterBook> {
+    if items.is_empty() {
+        return None;
+    }
+    Some(CharacterBook {
+        entries: items.into_iter().enumerate().map(|(index, item)| LorebookEntry {
+            keys: item.key.split(',').map(|key| key.trim().to_string()).filter(|key| !key.is_empty()).collect(),
+            content: item.value,
+            enabled: true,
+            insertion_order: index as i64,
+            ..LorebookEntry::default()
+        }).collect(),
+        ..CharacterBook::default()
+    })
+}
+
+pub fn base_prompt_extension(base_prompt: String) -> serde_json::Map<String, serde_json::Value> {
+    let mut extensions = serde_json::Map::new();
+    if base_prompt != DEFAULT_BASE_PROMPT {
+        extensions.insert("backyard".to_string(), serde_json::json!({ "basePrompt": base_prompt }));
+    }
+    extensions
+}
+
+pub fn export_byaf(character: &CharacterClass, options: &ExportOptions) -> PyResult<Vec<u8>> {
+    let current_time = options.current_time(character);
+    let created = format_time(character.created_time.unwrap_or(current_time));
+    let updated = format_time(current_time);
+    let character_id = history::content_hash(character)[..32].to_string();
+
+    let mut images = Vec::new();
+    let image = match &character.image_path {
+        Some(path) => {
+            let bytes = std::fs::read(path).map_err(|e| pyo3::exceptions::PyIOError::new_err(format!("Failed to open image file '{}': {}", path, e)))?;
+            images.push(serde_json::json!({ "path": "images/avatar.png", "label": "" }));
+            Some(bytes)
+        },
+        None => None,
+    };
+
+    let character_document = serde_json::json!({
+        "schemaVersion": 1,
+        "id": character_id,
+        "name": character.name,
+        "displayName": character.name,
+        "isNSFW": false,
+        "persona": character.personality,
+        "createdAt": created,
+        "updatedAt": updated,
+        "loreItems": backyard_character(character).lore_items.iter().enumerate().map(|(index, item)| serde_json::json!({
+            "id": index.to_string(),
+            "key": item.key,
+            "value": item.value,
+        })).collect::<Vec<serde_json::Value>>(),
+        "images": images,
+    });
+    let first_messages: Vec<serde_json::Value> = std::iter::once(&character.greeting_message)
+        .chain(&character.alternate_greetings)
+        .filter(|text| !text.is_empty())
+        .map(|text| serde_json::json!({ "characterID": character_id, "text": text }))
+        .collect();
+    let example_messages: Vec<serde_json::Value> = Some(&character.example_messages)
+        .filter(|text| !text.is_empty())
+        .map(|text| serde_json::json!({ "characterID": character_id, "text": text }))
+        .into_iter().collect();
+    let mut scenario = serde_json::json!({
+        "schemaVersion": 1,
+        "title": character.name,
+        "narrative": character.scenario,
+        "formattingInstructions": base_prompt(character),
+        "minP": 0.1,
+        "minPEnabled": true,
+        "temperature": 1.2,
+        "repeatPenalty": 1.05,
+        "repeatLastN": 256,
+        "topK": 30,
+        "topP": 0.9,
+        "firstMessages": first_messages,
+        "exampleMessages": example_messages,
+        "canDeleteExampleMessages": false,
+        "promptTemplate": null,
+        "grammar": null,
+        "messages": [],
+    });
+    let backyard = character.extensions.get("backyard");
+    if let Some(serde_json::Value::Object(settings)) = backyard.and_then(|backyard| backyard.get("samplerSettings")) {
+        for (key, value) in settings.iter().filter(|(key, _)| SAMPLER_SETTINGS.contains(&key.as_str())) {
+            scenario[key] = value.clone();
+        }
+    }
+    let extra_scenarios = match backyard.and_then(|backyard| backyard.get("scenarios")) {
+        Some(serde_json::Value::Array(scenarios)) => scenarios.iter().filter(|scenario| scenario.is_object()).collect(),
+        _ => Vec::new(),
+    };
+    let scenario_paths: Vec<String> = (1..=extra_scenarios.len() + 1).map(|number| format!("scenarios/scenario{}.json", number)).collect();
+    let manifest = serde_json::json!({
+        "schemaVersion": 1,
+        "createdAt": updated,
+        "character": "character/character.json",
+        "scenarios": scenario_paths,
+    });
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let file_options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut files = vec![
+        ("manifest.json", serde_json::to_vec_pretty(&manifest).expect("Error while serializing JSON")),
+        ("character/character.json", serde_json::to_vec_pretty(&character_document).expect("Error while serializing JSON")),
+    ];
+    for (path, scenario) in scenario_paths.iter().zip(std::iter::once(&scenario).chain(extra_scenarios)) {
+        files.push((path, serde_json::to_vec_pretty(scenario).expect("Error while serializing JSON")));
+    }
+    if let Some(image) = image {
+        files.push(("character/images/avatar.png", image));
+    }
+    for (name, bytes) in files {
+        writer.start_file(name, file_options).map_err(archive_error)?;
+        writer.write_all(&bytes)?;
+    }
+    Ok(writer.finish().map_err(archive_error)?.into_inner())
+}
+
+pub fn load_byaf(bytes: &[u8], limits: &LoadLimits) -> PyResult<CharacterClass> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).map_err(archive_error)?;
+    let mut total_size = 0;
+    let manifest: ByafManifest = read_json(&mut archive, "manifest.json", limits, &mut total_size)?;
+    let byaf_character: ByafCharacter = read_json(&mut archive, &manifest.character, limits, &mut total_size)?;
+    let scenario: ByafScenario = match manifest.scenarios.first() {
+        Some(path) => read_json(&mut archive, path, limits, &mut total_size)?,
+        None => ByafScenario::default(),
+    };
+    let extra_scenarios = manifest.scenarios.iter().skip(1)
+        .map(|path| read_json(&mut archive, path, limits, &mut total_size))
+        .collect::<PyResult<Vec<serde_json::Value>>>()?;
+
+    let mut first_messages = scenario.first_messages.into_iter().map(|message| message.text);
+    let greeting_message = first_messages.next().unwrap_or_default();
+    let example_messages = scenario.example_messages.into_iter().map(|message| message.text).collect::<Vec<String>>().join("\n");
+    let name = if byaf_character.display_name.is_empty() { byaf_character.name } else { byaf_character.display_name };
+
+    let mut character = CharacterClass::default()
+        .with_name(name)
+        .with_personality(byaf_character.persona)
+        .with_scenario(scenario.narrative)
+        .with_greeting_message(greeting_message)
+        .with_example_messages(example_messages)
+        .with_alternate_greetings(first_messages.collect())
+        .with_character_book(book_from_lore_items(byaf_character.lore_items))
+        .with_extensions(base_prompt_extension(scenario.formatting_instructions.unwrap_or_else(|| DEFAULT_BASE_PROMPT.to_string())));
+    let sampler_settings: serde_json::Map<String, serde_json::Value> = scenario.other.into_iter()
+        .filter(|(key, _)| SAMPLER_SETTINGS.contains(&key.as_str()))
+        .collect();
+    for (key, value) in [("samplerSettings", serde_json::Value::Object(sampler_settings)), ("scenarios", serde_json::Value::Array(extra_scenarios))] {
+        let empty = value.as_object().is_some_and(serde_json::Map::is_empty) || value.as_array().is_some_and(Vec::is_empty);
+        if !empty {
+            let backyard = character.extensions.entry("backyard").or_insert_with(|| serde_json::json!({}));
+            backyard[key] = value;
+        }
+    }
+    character.created_time = byaf_character.created_at.as_deref().and_then(parse_time);
+    character.modified_time = byaf_character.updated_at.as_deref().and_then(parse_time);
+    Ok(character)
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(archive: &mut ZipArchive<Cursor<&[u8]>>, path: &str, limits: &LoadLimits, total_size: &mut u64) -> PyResult<T> {
+    let data = read_archive_file(archive, path, limits, total_size)?;
+    let text = String::from_utf8(data)
+        .map_err(|_| pyo3::exceptions::PyValueError::new_err(format!("Backyard archive file '{}' is not valid UTF-8", path)))?;
+    check_json_depth(&text, limits)?;
+    serde_json::from_str(&text)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Backyard archive file '{}' does not contain correct json data: {}", path, e)))
+}
+
+fn format_time(milliseconds: i64) -> String {
+    Utc.timestamp_millis_opt(milliseconds).single().unwrap_or_default().to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn parse_time(text: &str) -> Option<i64> {
+    DateTime::parse_from_rfc3339(text).ok().map(|time| time.timestamp_millis())
+}
+
+fn archive_error(error: zip::result::ZipError) -> PyErr {
+    pyo3::exceptions::PyValueError::new_err(format!("Backyard archive error: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in files {
+            writer.start_file(*name, SimpleFileOptions::default()).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn manifest() -> &'static [u8] {
+        br#"{"character": "character/character.json", "scenarios": ["scenarios/scenario1.json"]}"#
+    }
+
+    fn character() -> CharacterClass {
+        let book = CharacterBook {
+            entries: vec![LorebookEntry { keys: vec!["town".to_string(), "city".to_string()], content: "A town".to_string(), enabled: true, ..LorebookEntry::default() }],
+            ..CharacterBook::default()
+        };
+        CharacterClass::default()
+            .with_name("Ann")
+            .with_personality("Kind")
+            .with_scenario("A town")
+            .with_greeting_message("Hi")
+            .with_alternate_greetings(vec!["Hello".to_string()])
+            .with_example_messages("{user}: Hi\n{character}: Hello")
+            .with_character_book(Some(book))
+            .with_extensions(base_prompt_extension("Custom prompt".to_string()))
+    }
+
+    #[test]
+    fn byaf_round_trip() {
+        let character = character();
+        let options = ExportOptions::new("keep", true, Some(1_700_000_000_000)).unwrap();
+        let bytes = export_byaf(&character, &options).unwrap();
+        assert_eq!(bytes, export_byaf(&character, &options).unwrap());
+
+        let loaded = load_byaf(&bytes, &LoadLimits::default()).unwrap();
+        assert_eq!(loaded.name, "Ann");
+        assert_eq!(loaded.personality, "Kind");
+        assert_eq!(loaded.scenario, "A town");
+        assert_eq!(loaded.greeting_message, "Hi");
+        assert_eq!(loaded.alternate_greetings, ["Hello"]);
+        assert_eq!(loaded.example_messages, character.example_messages);
+        assert_eq!(loaded.character_book.as_ref().unwrap().entries[0].keys, ["town", "city"]);
+        assert_eq!(base_prompt(&loaded), "Custom prompt");
+        assert_eq!(loaded.modified_time, Some(1_700_000_000_000));
+        assert_eq!(loaded.extensions["backyard"]["samplerSettings"]["temperature"], 1.2);
+        assert!(loaded.extensions["backyard"].get("scenarios").is_none());
+    }
+
+    #[test]
+    fn sampler_settings_and_extra_scenarios_survive_a_round_trip() {
+        let scenario = br#"{"narrative": "A town", "temperature": 0.7, "topK": 40, "title": "Main"}"#;
+        let bytes = archive(&[
+            ("manifest.json", br#"{"character": "character.json", "scenarios": ["one.json", "two.json"]}"#),
+            ("character.json", br#"{"name": "Ann"}"#),
+            ("one.json", scenario),
+            ("two.json", br#"{"narrative": "A castle", "title": "Second"}"#),
+        ]);
+        let loaded = load_byaf(&bytes, &LoadLimits::default()).unwrap();
+        assert_eq!(loaded.scenario, "A town");
+        assert_eq!(loaded.extensions["backyard"]["samplerSettings"], serde_json::json!({"temperature": 0.7, "topK": 40}));
+        assert_eq!(loaded.extensions["backyard"]["scenarios"][0]["narrative"], "A castle");
+
+        let options = ExportOptions::new("keep", true, Some(1_700_000_000_000)).unwrap();
+        let reloaded = load_byaf(&export_byaf(&loaded, &options).unwrap(), &LoadLimits::default()).unwrap();
+        assert_eq!(reloaded.extensions["backyard"]["samplerSettings"]["temperature"], 0.7);
+        assert_eq!(reloaded.extensions["backyard"]["samplerSettings"]["topK"], 40);
+        assert_eq!(reloaded.extensions["backyard"]["samplerSettings"]["topP"], 0.9);
+        assert_eq!(reloaded.extensions["backyard"]["scenarios"], loaded.extensions["backyard"]["scenarios"]);
+    }
+
+    #[test]
+    fn default_base_prompt_is_not_kept_as_extension() {
+        assert!(base_prompt_extension(DEFAULT_BASE_PROMPT.to_string()).is_empty());
+        assert_eq!(base_prompt(&CharacterClass::default()), DEFAULT_BASE_PROMPT);
+        assert!(book_from_lore_items(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn malformed_archives_are_rejected() {
+        pyo3::prepare_freethreaded_python();
+        let limits = LoadLimits::default();
+        assert!(load_byaf(b"not a zip", &limits).is_err());
+        assert!(load_byaf(&archive(&[("character/character.json", b"{}")]), &limits).is_err());
+        let error = load_byaf(&archive(&[("manifest.json", manifest()), ("character/character.json", b"{")]), &limits).err().unwrap();
+        assert!(error.to_string().contains("does not contain correct json data"));
+        let error = load_byaf(&archive(&[("manifest.json", manifest()), ("character/character.json", &[0xff, 0xfe])]), &limits).err().unwrap();
+        assert!(error.to_string().contains("not valid UTF-8"));
+
+        let loaded = load_byaf(&archive(&[("manifest.json", br#"{"character": "./character.json"}"#), ("character.json", br#"{"name": "Ann"}"#)]), &limits).unwrap();
+        assert_eq!(loaded.name, "Ann");
+        assert!(loaded.greeting_message.is_empty());
+    }
+
+    #[test]
+    fn decompressed_size_and_json_depth_are_limited() {
+        pyo3::prepare_freethreaded_python();
+        let padding = format!(r#"{{"name": "Ann", "persona": "{}"}}"#, " ".repeat(1_000_000));
+        let bytes = archive(&[("manifest.json", br#"{"character": "character.json"}"#), ("character.json", padding.as_bytes())]);
+        assert!(bytes.len() < 100_000);
+        let limits = LoadLimits { max_file_size: Some(64 * 1024), ..LoadLimits::default() };
+        let error = load_byaf(&bytes, &limits).err().unwrap();
+        assert!(error.to_string().contains("limit of 65536 bytes"));
+        assert!(load_byaf(&bytes, &LoadLimits { max_file_size: None, ..LoadLimits::default() }).is_ok());
+
+        let nested = format!(r#"{{"name": "Ann", "loreItems": {}}}"#, "[".repeat(100) + &"]".repeat(100));
+        let bytes = archive(&[("manifest.json", br#"{"character": "character.json"}"#), ("character.json", nested.as_bytes())]);
+        assert!(load_byaf(&bytes, &LoadLimits { max_json_depth: Some(32), ..LoadLimits::default() }).is_err());
+    }
+
+    #[test]
+    fn losses_depend_on_target() {
+        let character = character().with_summary("Summary").with_tags(vec!["tag".to_string()]);
+        let backyard = crate::losses::export_losses(&character, "backyard").unwrap();
+        assert!(backyard.contains(&"alternate_greetings".to_string()));
+        let byaf = crate::losses::export_losses(&character, "byaf").unwrap();
+        assert!(byaf.contains(&"summary".to_string()) && byaf.contains(&"tags".to_string()));
+        assert!(!byaf.contains(&"alternate_greetings".to_string()));
+        assert!(!byaf.contains(&"extensions".to_string()));
+
+        let mut settings = character.clone();
+        settings.extensions.insert("backyard".to_string(), serde_json::json!({"basePrompt": "Custom prompt", "samplerSettings": {"topK": 40}}));
+        assert!(crate::losses::export_losses(&settings, "backyard").unwrap().contains(&"extensions".to_string()));
+        assert!(!crate::losses::export_losses(&settings, "byaf").unwrap().contains(&"extensions".to_string()));
+    }
+}
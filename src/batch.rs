@@ -0,0 +1,148 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use pyo3::prelude::*;
+use rayon::prelude::*;
+
+use crate::{ExportOptions, export_as_card, export_as_json, export_as_toml, export_as_yaml, load_character_path};
+
+#[derive(Clone)]
+#[pyclass]
+pub struct ConversionResult {
+    #[pyo3(get)]
+    input: String,
+    #[pyo3(get)]
+    output: Option<String>,
+    #[pyo3(get)]
+    error: Option<String>,
+}
+
+#[pymethods]
+impl ConversionResult {
+    fn ok(&self) -> bool {
+        self.error.is_none()
+    }
+
+    fn __repr__(&self) -> String {
+        match &self.error {
+            Some(error) => format!("<ConversionResult {} failed: {}>", self.input, error),
+            None => format!("<ConversionResult {} -> {}>", self.input, self.output.as_deref().unwrap_or_default()),
+        }
+    }
+}
+
+pub fn convert_many(py: Python, inputs: Vec<String>, output_dir: &str, format_type: &str, output_kind: &str, options: &ExportOptions) -> PyResult<Vec<ConversionResult>> {
+    let extension = match output_kind.to_lowercase().as_str() {
+        "json" => "json",
+        "yaml" => "yaml",
+        "toml" => "toml",
+        "card" | "png" => "png",
+        _ => {
+            return Err(pyo3::exceptions::PyValueError::new_err("Output kind not supported, supported kinds: 'json', 'yaml', 'toml', 'card'"));
+        }
+    };
+    fs::create_dir_all(output_dir)?;
+
+    let mut used_outputs = HashSet::new();
+    let jobs: Vec<(String, Option<PathBuf>)> = inputs.into_iter().map(|input| {
+        let stem = Path::new(&input).file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+        let output = Path::new(output_dir).join(format!("{}.{}", stem, extension));
+        let output = if used_outputs.insert(output.clone()) { Some(output) } else { None };
+        (input, output)
+    }).collect();
+
+    let results: Vec<(String, Option<String>, Result<(), PyErr>)> = py.allow_threads(|| {
+        jobs.into_par_iter().map(|(input, output)| {
+            let Some(output) = output else {
+                let error = pyo3::exceptions::PyValueError::new_err("Another input file is converted to the same output path");
+                return (input, None, Err(error));
+            };
+            let output_path = output.to_string_lossy().to_string();
+            let result = convert_file(&input, &output, format_type, extension, options);
+            (input, Some(output_path), result)
+        }).collect()
+    });
+
+    Ok(results.into_iter().map(|(input, output, result)| match result {
+        Ok(()) => ConversionResult { input, output, error: None },
+        Err(e) => ConversionResult { input, output: None, error: Some(e.value(py).to_string()) },
+    }).collect())
+}
+
+fn convert_file(input: &str, output: &Path, format_type: &str, extension: &str, options: &ExportOptions) -> PyResult<()> {
+    let character = load_character_path(input)?;
+    let bytes = match extension {
+        "json" => export_as_json(&character, format_type, options)?.into_bytes(),
+        "yaml" => export_as_yaml(&character, format_type, options)?.into_bytes(),
+        "toml" => export_as_toml(&character, Some(format_type), options)?.into_bytes(),
+        _ => export_as_card(&character, format_type, options)?,
+    };
+    fs::write(output, bytes)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempPath;
+
+    fn directory(name: &str) -> TempPath {
+        let directory = TempPath::new(&format!("batch_{}", name));
+        fs::create_dir_all(directory.path().join("other")).unwrap();
+        directory
+    }
+
+    fn convert(inputs: Vec<String>, output_dir: &Path, format_type: &str, output_kind: &str) -> PyResult<Vec<ConversionResult>> {
+        pyo3::prepare_freethreaded_python();
+        let options = ExportOptions::new("update", false, None)?;
+        Python::with_gil(|py| convert_many(py, inputs, output_dir.to_str().unwrap(), format_type, output_kind, &options))
+    }
+
+    #[test]
+    fn converts_files_and_reports_errors_per_file() {
+        let temp = directory("convert");
+        let directory = temp.path();
+        let good = directory.join("ann.json");
+        let duplicate = directory.join("other/ann.yaml");
+        let broken = directory.join("broken.json");
+        fs::write(&good, r#"{"name": "Ann", "first_mes": "Hi"}"#).unwrap();
+        fs::write(&duplicate, "name: Ann\n").unwrap();
+        fs::write(&broken, "{").unwrap();
+        let inputs = [&good, &duplicate, &broken].iter().map(|path| path.to_str().unwrap().to_string()).collect();
+
+        let output_dir = directory.join("out");
+        let results = convert(inputs, &output_dir, "tavernai", "yaml").unwrap();
+        assert_eq!(results.iter().map(ConversionResult::ok).collect::<Vec<bool>>(), [true, false, false]);
+        assert_eq!(results[0].output.as_deref(), output_dir.join("ann.yaml").to_str());
+        assert!(results[1].error.as_deref().unwrap().contains("same output path"));
+        assert!(results[2].output.is_none());
+        assert!(fs::read_to_string(output_dir.join("ann.yaml")).unwrap().contains("name: Ann"));
+    }
+
+    #[test]
+    fn cards_without_image_fail_and_unknown_kinds_are_rejected() {
+        let temp = directory("cards");
+        let directory = temp.path();
+        let input = directory.join("ann.json");
+        fs::write(&input, r#"{"name": "Ann"}"#).unwrap();
+        let results = convert(vec![input.to_str().unwrap().to_string()], &directory.join("out"), "chara_card_v2", "card").unwrap();
+        assert!(results[0].error.as_deref().unwrap().contains("png file"));
+        assert!(convert(Vec::new(), directory, "tavernai", "xml").is_err());
+    }
+
+    #[test]
+    fn export_options_apply_to_every_file() {
+        pyo3::prepare_freethreaded_python();
+        let temp = directory("options");
+        let input = temp.path().join("ann.json");
+        fs::write(&input, r#"{"name": "Ann", "metadata": {"created": 5, "modified": 6}}"#).unwrap();
+        let output_dir = temp.path().join("out");
+        let options = ExportOptions::new("keep", true, Some(1_700_000_000_000)).unwrap();
+        let results = Python::with_gil(|py| {
+            convert_many(py, vec![input.to_str().unwrap().to_string()], output_dir.to_str().unwrap(), "tavernai", "json", &options)
+        }).unwrap();
+        assert!(results[0].ok());
+        let output: serde_json::Value = serde_json::from_str(&fs::read_to_string(output_dir.join("ann.json")).unwrap()).unwrap();
+        assert_eq!((output["metadata"]["created"].as_i64(), output["metadata"]["modified"].as_i64()), (Some(5), Some(6)));
+    }
+}
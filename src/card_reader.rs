@@ -0,0 +1,611 @@
+use std::collections::BTreeMap;
+use std::io::{self, Read, Seek};
+use pyo3::prelude::*;
+use base64::{Engine, engine::GeneralPurpose, engine::GeneralPurposeConfig, alphabet::STANDARD};
+use flate2::read::ZlibDecoder;
+use serde::Deserialize;
+use zip::ZipArchive;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+pub const ASSET_CHUNK_PREFIX: &str = "chara-ext-asset_";
+pub const ASSET_URI_PREFIX: &str = "__asset:";
+// PNG keywords are 1 to 79 bytes long, followed by a null separator.
+const MAX_KEYWORD_LENGTH: u64 = 79;
+
+pub const CHARA_NOT_FOUND_MESSAGE: &str = r#"Failed to find 'chara' metadata (tEXt chunk) in the PNG file. This may occur due to:
+            1. The file is not a valid character card (Tavern Card V1 format).
+            2. The file may be corrupted or incomplete.
+            3. The character data might be stored in a different format.
+
+            Please ensure that:
+            - You are using a file created by a compatible character creation tool.
+            - The file hasn't been modified or damaged.
+            - You are using the correct file format for your character data.
+
+            If the problem persists, try re-exporting the character from its original creation tool."#;
+
+#[derive(Clone)]
+#[pyclass]
+pub struct LoadLimits {
+    #[pyo3(get, set)]
+    pub max_file_size: Option<u64>,
+    #[pyo3(get, set)]
+    pub max_text_chunk_size: Option<u64>,
+    #[pyo3(get, set)]
+    pub max_pixels: Option<u64>,
+    #[pyo3(get, set)]
+    pub max_json_depth: Option<usize>,
+}
+
+impl Default for LoadLimits {
+    fn default() -> Self {
+        LoadLimits {
+            max_file_size: Some(64 * 1024 * 1024),
+            max_text_chunk_size: Some(16 * 1024 * 1024),
+            max_pixels: Some(100_000_000),
+            max_json_depth: Some(64),
+        }
+    }
+}
+
+#[pymethods]
+impl LoadLimits {
+    #[new]
+    #[pyo3(signature = (max_file_size = Some(64 * 1024 * 1024), max_text_chunk_size = Some(16 * 1024 * 1024), max_pixels = Some(100_000_000), max_json_depth = Some(64)))]
+    fn new(max_file_size: Option<u64>, max_text_chunk_size: Option<u64>, max_pixels: Option<u64>, max_json_depth: Option<usize>) -> Self {
+        LoadLimits { max_file_size, max_text_chunk_size, max_pixels, max_json_depth }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<LoadLimits max_file_size={} max_text_chunk_size={} max_pixels={} max_json_depth={}>",
+            limit_repr(self.max_file_size), limit_repr(self.max_text_chunk_size), limit_repr(self.max_pixels), limit_repr(self.max_json_depth))
+    }
+}
+
+fn limit_repr<T: ToString>(limit: Option<T>) -> String {
+    limit.map(|limit| limit.to_string()).unwrap_or_else(|| "None".to_string())
+}
+
+#[derive(Clone)]
+#[pyclass]
+pub struct CardInfo {
+    #[pyo3(get)]
+    path: String,
+    #[pyo3(get)]
+    name: String,
+    #[pyo3(get)]
+    tags: Vec<String>,
+    #[pyo3(get)]
+    spec_version: Option<String>,
+}
+
+#[pymethods]
+impl CardInfo {
+    fn __repr__(&self) -> String {
+        format!("<CardInfo {} ({})>", self.name, self.path)
+    }
+}
+
+// Only the fields a CardInfo needs are deserialized, serde skips everything else without building it.
+#[derive(Deserialize)]
+struct PeekCharacter {
+    name: Option<String>,
+    char_name: Option<String>,
+    tags: Option<Vec<String>>,
+    spec_version: Option<serde_json::Value>,
+    data: Option<Box<PeekCharacter>>,
+}
+
+pub fn peek_card(path: &str, limits: &LoadLimits) -> PyResult<CardInfo> {
+    let file = std::fs::File::open(path)?;
+    if let Some(max_file_size) = limits.max_file_size {
+        if file.metadata()?.len() > max_file_size {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!("PNG file is larger than the limit of {} bytes", max_file_size)));
+        }
+    }
+    let character_text = read_card_json(io::BufReader::new(file), limits)?;
+    let character: PeekCharacter = serde_json::from_str(&character_text)
+        .map_err(|_| pyo3::exceptions::PyValueError::new_err("Your image file does not contain correct json data"))?;
+    let (data_name, data_tags) = match character.data {
+        Some(data) => (data.name.or(data.char_name), data.tags),
+        None => (None, None),
+    };
+    Ok(CardInfo {
+        path: path.to_string(),
+        name: data_name.or(character.name).or(character.char_name).unwrap_or_default(),
+        tags: data_tags.or(character.tags).unwrap_or_default(),
+        // Some tools write the version as a number, e.g. 2.0 instead of "2.0", which serde_json prints back as written.
+        spec_version: character.spec_version.and_then(|version| match version {
+            serde_json::Value::String(version) => Some(version),
+            serde_json::Value::Number(version) => Some(version.to_string()),
+            _ => None,
+        }),
+    })
+}
+
+// Bytes beyond the requested size (some file-like objects return more than asked for) are kept for the next read.
+pub struct PyStreamReader<'py> {
+    stream: &'py PyAny,
+    pending: Vec<u8>,
+    position: usize,
+}
+
+impl<'py> PyStreamReader<'py> {
+    pub fn new(stream: &'py PyAny) -> Self {
+        PyStreamReader { stream, pending: Vec::new(), position: 0 }
+    }
+}
+
+impl Read for PyStreamReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position == self.pending.len() {
+            let data = self.stream.call_method1("read", (buf.len(),)).map_err(io::Error::other)?;
+            let bytes: &[u8] = data.extract().map_err(io::Error::other)?;
+            self.pending.clear();
+            self.pending.extend_from_slice(bytes);
+            self.position = 0;
+        }
+        let length = (self.pending.len() - self.position).min(buf.len());
+        buf[..length].copy_from_slice(&self.pending[self.position..self.position + length]);
+        self.position += length;
+        Ok(length)
+    }
+}
+
+pub struct Card {
+    pub json: String,
+    pub assets: BTreeMap<String, String>,
+}
+
+/// What the chunk walk does after a wanted text chunk was visited.
+enum Walk {
+    Continue,
+    // The character chunk was found, a `ccv3` chunk could still follow before the image data.
+    StopAtImageData,
+    Stop,
+}
+
+pub fn read_card_json<R: Read>(reader: R, limits: &LoadLimits) -> PyResult<String> {
+    let mut chara = None;
+    let mut ccv3 = None;
+    walk_text_chunks(reader, limits, |keyword| matches!(keyword, b"chara" | b"ccv3"), |keyword, text| {
+        if keyword == b"ccv3" {
+            ccv3 = Some(text);
+            return Walk::Stop;
+        }
+        chara.get_or_insert(text);
+        Walk::StopAtImageData
+    })?;
+    decode_card_text(ccv3.or(chara), limits)
+}
+
+// Asset chunks are written before the character chunks, so the walk ends with the character like read_card_json().
+pub fn read_card<R: Read>(reader: R, limits: &LoadLimits) -> PyResult<Card> {
+    let mut chara = None;
+    let mut ccv3 = None;
+    let mut assets = BTreeMap::new();
+    let wanted = |keyword: &[u8]| matches!(keyword, b"chara" | b"ccv3") || keyword.starts_with(ASSET_CHUNK_PREFIX.as_bytes());
+    walk_text_chunks(reader, limits, wanted, |keyword, text| {
+        match keyword {
+            b"ccv3" => {
+                ccv3 = Some(text);
+                Walk::Stop
+            },
+            b"chara" => {
+                chara.get_or_insert(text);
+                Walk::StopAtImageData
+            },
+            _ => {
+                let id = String::from_utf8_lossy(&keyword[ASSET_CHUNK_PREFIX.len()..]).trim_start_matches(':').to_string();
+                assets.insert(format!("{}{}", ASSET_URI_PREFIX, id), text.trim().to_string());
+                Walk::Continue
+            },
+        }
+    })?;
+    Ok(Card { json: decode_card_text(ccv3.or(chara), limits)?, assets })
+}
+
+fn decode_card_text(character_base64: Option<String>, limits: &LoadLimits) -> PyResult<String> {
+    let character_base64 = character_base64.ok_or_else(|| pyo3::exceptions::PyValueError::new_err(CHARA_NOT_FOUND_MESSAGE))?;
+    let engine = GeneralPurpose::new(&STANDARD, GeneralPurposeConfig::new());
+    let character_bytes = engine.decode(character_base64.trim())
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Error while decoding base64 character data from character card: {:?}", e)))?;
+    let character_text = String::from_utf8(character_bytes)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Error while parsing decoded base64 bytes to utf8 string: {:?}", e.utf8_error())))?;
+    check_json_depth(&character_text, limits)?;
+    Ok(character_text)
+}
+
+pub fn check_json_depth(text: &str, limits: &LoadLimits) -> PyResult<()> {
+    let Some(max_depth) = limits.max_json_depth else {
+        return Ok(());
+    };
+    let mut depth: usize = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for byte in text.bytes() {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {},
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => {
+                depth += 1;
+                if depth > max_depth {
+                    return Err(pyo3::exceptions::PyValueError::new_err(format!("Character JSON data is nested deeper than the limit of {} levels", max_depth)));
+                }
+            },
+            b'}' | b']' => depth = depth.saturating_sub(1),
+            _ => {},
+        }
+    }
+    Ok(())
+}
+
+// Walks the chunk list without ever inflating IDAT, image data is only skipped over.
+// Text chunks are skipped unread unless their keyword is wanted, then they are decoded and passed to `visit`.
+fn walk_text_chunks<R: Read>(mut reader: R, limits: &LoadLimits, wanted: impl Fn(&[u8]) -> bool, mut visit: impl FnMut(&[u8], String) -> Walk) -> PyResult<()> {
+    let mut signature = [0u8; 8];
+    reader.read_exact(&mut signature).map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Failed to read PNG info: {}", e)))?;
+    if signature != PNG_SIGNATURE {
+        return Err(pyo3::exceptions::PyValueError::new_err("Failed to read PNG info: invalid PNG signature"));
+    }
+
+    let mut total_size: u64 = PNG_SIGNATURE.len() as u64;
+    let mut stop_at_image_data = false;
+    loop {
+        let mut header = [0u8; 8];
+        if let Err(e) = reader.read_exact(&mut header) {
+            return match e.kind() {
+                io::ErrorKind::UnexpectedEof => Ok(()),
+                _ => Err(pyo3::exceptions::PyIOError::new_err(format!("Failed to read PNG chunk: {}", e))),
+            };
+        }
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        let chunk_type = [header[4], header[5], header[6], header[7]];
+
+        total_size += 12 + length;
+        if let Some(max_file_size) = limits.max_file_size {
+            if total_size > max_file_size {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!("PNG file is larger than the limit of {} bytes", max_file_size)));
+            }
+        }
+
+        match &chunk_type {
+            b"IHDR" => {
+                let data = read_chunk_data(&mut reader, length)?;
+                if data.len() >= 8 {
+                    let width = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as u64;
+                    let height = u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as u64;
+                    if let Some(max_pixels) = limits.max_pixels {
+                        if width * height > max_pixels {
+                            return Err(pyo3::exceptions::PyValueError::new_err(format!("PNG image has {}x{} pixels, more than the limit of {} pixels", width, height, max_pixels)));
+                        }
+                    }
+                }
+            },
+            b"tEXt" | b"zTXt" | b"iTXt" => {
+                let mut keyword = Vec::new();
+                reader.by_ref().take(length.min(MAX_KEYWORD_LENGTH + 1)).read_to_end(&mut keyword)?;
+                let Some(separator) = keyword.iter().position(|&byte| byte == 0) else {
+                    skip_chunk_data(&mut reader, length - keyword.len() as u64 + 4)?;
+                    continue;
+                };
+                if !wanted(&keyword[..separator]) {
+                    skip_chunk_data(&mut reader, length - keyword.len() as u64 + 4)?;
+                    continue;
+                }
+                if let Some(max_text_chunk_size) = limits.max_text_chunk_size {
+                    if length > max_text_chunk_size {
+                        return Err(pyo3::exceptions::PyValueError::new_err(format!("PNG text chunk is larger than the limit of {} bytes", max_text_chunk_size)));
+                    }
+                }
+                let mut data = keyword;
+                data.extend(read_chunk_data(&mut reader, length - data.len() as u64)?);
+                match visit(&data[..separator], chunk_text(&chunk_type, &data[separator + 1..], limits)?) {
+                    Walk::Continue => {},
+                    Walk::StopAtImageData => stop_at_image_data = true,
+                    Walk::Stop => return Ok(()),
+                }
+            },
+            b"IDAT" if stop_at_image_data => return Ok(()),
+            b"IEND" => return Ok(()),
+            _ => {
+                skip_chunk_data(&mut reader, length + 4)?;
+            },
+        }
+    }
+}
+
+fn chunk_text(chunk_type: &[u8; 4], rest: &[u8], limits: &LoadLimits) -> PyResult<String> {
+    let text = match chunk_type {
+        b"tEXt" => rest.iter().map(|&byte| byte as char).collect(),
+        b"zTXt" => {
+            let compressed = rest.get(1..).unwrap_or_default();
+            inflate_limited(compressed, limits)?.iter().map(|&byte| byte as char).collect()
+        },
+        _ => {
+            let compressed = rest.first().copied().unwrap_or_default() == 1;
+            let after_flags = rest.get(2..).unwrap_or_default();
+            let mut fields = after_flags.splitn(3, |&byte| byte == 0);
+            let _language_tag = fields.next();
+            let _translated_keyword = fields.next();
+            let text = fields.next().unwrap_or_default();
+            let text = if compressed { inflate_limited(text, limits)? } else { text.to_vec() };
+            String::from_utf8_lossy(&text).into_owned()
+        },
+    };
+    Ok(text)
+}
+
+fn inflate_limited(compressed: &[u8], limits: &LoadLimits) -> PyResult<Vec<u8>> {
+    let max_size = limits.max_text_chunk_size.unwrap_or(u64::MAX);
+    let mut inflated = Vec::new();
+    ZlibDecoder::new(compressed).take(max_size.saturating_add(1)).read_to_end(&mut inflated)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Failed to decompress PNG text chunk: {}", e)))?;
+    if inflated.len() as u64 > max_size {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!("Decompressed PNG text chunk is larger than the limit of {} bytes", max_size)));
+    }
+    Ok(inflated)
+}
+
+// Archive entries are inflated under max_file_size, both per entry and summed over `total_size`, so a zip bomb fails early.
+pub fn read_archive_file<R: Read + Seek>(archive: &mut ZipArchive<R>, path: &str, limits: &LoadLimits, total_size: &mut u64) -> PyResult<Vec<u8>> {
+    let path = path.trim_start_matches("./");
+    let entry = archive.by_name(path)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Failed to read archive file '{}': {}", path, e)))?;
+    let max_size = limits.max_file_size.map_or(u64::MAX, |max_size| max_size.saturating_sub(*total_size));
+    let mut data = Vec::new();
+    entry.take(max_size.saturating_add(1)).read_to_end(&mut data)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Failed to decompress archive file '{}': {}", path, e)))?;
+    if data.len() as u64 > max_size {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!("Decompressed archive data is larger than the limit of {} bytes", limits.max_file_size.unwrap_or_default())));
+    }
+    *total_size += data.len() as u64;
+    Ok(data)
+}
+
+fn read_chunk_data<R: Read>(reader: &mut R, length: u64) -> PyResult<Vec<u8>> {
+    let mut data = Vec::new();
+    reader.by_ref().take(length).read_to_end(&mut data)?;
+    if (data.len() as u64) < length {
+        return Err(pyo3::exceptions::PyValueError::new_err("Failed to read PNG chunk: unexpected end of file"));
+    }
+    skip_chunk_data(reader, 4)?;
+    Ok(data)
+}
+
+fn skip_chunk_data<R: Read>(reader: &mut R, length: u64) -> PyResult<()> {
+    let skipped = io::copy(&mut reader.by_ref().take(length), &mut io::sink())?;
+    if skipped < length {
+        return Err(pyo3::exceptions::PyValueError::new_err(CHARA_NOT_FOUND_MESSAGE));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempPath;
+
+    fn chunk(chunk_type: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut bytes = (data.len() as u32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(chunk_type);
+        bytes.extend_from_slice(data);
+        bytes.extend_from_slice(&[0; 4]);
+        bytes
+    }
+
+    fn text_chunk(keyword: &str, text: &str) -> Vec<u8> {
+        chunk(b"tEXt", &[keyword.as_bytes(), &[0], text.as_bytes()].concat())
+    }
+
+    fn png(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut ihdr = 1u32.to_be_bytes().to_vec();
+        ihdr.extend_from_slice(&1u32.to_be_bytes());
+        ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+        let mut bytes = PNG_SIGNATURE.to_vec();
+        bytes.extend(chunk(b"IHDR", &ihdr));
+        for data in chunks {
+            bytes.extend_from_slice(data);
+        }
+        bytes.extend(chunk(b"IEND", &[]));
+        bytes
+    }
+
+    fn base64(text: &str) -> String {
+        GeneralPurpose::new(&STANDARD, GeneralPurposeConfig::new()).encode(text)
+    }
+
+    #[test]
+    fn prefers_ccv3_over_chara() {
+        let bytes = png(&[text_chunk("chara", &base64(r#"{"name":"v2"}"#)), text_chunk("ccv3", &base64(r#"{"name":"v3"}"#))]);
+        assert_eq!(read_card_json(bytes.as_slice(), &LoadLimits::default()).unwrap(), r#"{"name":"v3"}"#);
+
+        let bytes = png(&[text_chunk("chara", &base64(r#"{"name":"v2"}"#))]);
+        assert_eq!(read_card_json(bytes.as_slice(), &LoadLimits::default()).unwrap(), r#"{"name":"v2"}"#);
+    }
+
+    #[test]
+    fn read_card_collects_asset_chunks() {
+        let bytes = png(&[
+            text_chunk("chara-ext-asset_:0", &base64("zero")),
+            text_chunk("chara-ext-asset_:7", &format!("{}\n", base64("seven"))),
+            text_chunk("ccv3", &base64(r#"{"name":"v3"}"#)),
+        ]);
+        let card = read_card(bytes.as_slice(), &LoadLimits::default()).unwrap();
+        assert_eq!(card.json, r#"{"name":"v3"}"#);
+        assert_eq!(card.assets, BTreeMap::from([
+            ("__asset:0".to_string(), base64("zero")),
+            ("__asset:7".to_string(), base64("seven")),
+        ]));
+        assert!(read_card(png(&[text_chunk("chara-ext-asset_:0", &base64("zero"))]).as_slice(), &LoadLimits::default()).is_err());
+    }
+
+    fn card(json: &str) -> Vec<u8> {
+        png(&[text_chunk("chara", &base64(json))])
+    }
+
+    fn error(result: PyResult<String>) -> String {
+        pyo3::prepare_freethreaded_python();
+        result.expect_err("Expected an error").to_string()
+    }
+
+    fn ztxt_chunk(keyword: &str, text: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+        io::Write::write_all(&mut encoder, text).unwrap();
+        chunk(b"zTXt", &[keyword.as_bytes(), &[0, 0], &encoder.finish().unwrap()].concat())
+    }
+
+    #[test]
+    fn reads_compressed_and_international_text_chunks() {
+        let json = r#"{"name":"Ann"}"#;
+        let bytes = png(&[ztxt_chunk("chara", base64(json).as_bytes())]);
+        assert_eq!(read_card_json(bytes.as_slice(), &LoadLimits::default()).unwrap(), json);
+
+        let itxt = chunk(b"iTXt", &[b"chara".as_slice(), &[0, 0, 0, 0, 0], base64(json).as_bytes()].concat());
+        assert_eq!(read_card_json(png(&[itxt]).as_slice(), &LoadLimits::default()).unwrap(), json);
+    }
+
+    #[test]
+    fn malformed_files_are_rejected() {
+        assert!(error(read_card_json(&b"GIF89a.."[..], &LoadLimits::default())).contains("invalid PNG signature"));
+        assert!(error(read_card_json(png(&[]).as_slice(), &LoadLimits::default())).contains("Failed to find 'chara'"));
+        let bytes = card(r#"{"name":"Ann"}"#);
+        assert!(read_card_json(&bytes[..bytes.len() - 20], &LoadLimits::default()).is_err());
+        assert!(error(read_card_json(png(&[text_chunk("chara", "not base64!")]).as_slice(), &LoadLimits::default())).contains("base64"));
+    }
+
+    #[test]
+    fn limits_are_enforced() {
+        let bytes = card(r#"{"name":"Ann"}"#);
+        let limits = LoadLimits { max_file_size: Some(40), ..LoadLimits::default() };
+        assert!(error(read_card_json(bytes.as_slice(), &limits)).contains("limit of 40 bytes"));
+
+        let mut ihdr = 5000u32.to_be_bytes().to_vec();
+        ihdr.extend_from_slice(&5000u32.to_be_bytes());
+        ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+        let mut large = PNG_SIGNATURE.to_vec();
+        large.extend(chunk(b"IHDR", &ihdr));
+        let limits = LoadLimits { max_pixels: Some(1_000_000), ..LoadLimits::default() };
+        assert!(error(read_card_json(large.as_slice(), &limits)).contains("5000x5000"));
+
+        let limits = LoadLimits { max_text_chunk_size: Some(10), ..LoadLimits::default() };
+        assert!(error(read_card_json(bytes.as_slice(), &limits)).contains("text chunk is larger"));
+
+        let bomb = png(&[ztxt_chunk("chara", &vec![b'A'; 1 << 20])]);
+        let limits = LoadLimits { max_text_chunk_size: Some(4096), ..LoadLimits::default() };
+        assert!(error(read_card_json(bomb.as_slice(), &limits)).contains("Decompressed PNG text chunk"));
+
+        let nested = card(&format!("{}{}", "[".repeat(10), "]".repeat(10)));
+        let limits = LoadLimits { max_json_depth: Some(5), ..LoadLimits::default() };
+        assert!(error(read_card_json(nested.as_slice(), &limits)).contains("limit of 5 levels"));
+        let unlimited = LoadLimits { max_file_size: None, max_text_chunk_size: None, max_pixels: None, max_json_depth: None };
+        assert!(read_card_json(nested.as_slice(), &unlimited).is_ok());
+    }
+
+    #[test]
+    fn image_data_is_skipped() {
+        let mut bytes = card(r#"{"name":"Ann"}"#);
+        bytes.truncate(bytes.len() - 12);
+        bytes.extend(chunk(b"IDAT", &[0; 64]));
+        bytes.extend(chunk(b"IEND", &[]));
+        assert_eq!(read_card_json(bytes.as_slice(), &LoadLimits::default()).unwrap(), r#"{"name":"Ann"}"#);
+        assert!(check_json_depth(r#"{"text": "[[[[[[[[[["}"#, &LoadLimits { max_json_depth: Some(2), ..LoadLimits::default() }).is_ok());
+    }
+
+    #[test]
+    fn unwanted_text_chunks_are_skipped_unread() {
+        let json = r#"{"name":"Ann"}"#;
+        let bytes = png(&[text_chunk("comment", &"x".repeat(1000)), text_chunk("chara", &base64(json))]);
+        let limits = LoadLimits { max_text_chunk_size: Some(100), ..LoadLimits::default() };
+        assert_eq!(read_card_json(bytes.as_slice(), &limits).unwrap(), json);
+        assert_eq!(read_card(bytes.as_slice(), &limits).unwrap().json, json);
+    }
+
+    #[test]
+    fn walk_stops_once_the_character_is_found() {
+        let json = r#"{"name":"Ann"}"#;
+        let garbage = [0xff; 16];
+        let mut after_ccv3 = png(&[text_chunk("ccv3", &base64(json))]);
+        after_ccv3.truncate(after_ccv3.len() - 12);
+        after_ccv3.extend_from_slice(&garbage);
+        assert_eq!(read_card_json(after_ccv3.as_slice(), &LoadLimits::default()).unwrap(), json);
+        assert_eq!(read_card(after_ccv3.as_slice(), &LoadLimits::default()).unwrap().json, json);
+
+        let mut after_image_data = png(&[text_chunk("chara", &base64(json)), chunk(b"IDAT", &[0; 8])]);
+        after_image_data.truncate(after_image_data.len() - 12);
+        after_image_data.extend_from_slice(&garbage);
+        assert_eq!(read_card_json(after_image_data.as_slice(), &LoadLimits::default()).unwrap(), json);
+    }
+
+    #[test]
+    fn stream_reader_keeps_oversized_reads() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let locals = pyo3::types::PyDict::new(py);
+            py.run(r#"
+import io
+class Greedy(io.BytesIO):
+    def read(self, size=-1):
+        return super().read(size + 7 if size >= 0 else size)
+"#, None, Some(locals)).unwrap();
+            let json = r#"{"name":"Ann"}"#;
+            let stream = locals.get_item("Greedy").unwrap().unwrap().call1((pyo3::types::PyBytes::new(py, &card(json)),)).unwrap();
+            assert_eq!(read_card_json(PyStreamReader::new(stream), &LoadLimits::default()).unwrap(), json);
+
+            let stream = py.eval("__import__('io').StringIO('text')", None, None).unwrap();
+            assert!(read_card_json(PyStreamReader::new(stream), &LoadLimits::default()).is_err());
+        });
+    }
+
+    fn peek(name: &str, bytes: &[u8]) -> PyResult<CardInfo> {
+        let path = TempPath::new(&format!("peek_{}.png", name));
+        std::fs::write(path.path(), bytes).unwrap();
+        peek_card(path.as_str(), &LoadLimits::default())
+    }
+
+    #[test]
+    fn peek_reads_name_tags_and_spec_version() {
+        let v2 = card(r#"{"spec": "chara_card_v2", "spec_version": "2.0", "name": "Top", "data": {"name": "Ann", "tags": ["fantasy"]}}"#);
+        let info = peek("v2", &v2).unwrap();
+        assert_eq!((info.name.as_str(), info.tags.clone(), info.spec_version.as_deref()), ("Ann", vec!["fantasy".to_string()], Some("2.0")));
+
+        let v1 = card(r#"{"char_name": "Old", "tags": ["a"]}"#);
+        let info = peek("v1", &v1).unwrap();
+        assert_eq!((info.name.as_str(), info.tags.clone(), info.spec_version.as_deref()), ("Old", vec!["a".to_string()], None));
+        assert!(info.__repr__().starts_with("<CardInfo Old ("));
+
+        for (version, expected) in [("2.0", "2.0"), ("3", "3"), ("3.25", "3.25")] {
+            let info = peek("numeric", &card(&format!(r#"{{"name": "Ann", "spec_version": {}}}"#, version))).unwrap();
+            assert_eq!(info.spec_version.as_deref(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn peek_applies_limits_and_stops_at_image_data() {
+        let bytes = card(r#"{"name": "Ann"}"#);
+        let path = TempPath::new("peek_limits.png");
+        std::fs::write(path.path(), &bytes).unwrap();
+        let limits = LoadLimits { max_file_size: Some(40), ..LoadLimits::default() };
+        assert!(error(peek_card(path.as_str(), &limits).map(|info| info.name)).contains("limit of 40 bytes"));
+
+        let mut after_image_data = png(&[text_chunk("chara", &base64(r#"{"name": "Ann"}"#)), chunk(b"IDAT", &[0; 8])]);
+        after_image_data.truncate(after_image_data.len() - 12);
+        after_image_data.extend_from_slice(&[0xff; 16]);
+        assert_eq!(peek("image_data", &after_image_data).unwrap().name, "Ann");
+    }
+
+    #[test]
+    fn peek_rejects_invalid_cards() {
+        assert!(peek("not_json", &card("not json")).is_err());
+        assert!(peek("no_card", &png(&[])).is_err());
+        assert!(peek_card("/nonexistent/aichar.png", &LoadLimits::default()).is_err());
+    }
+}
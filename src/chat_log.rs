@@ -0,0 +1,487 @@
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use pyo3::prelude::*;
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+
+use crate::{CharacterClass, convert};
+
+const BEGIN_VISIBLE_CHAT: &str = "<|BEGIN-VISIBLE-CHAT|>";
+const SILLYTAVERN_SEND_DATE: &str = "%B %-d, %Y %-I:%M%P";
+const SILLYTAVERN_CREATE_DATE: &str = "%Y-%m-%d@%Hh%Mm%Ss";
+const TEXTGEN_TIMESTAMP: &str = "%b %d, %Y %H:%M";
+
+#[derive(Clone, Default, PartialEq)]
+#[pyclass(module = "aichar")]
+pub struct ChatMessage {
+    #[pyo3(get, set)]
+    pub speaker: String,
+    #[pyo3(get, set)]
+    pub text: String,
+    #[pyo3(get, set)]
+    pub is_user: bool,
+    #[pyo3(get, set)]
+    pub is_system: bool,
+    #[pyo3(get, set)]
+    pub timestamp: Option<i64>,
+    #[pyo3(get, set)]
+    pub swipes: Vec<String>,
+    pub metadata: Map<String, Value>,
+}
+
+#[pymethods]
+impl ChatMessage {
+    #[new]
+    #[pyo3(signature = (speaker, text, is_user = false, is_system = false, timestamp = None, swipes = Vec::new()))]
+    fn new(speaker: String, text: String, is_user: bool, is_system: bool, timestamp: Option<i64>, swipes: Vec<String>) -> Self {
+        ChatMessage { speaker, text, is_user, is_system, timestamp, swipes, metadata: Map::new() }
+    }
+
+    #[getter]
+    fn metadata(&self, py: Python) -> PyObject {
+        convert::value_to_py(py, &Value::Object(self.metadata.clone()))
+    }
+
+    #[setter]
+    fn set_metadata(&mut self, metadata: &PyAny) -> PyResult<()> {
+        self.metadata = match convert::py_to_value(metadata)? {
+            Value::Object(map) => map,
+            _ => return Err(pyo3::exceptions::PyTypeError::new_err("Metadata must be a dict")),
+        };
+        Ok(())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<ChatMessage {}: {:?}>", self.speaker, self.text.chars().take(40).collect::<String>())
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+#[derive(Clone, Default, PartialEq)]
+#[pyclass(module = "aichar")]
+pub struct ChatLog {
+    #[pyo3(get, set)]
+    pub character_name: String,
+    #[pyo3(get, set)]
+    pub user_name: String,
+    #[pyo3(get, set)]
+    pub messages: Vec<ChatMessage>,
+    #[pyo3(get, set)]
+    pub created_time: Option<i64>,
+    pub metadata: Map<String, Value>,
+}
+
+#[pymethods]
+impl ChatLog {
+    #[new]
+    #[pyo3(signature = (character_name = String::new(), user_name = "User".to_string(), messages = Vec::new(), created_time = None))]
+    fn new(character_name: String, user_name: String, messages: Vec<ChatMessage>, created_time: Option<i64>) -> Self {
+        ChatLog { character_name, user_name, messages, created_time, metadata: Map::new() }
+    }
+
+    #[getter]
+    fn metadata(&self, py: Python) -> PyObject {
+        convert::value_to_py(py, &Value::Object(self.metadata.clone()))
+    }
+
+    #[setter]
+    fn set_metadata(&mut self, metadata: &PyAny) -> PyResult<()> {
+        self.metadata = match convert::py_to_value(metadata)? {
+            Value::Object(map) => map,
+            _ => return Err(pyo3::exceptions::PyTypeError::new_err("Metadata must be a dict")),
+        };
+        Ok(())
+    }
+
+    fn append(&mut self, message: ChatMessage) {
+        self.messages.push(message);
+    }
+
+    pub(crate) fn is_for(&self, character: &CharacterClass) -> bool {
+        self.character_name.trim().eq_ignore_ascii_case(character.name.trim())
+    }
+
+    fn link(&mut self, character: &CharacterClass) {
+        let old_name = std::mem::replace(&mut self.character_name, character.name.clone());
+        for message in self.messages.iter_mut().filter(|message| !message.is_user && !message.is_system) {
+            if message.speaker.is_empty() || message.speaker == old_name {
+                message.speaker = character.name.clone();
+            }
+        }
+    }
+
+    fn export_sillytavern(&self) -> String {
+        to_sillytavern(self)
+    }
+
+    fn export_sillytavern_file(&self, path: &str) -> PyResult<()> {
+        std::fs::write(path, to_sillytavern(self))?;
+        Ok(())
+    }
+
+    fn export_textgen(&self) -> String {
+        to_textgen(self)
+    }
+
+    fn export_textgen_file(&self, path: &str) -> PyResult<()> {
+        std::fs::write(path, to_textgen(self))?;
+        Ok(())
+    }
+
+    fn __len__(&self) -> usize {
+        self.messages.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<ChatLog \"{}\" {} messages>", self.character_name, self.messages.len())
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+#[derive(Deserialize)]
+struct SillyTavernLine {
+    user_name: Option<String>,
+    character_name: Option<String>,
+    create_date: Option<Value>,
+    chat_metadata: Option<Map<String, Value>>,
+    name: Option<String>,
+    #[serde(default)]
+    is_user: bool,
+    #[serde(default)]
+    is_system: bool,
+    send_date: Option<Value>,
+    mes: Option<String>,
+    #[serde(default)]
+    swipes: Vec<String>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+#[derive(Deserialize)]
+struct TextgenHistory {
+    #[serde(default)]
+    internal: Vec<Vec<String>>,
+    #[serde(default)]
+    visible: Vec<Vec<String>>,
+    #[serde(default)]
+    metadata: Map<String, Value>,
+}
+
+pub fn load_sillytavern(jsonl: &str, character: Option<&CharacterClass>) -> PyResult<ChatLog> {
+    let mut log = ChatLog { user_name: "User".to_string(), ..ChatLog::default() };
+    for (index, line) in jsonl.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let line: SillyTavernLine = serde_json::from_str(line)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Line {} of the chat does not contain correct json data: {}", index + 1, e)))?;
+        let Some(text) = line.mes else {
+            log.user_name = line.user_name.unwrap_or(log.user_name);
+            log.character_name = line.character_name.unwrap_or_default();
+            log.created_time = line.create_date.as_ref().and_then(parse_time);
+            log.metadata = line.chat_metadata.unwrap_or_default();
+            continue;
+        };
+        let mut metadata = line.extra;
+        metadata.remove("swipe_id");
+        let timestamp = line.send_date.as_ref().and_then(parse_time);
+        if let Some(send_date) = line.send_date.filter(|_| timestamp.is_none()) {
+            metadata.insert("send_date".to_string(), send_date);
+        }
+        log.messages.push(ChatMessage {
+            speaker: line.name.unwrap_or_default(),
+            text,
+            is_user: line.is_user,
+            is_system: line.is_system,
+            timestamp,
+            swipes: line.swipes,
+            metadata,
+        });
+    }
+    if let Some(character) = character {
+        log.link(character);
+    }
+    Ok(log)
+}
+
+pub fn load_textgen(json: &str, character: Option<&CharacterClass>, user_name: &str) -> PyResult<ChatLog> {
+    let history: TextgenHistory = serde_json::from_str(json)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Chat history does not contain correct json data: {}", e)))?;
+    let character_name = character.map(|character| character.name.clone()).unwrap_or_default();
+    let mut log = ChatLog { character_name: character_name.clone(), user_name: user_name.to_string(), ..ChatLog::default() };
+
+    let pair_count = history.internal.len().max(history.visible.len());
+    for index in 0..pair_count {
+        let text = |position: usize| {
+            history.internal.get(index).and_then(|pair| pair.get(position)).cloned()
+                .or_else(|| history.visible.get(index).and_then(|pair| pair.get(position)).map(|text| unescape_html(text)))
+                .unwrap_or_default()
+        };
+        let entry = |role: &str| history.metadata.get(&format!("{}_{}", role, index));
+        let (user_text, bot_text) = (text(0), text(1));
+        if !user_text.is_empty() && user_text != BEGIN_VISIBLE_CHAT {
+            log.messages.push(ChatMessage {
+                speaker: user_name.to_string(),
+                text: user_text,
+                is_user: true,
+                timestamp: entry("user").and_then(|entry| entry.get("timestamp")).and_then(parse_time),
+                ..ChatMessage::default()
+            });
+        }
+        if !bot_text.is_empty() {
+            let swipes: Vec<String> = entry("assistant").and_then(|entry| entry.get("versions")).and_then(Value::as_array)
+                .map(|versions| versions.iter().filter_map(|version| version.get("content")?.as_str().map(str::to_string)).collect())
+                .unwrap_or_default();
+            log.messages.push(ChatMessage {
+                speaker: character_name.clone(),
+                text: bot_text,
+                timestamp: entry("assistant").and_then(|entry| entry.get("timestamp")).and_then(parse_time),
+                swipes: if swipes.len() > 1 { swipes } else { Vec::new() },
+                ..ChatMessage::default()
+            });
+        }
+    }
+    Ok(log)
+}
+
+fn to_sillytavern(log: &ChatLog) -> String {
+    let created = log.created_time.or(log.messages.first().and_then(|message| message.timestamp)).unwrap_or_else(|| Utc::now().timestamp_millis());
+    let header = json!({
+        "user_name": log.user_name,
+        "character_name": log.character_name,
+        "create_date": format_time(created, SILLYTAVERN_CREATE_DATE),
+        "chat_metadata": log.metadata,
+    });
+    let mut lines = vec![header.to_string()];
+    for message in &log.messages {
+        let mut line = message.metadata.clone();
+        line.insert("name".to_string(), Value::from(message.speaker.as_str()));
+        line.insert("is_user".to_string(), Value::from(message.is_user));
+        line.insert("is_system".to_string(), Value::from(message.is_system));
+        line.insert("mes".to_string(), Value::from(message.text.as_str()));
+        if let Some(timestamp) = message.timestamp {
+            line.insert("send_date".to_string(), Value::from(format_time(timestamp, SILLYTAVERN_SEND_DATE)));
+        }
+        if !message.swipes.is_empty() {
+            let mut swipes = message.swipes.clone();
+            let swipe_id = swipes.iter().position(|swipe| swipe == &message.text).unwrap_or_else(|| {
+                swipes.push(message.text.clone());
+                swipes.len() - 1
+            });
+            line.insert("swipe_id".to_string(), Value::from(swipe_id));
+            line.insert("swipes".to_string(), Value::from(swipes));
+        }
+        lines.push(Value::Object(line).to_string());
+    }
+    lines.join("\n") + "\n"
+}
+
+fn to_textgen(log: &ChatLog) -> String {
+    let mut pairs: Vec<(String, Option<&ChatMessage>, Option<&ChatMessage>)> = Vec::new();
+    for message in log.messages.iter().filter(|message| !message.is_system) {
+        if message.is_user {
+            pairs.push((message.text.clone(), Some(message), None));
+            continue;
+        }
+        match pairs.last_mut() {
+            Some((_, Some(_), bot @ None)) => *bot = Some(message),
+            _ => {
+                let user_text = if pairs.is_empty() { BEGIN_VISIBLE_CHAT } else { "" };
+                pairs.push((user_text.to_string(), None, Some(message)));
+            },
+        }
+    }
+
+    let mut metadata = Map::new();
+    for (index, (_, user, bot)) in pairs.iter().enumerate() {
+        if let Some(timestamp) = user.and_then(|user| user.timestamp) {
+            metadata.insert(format!("user_{}", index), json!({ "timestamp": format_time(timestamp, TEXTGEN_TIMESTAMP) }));
+        }
+        if let Some(bot) = bot {
+            let mut entry = Map::new();
+            if let Some(timestamp) = bot.timestamp {
+                entry.insert("timestamp".to_string(), Value::from(format_time(timestamp, TEXTGEN_TIMESTAMP)));
+            }
+            if !bot.swipes.is_empty() {
+                entry.insert("versions".to_string(), Value::from(bot.swipes.iter().map(|swipe| json!({
+                    "content": swipe,
+                    "visible_content": escape_html(swipe),
+                })).collect::<Vec<Value>>()));
+            }
+            if !entry.is_empty() {
+                metadata.insert(format!("assistant_{}", index), Value::Object(entry));
+            }
+        }
+    }
+
+    let bot_text = |bot: &Option<&ChatMessage>| bot.map(|bot| bot.text.clone()).unwrap_or_default();
+    let internal: Vec<[String; 2]> = pairs.iter().map(|(user_text, _, bot)| [user_text.clone(), bot_text(bot)]).collect();
+    let visible: Vec<[String; 2]> = pairs.iter().map(|(user_text, _, bot)| {
+        let user_text = if user_text == BEGIN_VISIBLE_CHAT { String::new() } else { escape_html(user_text) };
+        [user_text, escape_html(&bot_text(bot))]
+    }).collect();
+    let mut history = json!({ "internal": internal, "visible": visible });
+    if !metadata.is_empty() {
+        history["metadata"] = Value::Object(metadata);
+    }
+    serde_json::to_string_pretty(&history).expect("Error while serializing JSON")
+}
+
+fn parse_time(value: &Value) -> Option<i64> {
+    if let Some(milliseconds) = value.as_i64() {
+        return Some(milliseconds);
+    }
+    let text = value.as_str()?.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Some(time.timestamp_millis());
+    }
+    [SILLYTAVERN_SEND_DATE, SILLYTAVERN_CREATE_DATE, TEXTGEN_TIMESTAMP, "%B %d, %Y %I:%M%p", "%B %d, %Y %I:%M %p"].iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .map(|time| time.and_utc().timestamp_millis())
+}
+
+fn format_time(milliseconds: i64, format: &str) -> String {
+    Utc.timestamp_millis_opt(milliseconds).single().unwrap_or_default().format(format).to_string()
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&#x27;")
+}
+
+fn unescape_html(text: &str) -> String {
+    text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&#x27;", "'").replace("&#39;", "'").replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIME: i64 = 1_699_999_980_000;
+
+    fn message(speaker: &str, text: &str, is_user: bool) -> ChatMessage {
+        ChatMessage { speaker: speaker.to_string(), text: text.to_string(), is_user, timestamp: Some(TIME), ..ChatMessage::default() }
+    }
+
+    fn log() -> ChatLog {
+        let mut greeting = message("Ann", "Hi <there> & \"you\"", false);
+        greeting.swipes = vec!["Hello".to_string(), greeting.text.clone()];
+        let mut metadata = Map::new();
+        metadata.insert("note".to_string(), Value::from("x"));
+        ChatLog {
+            character_name: "Ann".to_string(),
+            user_name: "Bob".to_string(),
+            messages: vec![greeting, message("Bob", "Hey\nthere", true), message("Ann", "How are you?", false)],
+            created_time: Some(TIME),
+            metadata,
+        }
+    }
+
+    #[test]
+    fn sillytavern_round_trip() {
+        let mut log = log();
+        log.messages.push(ChatMessage { speaker: "System".to_string(), text: "Note".to_string(), is_system: true, ..ChatMessage::default() });
+        let jsonl = to_sillytavern(&log);
+        assert_eq!(jsonl.lines().count(), 5);
+        let first: Value = serde_json::from_str(jsonl.lines().nth(1).unwrap()).unwrap();
+        assert_eq!(first["swipe_id"], 1);
+        assert!(load_sillytavern(&jsonl, None).unwrap() == log);
+    }
+
+    #[test]
+    fn sillytavern_keeps_unmapped_message_fields() {
+        let jsonl = concat!(
+            "{\"user_name\": \"Bob\", \"character_name\": \"Ann\", \"chat_metadata\": {}}\n",
+            "{\"name\": \"Ann\", \"is_user\": false, \"is_system\": false, \"mes\": \"Hi\", \"send_date\": \"not a date\", \"force_avatar\": \"ann.png\", \"gen_started\": \"2023-11-14T22:13:00Z\", ",
+            "\"extra\": {\"api\": \"openai\", \"model\": \"gpt-4\", \"reasoning\": \"...\"}, \"swipe_id\": 0, \"swipes\": [\"Hi\"], \"swipe_info\": [{\"extra\": {}}]}\n",
+        );
+        let log = load_sillytavern(jsonl, None).unwrap();
+        let metadata = &log.messages[0].metadata;
+        assert_eq!(metadata["extra"]["model"], "gpt-4");
+        assert_eq!(metadata["force_avatar"], "ann.png");
+        assert_eq!(metadata["send_date"], "not a date");
+        assert!(!metadata.contains_key("swipe_id"));
+
+        let line: Value = serde_json::from_str(to_sillytavern(&log).lines().nth(1).unwrap()).unwrap();
+        let original: Value = serde_json::from_str(jsonl.lines().nth(1).unwrap()).unwrap();
+        assert_eq!(line, original);
+    }
+
+    #[test]
+    fn sillytavern_adds_text_missing_from_swipes() {
+        let mut log = log();
+        log.messages[0].swipes = vec!["Hello".to_string(), "Hey".to_string()];
+        let line: Value = serde_json::from_str(to_sillytavern(&log).lines().nth(1).unwrap()).unwrap();
+        assert_eq!(line["swipe_id"], 2);
+        assert_eq!(line["swipes"][2], line["mes"]);
+    }
+
+    #[test]
+    fn sillytavern_errors_and_linking() {
+        pyo3::prepare_freethreaded_python();
+        let error = load_sillytavern("{\"user_name\": \"Bob\"}\n\n{\"mes\": ", None).err().unwrap();
+        assert!(error.to_string().contains("Line 3"));
+
+        let jsonl = "\n{\"name\": \"Annie\", \"mes\": \"Hi\", \"send_date\": 1700000000000}\n{\"name\": \"Bob\", \"is_user\": true, \"mes\": \"Hey\", \"send_date\": \"not a date\"}\n";
+        let character = CharacterClass::default().with_name("Ann");
+        let log = load_sillytavern(jsonl, Some(&character)).unwrap();
+        assert_eq!(log.user_name, "User");
+        assert_eq!(log.character_name, "Ann");
+        assert_eq!(log.messages[0].timestamp, Some(1_700_000_000_000));
+        assert_eq!(log.messages[0].speaker, "Annie");
+        assert_eq!(log.messages[1].speaker, "Bob");
+        assert!(log.messages[1].timestamp.is_none());
+        assert!(log.is_for(&CharacterClass::default().with_name(" ann ")));
+    }
+
+    #[test]
+    fn textgen_round_trip() {
+        let log = log();
+        let json = to_textgen(&log);
+        let history: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(history["internal"][0], json!([BEGIN_VISIBLE_CHAT, "Hi <there> & \"you\""]));
+        assert_eq!(history["visible"][0], json!(["", "Hi &lt;there&gt; &amp; &quot;you&quot;"]));
+        assert_eq!(history["internal"][1], json!(["Hey\nthere", "How are you?"]));
+        assert_eq!(history["metadata"]["assistant_0"]["versions"][0]["content"], "Hello");
+
+        let character = CharacterClass::default().with_name("Ann");
+        let loaded = load_textgen(&json, Some(&character), "Bob").unwrap();
+        assert!(loaded.messages == log.messages);
+        assert_eq!(loaded.character_name, "Ann");
+    }
+
+    #[test]
+    fn textgen_pairs_and_visible_only_history() {
+        let log = ChatLog {
+            messages: vec![message("Bob", "One", true), message("Bob", "Two", true), message("Ann", "Reply", false), message("Ann", "More", false)],
+            ..ChatLog::default()
+        };
+        let history: Value = serde_json::from_str(&to_textgen(&log)).unwrap();
+        assert_eq!(history["internal"], json!([["One", ""], ["Two", "Reply"], ["", "More"]]));
+
+        let loaded = load_textgen(r#"{"visible": [["a &lt; b", "c &amp;amp; d"]]}"#, None, "You").unwrap();
+        assert_eq!(loaded.messages[0].text, "a < b");
+        assert_eq!(loaded.messages[1].text, "c &amp; d");
+        assert_eq!(loaded.messages[0].speaker, "You");
+        assert!(loaded.messages[1].speaker.is_empty());
+    }
+
+    #[test]
+    fn textgen_rejects_malformed_history() {
+        assert!(load_textgen("not json", None, "You").is_err());
+        assert!(load_textgen(r#"{"internal": [["a", 1]]}"#, None, "You").is_err());
+        assert!(load_textgen("{}", None, "You").unwrap().messages.is_empty());
+    }
+
+    #[test]
+    fn parses_timestamps() {
+        assert_eq!(parse_time(&Value::from(TIME)), Some(TIME));
+        assert_eq!(parse_time(&Value::from("2023-11-14T22:13:00Z")), Some(TIME));
+        assert_eq!(parse_time(&Value::from("November 14, 2023 10:13pm")), Some(TIME));
+        assert_eq!(parse_time(&Value::from("2023-11-14@22h13m00s")), Some(TIME));
+        assert_eq!(parse_time(&Value::from("Nov 14, 2023 22:13")), Some(TIME));
+        assert!(parse_time(&Value::from("yesterday")).is_none());
+        assert!(parse_time(&Value::Null).is_none());
+    }
+}
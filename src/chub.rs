@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use pyo3::prelude::*;
+use serde_json::{Map, Value};
+
+const CHUB_URL: &str = "https://chub.ai/characters/";
+
+#[derive(Clone, Default, PartialEq)]
+#[pyclass(module = "aichar")]
+pub struct ChubInfo {
+    #[pyo3(get, set)]
+    pub full_path: Option<String>,
+    #[pyo3(get, set)]
+    pub id: Option<i64>,
+    #[pyo3(get, set)]
+    pub related_lorebooks: Vec<i64>,
+    #[pyo3(get, set)]
+    pub alt_expressions: HashMap<String, String>,
+}
+
+#[pymethods]
+impl ChubInfo {
+    #[new]
+    #[pyo3(signature = (full_path = None, id = None, related_lorebooks = Vec::new(), alt_expressions = HashMap::new()))]
+    fn new(full_path: Option<String>, id: Option<i64>, related_lorebooks: Vec<i64>, alt_expressions: HashMap<String, String>) -> Self {
+        ChubInfo { full_path, id, related_lorebooks, alt_expressions }
+    }
+
+    #[getter]
+    fn url(&self) -> Option<String> {
+        self.full_path.as_ref().map(|full_path| format!("{}{}", CHUB_URL, full_path))
+    }
+
+    fn __repr__(&self) -> String {
+        match (&self.full_path, self.id) {
+            (Some(full_path), _) => format!("<ChubInfo {}>", full_path),
+            (None, Some(id)) => format!("<ChubInfo {}>", id),
+            (None, None) => "<ChubInfo>".to_string(),
+        }
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+pub fn chub_from_extensions(extensions: &Map<String, Value>) -> Option<ChubInfo> {
+    let chub = extensions.get("chub")?.as_object()?;
+    Some(ChubInfo {
+        full_path: chub.get("full_path").and_then(Value::as_str).map(str::to_string),
+        id: chub.get("id").and_then(id_from_value),
+        related_lorebooks: chub.get("related_lorebooks").and_then(Value::as_array)
+            .map(|lorebooks| lorebooks.iter().filter_map(id_from_value).collect())
+            .unwrap_or_default(),
+        alt_expressions: chub.get("alt_expressions").and_then(Value::as_object)
+            .map(|expressions| expressions.iter()
+                .filter_map(|(name, url)| Some((name.clone(), url.as_str()?.to_string())))
+                .collect())
+            .unwrap_or_default(),
+    })
+}
+
+pub fn update_extensions(extensions: &mut Map<String, Value>, chub: Option<&ChubInfo>) {
+    let Some(chub) = chub else {
+        extensions.remove("chub");
+        return;
+    };
+    let entry = extensions.entry("chub").or_insert_with(|| Value::Object(Map::new()));
+    if !entry.is_object() {
+        *entry = Value::Object(Map::new());
+    }
+    let block = entry.as_object_mut().expect("chub extension is an object");
+    match &chub.full_path {
+        Some(full_path) => block.insert("full_path".to_string(), Value::from(full_path.as_str())),
+        None => block.remove("full_path"),
+    };
+    match chub.id {
+        Some(id) => block.insert("id".to_string(), Value::from(id)),
+        None => block.remove("id"),
+    };
+    block.insert("related_lorebooks".to_string(), Value::from(chub.related_lorebooks.clone()));
+    block.insert("alt_expressions".to_string(), Value::Object(chub.alt_expressions.iter()
+        .map(|(name, url)| (name.clone(), Value::from(url.as_str())))
+        .collect()));
+}
+
+fn id_from_value(value: &Value) -> Option<i64> {
+    value.as_i64().or_else(|| value.as_str()?.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn extensions(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn reads_chub_block() {
+        let extensions = extensions(json!({"chub": {
+            "full_path": "someone/ann", "id": "123", "related_lorebooks": [1, "2", "x", null],
+            "alt_expressions": {"happy": "https://example.com/happy.png", "broken": 5},
+        }}));
+        let chub = chub_from_extensions(&extensions).unwrap();
+        assert_eq!(chub.full_path.as_deref(), Some("someone/ann"));
+        assert_eq!(chub.id, Some(123));
+        assert_eq!(chub.related_lorebooks, [1, 2]);
+        assert_eq!(chub.alt_expressions, HashMap::from([("happy".to_string(), "https://example.com/happy.png".to_string())]));
+        assert_eq!(chub.url().as_deref(), Some("https://chub.ai/characters/someone/ann"));
+        assert_eq!(chub.__repr__(), "<ChubInfo someone/ann>");
+    }
+
+    #[test]
+    fn missing_or_malformed_blocks() {
+        assert!(chub_from_extensions(&Map::new()).is_none());
+        assert!(chub_from_extensions(&extensions(json!({"chub": "someone/ann"}))).is_none());
+        let chub = chub_from_extensions(&extensions(json!({"chub": {"id": 1.5, "related_lorebooks": "1"}}))).unwrap();
+        assert!(chub == ChubInfo::default());
+        assert_eq!(chub.__repr__(), "<ChubInfo>");
+    }
+
+    #[test]
+    fn update_keeps_unknown_keys_and_round_trips() {
+        let mut extensions = extensions(json!({"chub": {"expressions": null, "full_path": "old"}, "other": 1}));
+        let chub = ChubInfo::new(Some("someone/ann".to_string()), Some(7), vec![3], HashMap::from([("sad".to_string(), "sad.png".to_string())]));
+        update_extensions(&mut extensions, Some(&chub));
+        assert!(chub_from_extensions(&extensions).unwrap() == chub);
+        assert!(extensions["chub"].as_object().unwrap().contains_key("expressions"));
+        assert_eq!(extensions["other"], 1);
+
+        let mut broken = extensions.clone();
+        broken.insert("chub".to_string(), json!("text"));
+        update_extensions(&mut broken, Some(&chub));
+        assert!(chub_from_extensions(&broken).unwrap() == chub);
+
+        update_extensions(&mut extensions, None);
+        assert!(!extensions.contains_key("chub"));
+        assert_eq!(extensions["other"], 1);
+    }
+
+    #[test]
+    fn unset_fields_are_left_out() {
+        let mut extensions = extensions(json!({"chub": {"full_path": "someone/ann", "id": 1}}));
+        update_extensions(&mut extensions, Some(&ChubInfo::default()));
+        assert_eq!(extensions["chub"], json!({"related_lorebooks": [], "alt_expressions": {}}));
+    }
+}
@@ -0,0 +1,388 @@
+use pyo3::prelude::*;
+use sha2::{Digest, Sha256};
+use similar::{ChangeTag, TextDiff};
+
+use crate::{CharacterBook, CharacterClass, LorebookEntry};
+
+#[derive(Clone)]
+#[pyclass]
+pub struct FieldChange {
+    #[pyo3(get)]
+    pub field: String,
+    #[pyo3(get)]
+    pub kind: &'static str,
+    #[pyo3(get)]
+    pub old: Option<String>,
+    #[pyo3(get)]
+    pub new: Option<String>,
+    #[pyo3(get)]
+    pub added: Vec<String>,
+    #[pyo3(get)]
+    pub removed: Vec<String>,
+}
+
+#[pymethods]
+impl FieldChange {
+    fn line_diff(&self) -> Vec<(&'static str, String)> {
+        let old = self.old.as_deref().unwrap_or_default();
+        let new = self.new.as_deref().unwrap_or_default();
+        TextDiff::from_lines(old, new).iter_all_changes()
+            .map(|change| {
+                let tag = match change.tag() {
+                    ChangeTag::Delete => "-",
+                    ChangeTag::Insert => "+",
+                    ChangeTag::Equal => " ",
+                };
+                (tag, change.value().trim_end_matches('\n').to_string())
+            })
+            .collect()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<FieldChange {} {}>", self.kind, self.field)
+    }
+}
+
+impl FieldChange {
+    fn text(field: String, old: Option<String>, new: Option<String>) -> Self {
+        let kind = match (&old, &new) {
+            (None, _) => "added",
+            (_, None) => "removed",
+            _ => "modified",
+        };
+        FieldChange { field, kind, old, new, added: Vec::new(), removed: Vec::new() }
+    }
+
+    fn unified(&self, context: usize) -> String {
+        let old = self.old.as_deref().unwrap_or_default();
+        let new = self.new.as_deref().unwrap_or_default();
+        TextDiff::from_lines(old, new)
+            .unified_diff()
+            .context_radius(context)
+            .header(&format!("a/{}", self.field), &format!("b/{}", self.field))
+            .to_string()
+    }
+}
+
+#[derive(Clone)]
+#[pyclass]
+pub struct CharacterDiff {
+    pub changes: Vec<FieldChange>,
+}
+
+#[pymethods]
+impl CharacterDiff {
+    #[getter]
+    fn changes(&self) -> Vec<FieldChange> {
+        self.changes.clone()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    #[pyo3(signature = (context = 3))]
+    fn unified(&self, context: usize) -> String {
+        self.changes.iter().map(|change| change.unified(context)).collect()
+    }
+
+    fn __len__(&self) -> usize {
+        self.changes.len()
+    }
+
+    fn __repr__(&self) -> String {
+        let fields: Vec<&str> = self.changes.iter().map(|change| change.field.as_str()).collect();
+        format!("<CharacterDiff [{}]>", fields.join(", "))
+    }
+}
+
+pub fn diff_characters(a: &CharacterClass, b: &CharacterClass) -> CharacterDiff {
+    let mut changes = Vec::new();
+
+    for ((field, old), (_, new)) in a.text_fields().into_iter().zip(b.text_fields()) {
+        if old != new {
+            changes.push(FieldChange::text(field.to_string(), non_empty(old), non_empty(new)));
+        }
+    }
+
+    for ((field, old), (_, new)) in a.list_fields().into_iter().zip(b.list_fields()) {
+        if old != new {
+            changes.push(list_change(field, old, new));
+        }
+    }
+
+    let empty_book = CharacterBook::default();
+    let old_book = a.character_book.as_ref().unwrap_or(&empty_book);
+    let new_book = b.character_book.as_ref().unwrap_or(&empty_book);
+    // A missing book and an empty one render the same header, so the header text is only None when there is no book at all.
+    let old_header = a.character_book.as_ref().map(render_book_header);
+    let new_header = b.character_book.as_ref().map(render_book_header);
+    if old_header != new_header {
+        changes.push(FieldChange::text("character_book".to_string(), old_header, new_header));
+    }
+    changes.extend(diff_entries(&old_book.entries, &new_book.entries));
+
+    if a.extensions != b.extensions {
+        changes.push(FieldChange::text("extensions".to_string(), render_json(&a.extensions), render_json(&b.extensions)));
+    }
+    if a.assets != b.assets {
+        changes.push(FieldChange::text("assets".to_string(), render_json(&a.assets), render_json(&b.assets)));
+    }
+
+    CharacterDiff { changes }
+}
+
+pub fn entry_keys(entries: &[LorebookEntry]) -> Vec<String> {
+    let mut unkeyed = 0;
+    unique_keys(entries.iter().map(|entry| own_key(entry).unwrap_or_else(|| {
+        unkeyed += 1;
+        format!("idx:{}", unkeyed - 1)
+    })))
+}
+
+// Keys of `entries` that line up with entry_keys(base). Entries without id or name are matched with a base entry
+// of the same keys and content first, then by their order among the unmatched ones, so editing their keys is a
+// change and not a remove plus an add, and removing one does not shift the others. Entries left over are keyed
+// by a hash of their keys and content, so the same addition gets the same key no matter which side made it.
+pub fn matched_entry_keys(base: &[LorebookEntry], entries: &[LorebookEntry]) -> Vec<String> {
+    let base_unkeyed: Vec<&LorebookEntry> = base.iter().filter(|entry| own_key(entry).is_none()).collect();
+    let mut taken = vec![false; base_unkeyed.len()];
+    let exact: Vec<Option<usize>> = entries.iter().map(|entry| {
+        if own_key(entry).is_some() {
+            return None;
+        }
+        let position = (0..base_unkeyed.len())
+            .find(|&index| !taken[index] && base_unkeyed[index].keys == entry.keys && base_unkeyed[index].content == entry.content)?;
+        taken[position] = true;
+        Some(position)
+    }).collect();
+    let mut remaining = (0..base_unkeyed.len()).filter(|&index| !taken[index]).collect::<Vec<usize>>().into_iter();
+
+    unique_keys(entries.iter().zip(exact).map(|(entry, exact)| own_key(entry).unwrap_or_else(|| {
+        match exact.or_else(|| remaining.next()) {
+            Some(position) => format!("idx:{}", position),
+            None => format!("new:{}", content_hash(entry)),
+        }
+    })))
+}
+
+fn unique_keys(keys: impl Iterator<Item = String>) -> Vec<String> {
+    let mut unique: Vec<String> = Vec::new();
+    for (index, mut key) in keys.enumerate() {
+        if unique.contains(&key) {
+            key = format!("{}#{}", key, index);
+        }
+        unique.push(key);
+    }
+    unique
+}
+
+fn content_hash(entry: &LorebookEntry) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(serde_json::to_vec(&(&entry.keys, &entry.content)).expect("Error while serializing JSON"));
+    format!("{:x}", hasher.finalize())[..12].to_string()
+}
+
+// Keys carry their kind, so an entry named "3" is never matched with the entry at position 3 or with id 3.
+fn own_key(entry: &LorebookEntry) -> Option<String> {
+    match (&entry.id, &entry.name) {
+        (Some(id), _) => Some(format!("id:{}", id)),
+        (None, Some(name)) if !name.is_empty() => Some(format!("name:{}", name)),
+        _ => None,
+    }
+}
+
+pub fn render_entry(entry: &LorebookEntry) -> String {
+    let mut text = format!("keys: {}\n", entry.keys.join(", "));
+    if let Some(secondary_keys) = &entry.secondary_keys {
+        text.push_str(&format!("secondary_keys: {}\n", secondary_keys.join(", ")));
+    }
+    text.push_str(&format!("enabled: {}\ninsertion_order: {}\n", entry.enabled, entry.insertion_order));
+    for (label, value) in [("case_sensitive", entry.case_sensitive), ("selective", entry.selective), ("constant", entry.constant), ("use_regex", entry.use_regex)] {
+        if let Some(value) = value {
+            text.push_str(&format!("{}: {}\n", label, value));
+        }
+    }
+    for (label, value) in [("name", &entry.name), ("comment", &entry.comment), ("position", &entry.position)] {
+        if let Some(value) = value {
+            text.push_str(&format!("{}: {}\n", label, value));
+        }
+    }
+    if let Some(priority) = entry.priority {
+        text.push_str(&format!("priority: {}\n", priority));
+    }
+    if !entry.extensions.is_empty() {
+        text.push_str(&format!("extensions: {}\n", serde_json::Value::Object(entry.extensions.clone())));
+    }
+    text.push('\n');
+    text.push_str(&entry.content);
+    text
+}
+
+pub fn render_book(book: &CharacterBook) -> String {
+    let mut text = render_book_header(book);
+    for entry in &book.entries {
+        text.push_str(&format!("\n[entry]\n{}\n", render_entry(entry)));
+    }
+    text
+}
+
+fn diff_entries(old_entries: &[LorebookEntry], new_entries: &[LorebookEntry]) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    let old_keys = entry_keys(old_entries);
+    let new_keys = matched_entry_keys(old_entries, new_entries);
+
+    for (key, old_entry) in old_keys.iter().zip(old_entries) {
+        let field = format!("character_book.entries[{}]", key);
+        match new_keys.iter().position(|new_key| new_key == key) {
+            Some(position) => {
+                let new_entry = &new_entries[position];
+                if old_entry != new_entry {
+                    changes.push(FieldChange::text(field, Some(render_entry(old_entry)), Some(render_entry(new_entry))));
+                }
+            },
+            None => changes.push(FieldChange::text(field, Some(render_entry(old_entry)), None)),
+        }
+    }
+    for (key, new_entry) in new_keys.iter().zip(new_entries) {
+        if !old_keys.contains(key) {
+            changes.push(FieldChange::text(format!("character_book.entries[{}]", key), None, Some(render_entry(new_entry))));
+        }
+    }
+    changes
+}
+
+fn list_change(field: &str, old: &[String], new: &[String]) -> FieldChange {
+    let render = |items: &[String]| items.iter().map(|item| format!("{}\n", item)).collect::<String>();
+    let mut change = FieldChange::text(field.to_string(), non_empty(&render(old)), non_empty(&render(new)));
+    change.added = new.iter().filter(|item| !old.contains(item)).cloned().collect();
+    change.removed = old.iter().filter(|item| !new.contains(item)).cloned().collect();
+    change
+}
+
+fn render_book_header(book: &CharacterBook) -> String {
+    let mut text = String::new();
+    for (label, value) in [("name", &book.name), ("description", &book.description)] {
+        if let Some(value) = value {
+            text.push_str(&format!("{}: {}\n", label, value));
+        }
+    }
+    for (label, value) in [("scan_depth", book.scan_depth), ("token_budget", book.token_budget)] {
+        if let Some(value) = value {
+            text.push_str(&format!("{}: {}\n", label, value));
+        }
+    }
+    if let Some(recursive_scanning) = book.recursive_scanning {
+        text.push_str(&format!("recursive_scanning: {}\n", recursive_scanning));
+    }
+    if !book.extensions.is_empty() {
+        text.push_str(&format!("extensions: {}\n", serde_json::Value::Object(book.extensions.clone())));
+    }
+    text
+}
+
+fn render_json<T: serde::Serialize>(value: &T) -> Option<String> {
+    let rendered = serde_json::to_string_pretty(value).expect("Error while serializing JSON");
+    match rendered.as_str() {
+        "{}" | "[]" => None,
+        _ => Some(rendered + "\n"),
+    }
+}
+
+fn non_empty(text: &str) -> Option<String> {
+    if text.is_empty() {
+        None
+    } else {
+        Some(text.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(keys: &[&str], content: &str) -> LorebookEntry {
+        LorebookEntry { keys: keys.iter().map(|key| key.to_string()).collect(), content: content.to_string(), enabled: true, ..LorebookEntry::default() }
+    }
+
+    fn with_entries(entries: Vec<LorebookEntry>) -> CharacterClass {
+        CharacterClass { character_book: Some(CharacterBook { entries, ..CharacterBook::default() }), ..CharacterClass::default() }
+    }
+
+    fn fields(diff: &CharacterDiff) -> Vec<(&str, &str)> {
+        diff.changes.iter().map(|change| (change.field.as_str(), change.kind)).collect()
+    }
+
+    #[test]
+    fn identical_characters_have_no_changes() {
+        let character = CharacterClass { name: "Ann".to_string(), tags: vec!["a".to_string()], ..CharacterClass::default() };
+        assert!(diff_characters(&character, &character.clone()).changes.is_empty());
+    }
+
+    #[test]
+    fn text_and_list_fields() {
+        let old = CharacterClass { name: "Ann".to_string(), personality: "kind\nbrave\n".to_string(), tags: vec!["a".to_string(), "b".to_string()], ..CharacterClass::default() };
+        let new = CharacterClass { name: "Ann".to_string(), personality: "kind\nshy\n".to_string(), tags: vec!["b".to_string(), "c".to_string()], nickname: Some("Annie".to_string()), ..old.clone() };
+        let diff = diff_characters(&old, &new);
+        assert_eq!(fields(&diff), [("personality", "modified"), ("nickname", "added"), ("tags", "modified")]);
+
+        let tags = &diff.changes[2];
+        assert_eq!(tags.added, ["c"]);
+        assert_eq!(tags.removed, ["a"]);
+        assert_eq!(diff.changes[0].line_diff(), [(" ", "kind".to_string()), ("-", "brave".to_string()), ("+", "shy".to_string())]);
+        let unified = diff.unified(3);
+        assert!(unified.contains("--- a/personality\n+++ b/personality\n"));
+        assert!(unified.contains("-brave\n+shy\n"));
+    }
+
+    #[test]
+    fn editing_keys_of_an_entry_without_id_is_a_modification() {
+        let old = with_entries(vec![entry(&["castle"], "A castle"), entry(&["river"], "A river")]);
+        let new = with_entries(vec![entry(&["castle", "keep"], "A castle"), entry(&["river"], "A river")]);
+        assert_eq!(fields(&diff_characters(&old, &new)), [("character_book.entries[idx:0]", "modified")]);
+    }
+
+    #[test]
+    fn removing_an_entry_without_id_does_not_shift_the_others() {
+        let with_id = LorebookEntry { id: Some(7), ..entry(&["tower"], "A tower") };
+        let old = with_entries(vec![entry(&["castle"], "A castle"), with_id.clone(), entry(&["river"], "A river"), entry(&["forest"], "A forest")]);
+        let new = with_entries(vec![entry(&["castle"], "A castle"), with_id, entry(&["forest"], "A forest")]);
+        assert_eq!(fields(&diff_characters(&old, &new)), [("character_book.entries[idx:1]", "removed")]);
+
+        let new = with_entries(vec![entry(&["river"], "A river"), entry(&["castle"], "A castle")]);
+        assert_eq!(matched_entry_keys(&old.character_book.unwrap().entries, &new.character_book.unwrap().entries), ["idx:1", "idx:0"]);
+    }
+
+    #[test]
+    fn entries_with_ids_and_names_are_matched_by_them() {
+        let with_id = |id, content: &str| LorebookEntry { id: Some(id), ..entry(&["key"], content) };
+        let old = with_entries(vec![with_id(1, "one"), with_id(2, "two")]);
+        let new = with_entries(vec![with_id(2, "two"), with_id(3, "three")]);
+        assert_eq!(fields(&diff_characters(&old, &new)), [("character_book.entries[id:1]", "removed"), ("character_book.entries[id:3]", "added")]);
+
+        let named = |name: &str, content: &str| LorebookEntry { name: Some(name.to_string()), ..entry(&[], content) };
+        let old = with_entries(vec![named("castle", "old")]);
+        let new = with_entries(vec![named("castle", "new")]);
+        assert_eq!(fields(&diff_characters(&old, &new)), [("character_book.entries[name:castle]", "modified")]);
+    }
+
+    #[test]
+    fn duplicate_entry_keys_stay_unique() {
+        let named = LorebookEntry { name: Some("same".to_string()), ..entry(&[], "") };
+        assert_eq!(entry_keys(&[named.clone(), named]), ["name:same", "name:same#1"]);
+        let numbered = LorebookEntry { name: Some("1".to_string()), ..entry(&[], "") };
+        let with_id = LorebookEntry { id: Some(1), ..entry(&[], "") };
+        assert_eq!(entry_keys(&[entry(&[], ""), numbered, with_id]), ["idx:0", "name:1", "id:1"]);
+    }
+
+    #[test]
+    fn removed_book_is_reported() {
+        let old = with_entries(vec![entry(&["castle"], "A castle")]);
+        let diff = diff_characters(&old, &CharacterClass::default());
+        assert_eq!(fields(&diff), [("character_book", "removed"), ("character_book.entries[idx:0]", "removed")]);
+
+        let empty = with_entries(Vec::new());
+        assert_eq!(fields(&diff_characters(&empty, &CharacterClass::default())), [("character_book", "removed")]);
+        assert_eq!(fields(&diff_characters(&CharacterClass::default(), &empty)), [("character_book", "added")]);
+    }
+}
@@ -0,0 +1,93 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyFloat, PyList, PyLong, PyString, PyTuple};
+use serde_json::{Map, Number, Value};
+
+pub fn value_to_py(py: Python, value: &Value) -> PyObject {
+    match value {
+        Value::Null => py.None(),
+        Value::Bool(b) => b.into_py(py),
+        Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                i.into_py(py)
+            } else if let Some(u) = n.as_u64() {
+                u.into_py(py)
+            } else {
+                n.as_f64().unwrap_or_default().into_py(py)
+            }
+        },
+        Value::String(s) => s.into_py(py),
+        Value::Array(items) => PyList::new(py, items.iter().map(|item| value_to_py(py, item))).into_py(py),
+        Value::Object(map) => {
+            let dict = PyDict::new(py);
+            for (key, item) in map {
+                dict.set_item(key, value_to_py(py, item)).expect("Error while building dict");
+            }
+            dict.into_py(py)
+        },
+    }
+}
+
+pub fn py_to_value(object: &PyAny) -> PyResult<Value> {
+    if object.is_none() {
+        Ok(Value::Null)
+    } else if let Ok(b) = object.downcast::<PyBool>() {
+        Ok(Value::Bool(b.is_true()))
+    } else if let Ok(i) = object.downcast::<PyLong>() {
+        match i.extract::<i64>() {
+            Ok(i) => Ok(Value::from(i)),
+            Err(_) => Ok(Value::from(i.extract::<u64>()?)),
+        }
+    } else if let Ok(f) = object.downcast::<PyFloat>() {
+        Number::from_f64(f.value())
+            .map(Value::Number)
+            .ok_or_else(|| pyo3::exceptions::PyValueError::new_err(format!("Float value {} cannot be represented", f.value())))
+    } else if let Ok(s) = object.downcast::<PyString>() {
+        Ok(Value::String(s.to_str()?.to_string()))
+    } else if let Ok(dict) = object.downcast::<PyDict>() {
+        let mut map = Map::new();
+        for (key, item) in dict {
+            let key = key.downcast::<PyString>()
+                .map_err(|_| pyo3::exceptions::PyTypeError::new_err(format!("Dictionary keys must be strings, got {}", key)))?;
+            map.insert(key.to_str()?.to_string(), py_to_value(item)?);
+        }
+        Ok(Value::Object(map))
+    } else if let Ok(list) = object.downcast::<PyList>() {
+        Ok(Value::Array(list.iter().map(py_to_value).collect::<PyResult<_>>()?))
+    } else if let Ok(tuple) = object.downcast::<PyTuple>() {
+        Ok(Value::Array(tuple.iter().map(py_to_value).collect::<PyResult<_>>()?))
+    } else {
+        Err(pyo3::exceptions::PyTypeError::new_err(format!("Unsupported value type: {}", object.get_type().name()?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn values_round_trip_through_python() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let value = json!({
+                "name": "Ann", "count": -3, "big": u64::MAX, "ratio": 0.5, "flag": true, "none": null,
+                "book": {"entries": [{"keys": ["a", "b"], "extensions": {}}]},
+            });
+            let object = value_to_py(py, &value);
+            assert_eq!(py_to_value(object.as_ref(py)).unwrap(), value);
+        });
+    }
+
+    #[test]
+    fn python_only_types_are_converted_or_rejected() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let tuple = py.eval("('a', 1)", None, None).unwrap();
+            assert_eq!(py_to_value(tuple).unwrap(), json!(["a", 1]));
+            assert!(py_to_value(py.eval("{1: 'a'}", None, None).unwrap()).is_err());
+            assert!(py_to_value(py.eval("{'a': {1, 2}}", None, None).unwrap()).is_err());
+            assert!(py_to_value(py.eval("float('nan')", None, None).unwrap()).is_err());
+            assert!(py_to_value(py.eval("2 ** 70", None, None).unwrap()).is_err());
+        });
+    }
+}
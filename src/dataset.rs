@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use pyo3::prelude::*;
+use serde_json::{Value, json};
+
+use crate::CharacterClass;
+use crate::chat_log::ChatLog;
+
+pub struct DatasetOptions {
+    pub macros: HashMap<String, String>,
+    pub substitute_macros: bool,
+    pub include_examples: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Role {
+    User,
+    Assistant,
+}
+
+struct Turn {
+    role: Role,
+    speaker: String,
+    text: String,
+}
+
+pub fn export_dataset(character: &CharacterClass, logs: &[ChatLog], format_type: &str, options: &DatasetOptions) -> PyResult<String> {
+    let format_type = format_type.to_lowercase();
+    if !matches!(format_type.as_str(), "sharegpt" | "openai" | "alpaca") {
+        return Err(pyo3::exceptions::PyValueError::new_err("Dataset format not supported, supported formats: 'sharegpt', 'openai', 'alpaca'"));
+    }
+    if let Some(log) = logs.iter().find(|log| !log.character_name.trim().is_empty() && !log.is_for(character)) {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!("Chat log of '{}' does not belong to character '{}'", log.character_name, character.name)));
+    }
+
+    let mut records = Vec::new();
+    for log in logs {
+        let macros = macros_for(character, log, options);
+        let substitute = |text: &str| if options.substitute_macros { substitute_macros(text, &macros) } else { text.to_string() };
+        let system = substitute(&system_message(character, options.include_examples));
+        let turns: Vec<Turn> = merge_turns(log).into_iter().map(|turn| Turn { text: substitute(&turn.text), ..turn }).collect();
+        if turns.is_empty() {
+            continue;
+        }
+
+        match format_type.as_str() {
+            "sharegpt" => {
+                let mut conversations: Vec<Value> = Vec::new();
+                if !system.is_empty() {
+                    conversations.push(json!({ "from": "system", "value": system }));
+                }
+                conversations.extend(turns.iter().map(|turn| json!({
+                    "from": if turn.role == Role::User { "human" } else { "gpt" },
+                    "value": turn.text,
+                })));
+                records.push(json!({ "conversations": conversations }));
+            },
+            "openai" => {
+                let mut messages: Vec<Value> = Vec::new();
+                if !system.is_empty() {
+                    messages.push(json!({ "role": "system", "content": system }));
+                }
+                messages.extend(turns.iter().map(|turn| json!({
+                    "role": if turn.role == Role::User { "user" } else { "assistant" },
+                    "content": turn.text,
+                })));
+                records.push(json!({ "messages": messages }));
+            },
+            _ => {
+                for (index, turn) in turns.iter().enumerate().filter(|(_, turn)| turn.role == Role::Assistant) {
+                    let input: Vec<String> = turns[..index].iter().map(|turn| format!("{}: {}", turn.speaker, turn.text)).collect();
+                    records.push(json!({ "instruction": system, "input": input.join("\n"), "output": turn.text }));
+                }
+            },
+        }
+    }
+
+    if format_type == "sharegpt" {
+        return Ok(serde_json::to_string_pretty(&records).expect("Error while serializing JSON"));
+    }
+    Ok(records.iter().map(|record| record.to_string() + "\n").collect())
+}
+
+fn system_message(character: &CharacterClass, include_examples: bool) -> String {
+    let mut parts = Vec::new();
+    if !character.summary.is_empty() {
+        parts.push(character.summary.clone());
+    }
+    if !character.personality.is_empty() {
+        parts.push(format!("{{{{char}}}}'s personality: {}", character.personality));
+    }
+    if !character.scenario.is_empty() {
+        parts.push(format!("Scenario: {}", character.scenario));
+    }
+    if include_examples && !character.example_messages.trim().is_empty() {
+        let examples: Vec<&str> = character.example_messages.lines().filter(|line| line.trim() != "<START>").collect();
+        parts.push(format!("Example dialogue:\n{}", examples.join("\n").trim()));
+    }
+    parts.join("\n")
+}
+
+fn merge_turns(log: &ChatLog) -> Vec<Turn> {
+    let mut turns: Vec<Turn> = Vec::new();
+    for message in log.messages.iter().filter(|message| !message.is_system && !message.text.trim().is_empty()) {
+        let role = if message.is_user { Role::User } else { Role::Assistant };
+        match turns.last_mut() {
+            Some(turn) if turn.role == role => {
+                turn.text.push_str("\n\n");
+                turn.text.push_str(&message.text);
+            },
+            _ => turns.push(Turn { role, speaker: message.speaker.clone(), text: message.text.clone() }),
+        }
+    }
+    turns
+}
+
+fn macros_for(character: &CharacterClass, log: &ChatLog, options: &DatasetOptions) -> HashMap<String, String> {
+    let mut macros = HashMap::from([
+        ("char".to_string(), character.name.clone()),
+        ("user".to_string(), log.user_name.clone()),
+    ]);
+    macros.extend(options.macros.iter().map(|(name, value)| (name.to_lowercase(), value.clone())));
+    macros
+}
+
+fn substitute_macros(text: &str, macros: &HashMap<String, String>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(['{', '<']) {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let (open, close) = if rest.starts_with("{{") { ("{{", "}}") } else { ("<", ">") };
+        let replacement = rest.strip_prefix(open)
+            .and_then(|inner| inner.find(close).map(|end| &inner[..end]))
+            .and_then(|name| {
+                let key = name.trim().to_lowercase();
+                let key = match (open, key.as_str()) {
+                    ("<", "user") => "user",
+                    ("<", "bot" | "char") => "char",
+                    ("<", _) => return None,
+                    _ => key.as_str(),
+                };
+                macros.get(key).map(|value| (open.len() + name.len() + close.len(), value))
+            });
+        match replacement {
+            Some((length, value)) => {
+                out.push_str(value);
+                rest = &rest[length..];
+            },
+            None => {
+                let length = rest.chars().next().map(char::len_utf8).unwrap_or(1);
+                out.push_str(&rest[..length]);
+                rest = &rest[length..];
+            },
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_log::ChatMessage;
+
+    fn options(substitute_macros: bool, include_examples: bool) -> DatasetOptions {
+        DatasetOptions { macros: HashMap::from([("Place".to_string(), "Town".to_string())]), substitute_macros, include_examples }
+    }
+
+    fn character() -> CharacterClass {
+        CharacterClass::default()
+            .with_name("Ann")
+            .with_personality("Kind to {{user}}")
+            .with_scenario("In {{place}}")
+            .with_example_messages("<START>\n{{user}}: Hi\n{{char}}: Hello")
+    }
+
+    fn message(text: &str, is_user: bool) -> ChatMessage {
+        ChatMessage { speaker: if is_user { "Bob" } else { "Ann" }.to_string(), text: text.to_string(), is_user, ..ChatMessage::default() }
+    }
+
+    fn log() -> ChatLog {
+        ChatLog {
+            character_name: "Ann".to_string(),
+            user_name: "Bob".to_string(),
+            messages: vec![
+                message("Hi <user>", false),
+                message("Hey", true),
+                message("Are you there?", true),
+                ChatMessage { is_system: true, ..message("System note", false) },
+                message("  ", false),
+                message("Yes, {{user}}", false),
+            ],
+            ..ChatLog::default()
+        }
+    }
+
+    fn records(format_type: &str, options: &DatasetOptions) -> Vec<Value> {
+        let output = export_dataset(&character(), &[log()], format_type, options).unwrap();
+        if format_type == "sharegpt" {
+            return serde_json::from_str(&output).unwrap();
+        }
+        output.lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+    }
+
+    #[test]
+    fn sharegpt_merges_turns_and_substitutes_macros() {
+        let records = records("sharegpt", &options(true, false));
+        assert_eq!(records, [json!({"conversations": [
+            {"from": "system", "value": "Ann's personality: Kind to Bob\nScenario: In Town"},
+            {"from": "gpt", "value": "Hi Bob"},
+            {"from": "human", "value": "Hey\n\nAre you there?"},
+            {"from": "gpt", "value": "Yes, Bob"},
+        ]})]);
+    }
+
+    #[test]
+    fn openai_keeps_macros_when_disabled_and_injects_examples() {
+        let records = records("OpenAI", &options(false, true));
+        let messages = records[0]["messages"].as_array().unwrap();
+        assert_eq!(messages[0]["content"], "{{char}}'s personality: Kind to {{user}}\nScenario: In {{place}}\nExample dialogue:\n{{user}}: Hi\n{{char}}: Hello");
+        assert_eq!(messages.iter().map(|message| message["role"].as_str().unwrap()).collect::<Vec<&str>>(), ["system", "assistant", "user", "assistant"]);
+        assert_eq!(messages[1]["content"], "Hi <user>");
+    }
+
+    #[test]
+    fn alpaca_writes_one_record_per_reply() {
+        let records = records("alpaca", &options(true, false));
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["input"], "");
+        assert_eq!(records[0]["output"], "Hi Bob");
+        assert_eq!(records[1]["input"], "Ann: Hi Bob\nBob: Hey\n\nAre you there?");
+        assert_eq!(records[1]["output"], "Yes, Bob");
+        assert_eq!(records[1]["instruction"], "Ann's personality: Kind to Bob\nScenario: In Town");
+    }
+
+    #[test]
+    fn rejects_unknown_formats_and_foreign_logs() {
+        pyo3::prepare_freethreaded_python();
+        assert!(export_dataset(&character(), &[log()], "csv", &options(true, false)).is_err());
+        let foreign = ChatLog { character_name: "Bob".to_string(), ..log() };
+        let error = export_dataset(&character(), &[foreign], "openai", &options(true, false)).err().unwrap();
+        assert!(error.to_string().contains("does not belong to character 'Ann'"));
+        let unnamed = ChatLog { character_name: String::new(), ..log() };
+        assert_eq!(export_dataset(&character(), &[unnamed, ChatLog::default()], "openai", &options(true, false)).unwrap().lines().count(), 1);
+        assert_eq!(export_dataset(&character(), &[], "sharegpt", &options(true, false)).unwrap(), "[]");
+    }
+
+    #[test]
+    fn macro_substitution_edge_cases() {
+        let macros = HashMap::from([("char".to_string(), "Ann".to_string()), ("user".to_string(), "Bob".to_string())]);
+        assert_eq!(substitute_macros("{{ Char }} <BOT> <user> <b>{{unknown}} {{char", &macros), "Ann Ann Bob <b>{{unknown}} {{char");
+        assert_eq!(substitute_macros("ąę {{user}}ż", &macros), "ąę Bobż");
+        assert_eq!(substitute_macros("", &macros), "");
+    }
+}
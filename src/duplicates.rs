@@ -0,0 +1,298 @@
+use std::collections::{HashMap, HashSet};
+use pyo3::prelude::*;
+use sha2::{Digest, Sha256};
+
+use crate::{CharacterClass, load_character_path};
+
+const MINHASH_PERMUTATIONS: usize = 64;
+const LSH_BANDS: usize = 16;
+const LSH_ROWS: usize = MINHASH_PERMUTATIONS / LSH_BANDS;
+const SHINGLE_SIZE: usize = 3;
+const MIN_WORD_SHINGLES: usize = 4;
+const CHARACTER_SHINGLE_SIZE: usize = 4;
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+#[derive(Clone)]
+#[pyclass]
+pub struct DuplicateGroup {
+    #[pyo3(get)]
+    kind: &'static str,
+    #[pyo3(get)]
+    paths: Vec<String>,
+    #[pyo3(get)]
+    similarity: f64,
+}
+
+#[pymethods]
+impl DuplicateGroup {
+    fn __repr__(&self) -> String {
+        format!("<DuplicateGroup {} {:.2} {:?}>", self.kind, self.similarity, self.paths)
+    }
+}
+
+// Files that share a fingerprint, with the MinHash signature of the first one.
+struct FingerprintGroup {
+    paths: Vec<String>,
+    signature: Option<Vec<u64>>,
+}
+
+// Every text is prefixed with its length and every list with its item count, so content can't move between fields or lists unnoticed.
+pub fn fingerprint(character: &CharacterClass) -> String {
+    let mut hasher = Sha256::new();
+    for (_, text) in character.text_fields() {
+        hash_text(&mut hasher, text);
+    }
+    for (field, items) in character.list_fields() {
+        if field == "tags" {
+            let mut tags: Vec<String> = items.iter().map(|tag| normalize(tag)).collect();
+            tags.sort();
+            tags.dedup();
+            hash_list(&mut hasher, &tags);
+        } else {
+            hash_list(&mut hasher, items);
+        }
+    }
+    let entries = character.character_book.as_ref().map(|book| book.entries.as_slice()).unwrap_or_default();
+    hasher.update((entries.len() as u64).to_le_bytes());
+    for entry in entries {
+        hash_list(&mut hasher, &entry.keys);
+        hash_text(&mut hasher, &entry.content);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+fn hash_text(hasher: &mut Sha256, text: &str) {
+    let normalized = normalize(text);
+    hasher.update((normalized.len() as u64).to_le_bytes());
+    hasher.update(normalized.as_bytes());
+}
+
+fn hash_list(hasher: &mut Sha256, items: &[String]) {
+    hasher.update((items.len() as u64).to_le_bytes());
+    for item in items {
+        hash_text(hasher, item);
+    }
+}
+
+/// Returns the duplicate groups and a `(path, error)` pair for every file that failed to load.
+pub fn find_duplicates(paths: Vec<String>, threshold: f64) -> (Vec<DuplicateGroup>, Vec<(String, String)>) {
+    let mut by_fingerprint: Vec<FingerprintGroup> = Vec::new();
+    let mut fingerprint_index: HashMap<String, usize> = HashMap::new();
+    let mut errors = Vec::new();
+
+    for path in paths {
+        let character = match load_character_path(&path) {
+            Ok(character) => character,
+            Err(error) => {
+                errors.push((path, error.to_string()));
+                continue;
+            },
+        };
+        let character_fingerprint = fingerprint(&character);
+        match fingerprint_index.get(&character_fingerprint) {
+            Some(&index) => by_fingerprint[index].paths.push(path),
+            None => {
+                fingerprint_index.insert(character_fingerprint, by_fingerprint.len());
+                let signature = minhash_signature(&format!("{}\n{}", character.personality, character.greeting_message));
+                by_fingerprint.push(FingerprintGroup { paths: vec![path], signature });
+            },
+        }
+    }
+
+    let mut groups: Vec<DuplicateGroup> = by_fingerprint.iter()
+        .filter(|group| group.paths.len() > 1)
+        .map(|group| DuplicateGroup { kind: "exact", paths: group.paths.clone(), similarity: 1.0 })
+        .collect();
+
+    let mut parents: Vec<usize> = (0..by_fingerprint.len()).collect();
+    let mut group_similarity: Vec<f64> = vec![1.0; by_fingerprint.len()];
+    let mut buckets: HashMap<(usize, u64), Vec<usize>> = HashMap::new();
+    for (index, group) in by_fingerprint.iter().enumerate() {
+        let Some(signature) = &group.signature else {
+            continue;
+        };
+        for band in 0..LSH_BANDS {
+            let rows = &signature[band * LSH_ROWS..(band + 1) * LSH_ROWS];
+            let band_hash = rows.iter().fold(FNV_OFFSET_BASIS, |hash, row| fnv1a(hash, &row.to_le_bytes()));
+            buckets.entry((band, band_hash)).or_default().push(index);
+        }
+    }
+
+    let mut compared: HashSet<(usize, usize)> = HashSet::new();
+    for candidates in buckets.values() {
+        for (position, &a) in candidates.iter().enumerate() {
+            for &b in &candidates[position + 1..] {
+                if !compared.insert((a, b)) {
+                    continue;
+                }
+                let (Some(signature_a), Some(signature_b)) = (&by_fingerprint[a].signature, &by_fingerprint[b].signature) else {
+                    continue;
+                };
+                let similarity = estimate_similarity(signature_a, signature_b);
+                if similarity >= threshold {
+                    let root_a = find_root(&mut parents, a);
+                    let root_b = find_root(&mut parents, b);
+                    let merged_similarity = group_similarity[root_a].min(group_similarity[root_b]).min(similarity);
+                    parents[root_b] = root_a;
+                    group_similarity[root_a] = merged_similarity;
+                }
+            }
+        }
+    }
+
+    let mut clusters: HashMap<usize, Vec<usize>> = HashMap::new();
+    for index in 0..by_fingerprint.len() {
+        let root = find_root(&mut parents, index);
+        clusters.entry(root).or_default().push(index);
+    }
+    let mut near_groups: Vec<DuplicateGroup> = clusters.into_iter()
+        .filter(|(_, members)| members.len() > 1)
+        .map(|(root, members)| DuplicateGroup {
+            kind: "near",
+            paths: members.iter().flat_map(|&member| by_fingerprint[member].paths.clone()).collect(),
+            similarity: group_similarity[root],
+        })
+        .collect();
+    near_groups.sort_by(|a, b| a.paths.cmp(&b.paths));
+    groups.extend(near_groups);
+    (groups, errors)
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ").to_lowercase()
+}
+
+// Word shingles only work for longer texts, a short greeting would be a single shingle that no small edit survives,
+// so texts with few words are shingled by characters instead.
+fn minhash_signature(text: &str) -> Option<Vec<u64>> {
+    let normalized = normalize(text);
+    let words: Vec<&str> = normalized.split(' ').filter(|word| !word.is_empty()).collect();
+    if words.is_empty() {
+        return None;
+    }
+    if words.len() >= SHINGLE_SIZE + MIN_WORD_SHINGLES - 1 {
+        return Some(signature(words.windows(SHINGLE_SIZE).map(|shingle| shingle.join(" "))));
+    }
+    let characters: Vec<char> = normalized.chars().collect();
+    if characters.len() < CHARACTER_SHINGLE_SIZE {
+        return Some(signature(std::iter::once(normalized)));
+    }
+    Some(signature(characters.windows(CHARACTER_SHINGLE_SIZE).map(|shingle| shingle.iter().collect())))
+}
+
+// Signatures use FNV-1a instead of the std hasher, whose output may change between Rust releases.
+fn signature(shingles: impl Iterator<Item = String>) -> Vec<u64> {
+    let mut signature = vec![u64::MAX; MINHASH_PERMUTATIONS];
+    for shingle in shingles {
+        for (seed, slot) in signature.iter_mut().enumerate() {
+            let hash = fnv1a(fnv1a(FNV_OFFSET_BASIS, &(seed as u64).to_le_bytes()), shingle.as_bytes());
+            *slot = (*slot).min(hash);
+        }
+    }
+    signature
+}
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME))
+}
+
+fn estimate_similarity(a: &[u64], b: &[u64]) -> f64 {
+    let equal = a.iter().zip(b).filter(|(a, b)| a == b).count();
+    equal as f64 / a.len() as f64
+}
+
+fn find_root(parents: &mut [usize], index: usize) -> usize {
+    let mut root = index;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    let mut current = index;
+    while parents[current] != root {
+        let next = parents[current];
+        parents[current] = root;
+        current = next;
+    }
+    root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempPath;
+
+    fn write_character(file_name: &str, personality: &str, greeting: &str) -> TempPath {
+        let path = TempPath::new(&format!("duplicates_{}.json", file_name));
+        let json = serde_json::json!({"name": "Ann", "personality": personality, "first_mes": greeting});
+        std::fs::write(path.path(), json.to_string()).unwrap();
+        path
+    }
+
+    #[test]
+    fn fingerprint_ignores_whitespace_case_and_metadata() {
+        let character = CharacterClass { name: "Ann".to_string(), personality: "Kind  and\nbrave".to_string(), tags: vec!["b".to_string(), "a".to_string()], ..CharacterClass::default() };
+        let same = CharacterClass { personality: "kind and brave".to_string(), tags: vec!["A".to_string(), "b".to_string()], modified_time: Some(5), source: Some("hub".to_string()), ..character.clone() };
+        assert_eq!(fingerprint(&character), fingerprint(&same));
+        assert_ne!(fingerprint(&character), fingerprint(&CharacterClass { personality: "kind".to_string(), ..character.clone() }));
+        assert_ne!(fingerprint(&character), fingerprint(&CharacterClass { system_prompt: "Be brief".to_string(), ..character.clone() }));
+        assert_ne!(fingerprint(&character), fingerprint(&CharacterClass { creator: "someone".to_string(), ..character.clone() }));
+        assert_ne!(fingerprint(&character), fingerprint(&CharacterClass { group_only_greetings: vec!["Hi".to_string()], ..character.clone() }));
+    }
+
+    #[test]
+    fn fingerprint_separates_lists() {
+        let entry = |keys: &[&str], content: &str| crate::LorebookEntry {
+            keys: keys.iter().map(|key| key.to_string()).collect(),
+            content: content.to_string(),
+            ..crate::LorebookEntry::default()
+        };
+        let with = |greetings: &[&str], entries: Vec<crate::LorebookEntry>| CharacterClass {
+            alternate_greetings: greetings.iter().map(|greeting| greeting.to_string()).collect(),
+            character_book: Some(crate::CharacterBook { entries, ..crate::CharacterBook::default() }),
+            ..CharacterClass::default()
+        };
+        assert_ne!(fingerprint(&with(&["a", "b", "c"], Vec::new())), fingerprint(&with(&["a"], vec![entry(&["b"], "c")])));
+        assert_ne!(fingerprint(&with(&[], vec![entry(&["a\nb"], "c")])), fingerprint(&with(&[], vec![entry(&["a", "b"], "c")])));
+    }
+
+    #[test]
+    fn short_texts_use_character_shingles() {
+        let a = minhash_signature("Hello there!").unwrap();
+        let b = minhash_signature("Hello there!!").unwrap();
+        assert!(estimate_similarity(&a, &b) > 0.5);
+        assert!(minhash_signature("  ").is_none());
+        assert!(minhash_signature("hi").is_some());
+
+        let long = "the knight guards the castle gate every night and never sleeps";
+        let edited = "the knight guards the castle gate every night and rarely sleeps";
+        assert!(estimate_similarity(&minhash_signature(long).unwrap(), &minhash_signature(edited).unwrap()) > 0.5);
+    }
+
+    #[test]
+    fn signatures_are_stable() {
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(minhash_signature("hi").unwrap()[0], fnv1a(fnv1a(FNV_OFFSET_BASIS, &0u64.to_le_bytes()), b"hi"));
+    }
+
+    #[test]
+    fn groups_exact_and_near_files_and_reports_errors() {
+        pyo3::prepare_freethreaded_python();
+        let personality = "A brave knight who guards the castle gate every night and never sleeps at all";
+        let original = write_character("original", personality, "Halt! Who goes there?");
+        let copy = write_character("copy", personality, "Halt!  Who goes there?");
+        let edited = write_character("edited", personality, "Halt! Who goes there??");
+        let other = write_character("other", "A cheerful baker selling bread in the town square every morning", "Fresh bread!");
+        let missing = TempPath::new("duplicates_missing.json");
+
+        let paths = [&original, &copy, &edited, &other, &missing].map(|path| path.as_str().to_string());
+        let (groups, errors) = find_duplicates(paths.to_vec(), 0.5);
+        let summary: Vec<(&str, Vec<String>)> = groups.iter().map(|group| (group.kind, group.paths.clone())).collect();
+        assert_eq!(summary, [
+            ("exact", vec![paths[0].clone(), paths[1].clone()]),
+            ("near", vec![paths[0].clone(), paths[1].clone(), paths[2].clone()]),
+        ]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, paths[4]);
+        assert!(!errors[0].1.is_empty());
+    }
+}
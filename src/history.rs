@@ -0,0 +1,129 @@
+use pyo3::prelude::*;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::CharacterClass;
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[pyclass]
+pub struct Revision {
+    #[pyo3(get)]
+    #[serde(default)]
+    pub author: Option<String>,
+    #[pyo3(get)]
+    pub timestamp: i64,
+    #[pyo3(get)]
+    #[serde(default)]
+    pub summary: String,
+    #[pyo3(get)]
+    pub hash: String,
+}
+
+#[pymethods]
+impl Revision {
+    fn __repr__(&self) -> String {
+        format!("<Revision {} {} {}>", self.timestamp, self.hash.chars().take(12).collect::<String>(), self.summary)
+    }
+}
+
+/// SHA-256 of every content field. Where the character came from (image path, timestamps, source, tools)
+/// and its history are left out, so re-saving unchanged content keeps the same hash.
+pub fn content_hash(character: &CharacterClass) -> String {
+    // serde_json objects keep their keys sorted, which makes the serialization canonical.
+    let mut content = serde_json::Map::new();
+    for (field, text) in character.text_fields() {
+        content.insert(field.to_string(), serde_json::Value::from(text));
+    }
+    for (field, items) in character.list_fields() {
+        content.insert(field.to_string(), serde_json::Value::from(items));
+    }
+    content.insert("nickname".to_string(), serde_json::json!(character.nickname));
+    content.insert("character_book".to_string(), serde_json::json!(character.character_book));
+    content.insert("extensions".to_string(), serde_json::Value::Object(character.extensions.clone()));
+    content.insert("assets".to_string(), serde_json::Value::from(character.assets.clone()));
+    content.insert("embedded_assets".to_string(), serde_json::json!(character.embedded_assets));
+    let serialized = serde_json::to_string(&content).expect("Error while serializing JSON");
+    format!("{:x}", Sha256::digest(serialized.as_bytes()))
+}
+
+pub fn new_revision(character: &CharacterClass, author: Option<String>, summary: String, timestamp: i64) -> Revision {
+    Revision { author, timestamp, summary, hash: content_hash(character) }
+}
+
+/// Appends an export revision unless the last revision already has the full content hash or `exported_hash`,
+/// the hash of what the export format keeps.
+pub fn history_for_export(character: &CharacterClass, exported_hash: &str, author: Option<String>, summary: String, timestamp: i64) -> Vec<Revision> {
+    let mut history = character.history.clone();
+    let unchanged = history.last().is_some_and(|revision| revision.hash == exported_hash || revision.hash == content_hash(character));
+    if !unchanged {
+        history.push(Revision { author, timestamp, summary, hash: exported_hash.to_string() });
+    }
+    history
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn character() -> CharacterClass {
+        CharacterClass { name: "Ann".to_string(), personality: "kind".to_string(), ..CharacterClass::default() }
+    }
+
+    #[test]
+    fn hash_follows_content() {
+        let hash = content_hash(&character());
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, content_hash(&character()));
+        assert_ne!(hash, content_hash(&CharacterClass { personality: "brave".to_string(), ..character() }));
+        assert_ne!(hash, content_hash(&CharacterClass { creator: "someone".to_string(), ..character() }));
+        assert_ne!(hash, content_hash(&CharacterClass { tags: vec!["a".to_string()], ..character() }));
+        assert_ne!(hash, content_hash(&CharacterClass { nickname: Some(String::new()), ..character() }));
+        assert_ne!(hash, content_hash(&CharacterClass { group_only_greetings: vec![String::new()], ..character() }));
+        assert_ne!(hash, content_hash(&CharacterClass { assets: vec![serde_json::json!(null)], ..character() }));
+        assert_eq!(hash, content_hash(&CharacterClass { image_path: Some("card.png".to_string()), modified_time: Some(5), ..character() }));
+    }
+
+    #[test]
+    fn hash_ignores_extension_key_order() {
+        let mut first = character();
+        first.extensions.insert("b".to_string(), serde_json::json!(1));
+        first.extensions.insert("a".to_string(), serde_json::json!({"y": 1, "x": 2}));
+        let mut second = character();
+        second.extensions.insert("a".to_string(), serde_json::json!({"x": 2, "y": 1}));
+        second.extensions.insert("b".to_string(), serde_json::json!(1));
+        assert_eq!(content_hash(&first), content_hash(&second));
+    }
+
+    #[test]
+    fn export_appends_revision_only_for_new_content() {
+        let mut character = character();
+        let history = history_for_export(&character, &content_hash(&character), Some("Alice".to_string()), "Exported as tavernai".to_string(), 10);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].author.as_deref(), Some("Alice"));
+        assert_eq!(history[0].timestamp, 10);
+
+        character.history = history;
+        assert_eq!(history_for_export(&character, &content_hash(&character), None, "Exported again".to_string(), 20).len(), 1);
+        assert_eq!(history_for_export(&character, "lossy", None, "Exported again".to_string(), 20).len(), 1);
+        character.scenario = "A castle".to_string();
+        let history = history_for_export(&character, &content_hash(&character), None, "Exported again".to_string(), 20);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].author, None);
+    }
+
+    #[test]
+    fn repr_shortens_any_hash() {
+        let revision = new_revision(&character(), None, "Initial".to_string(), 1);
+        assert_eq!(revision.__repr__(), format!("<Revision 1 {} Initial>", &revision.hash[..12]));
+        let revision = Revision { hash: "é".repeat(20), ..revision };
+        assert_eq!(revision.__repr__(), format!("<Revision 1 {} Initial>", "é".repeat(12)));
+    }
+
+    #[test]
+    fn revisions_deserialize_without_optional_fields() {
+        let revision: Revision = serde_json::from_str(r#"{"timestamp": 5, "hash": "abc"}"#).unwrap();
+        assert_eq!((revision.author, revision.summary.as_str()), (None, ""));
+        assert!(serde_json::from_str::<Revision>(r#"{"timestamp": 5}"#).is_err());
+    }
+}
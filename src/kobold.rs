@@ -0,0 +1,322 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{CharacterBook, CharacterClass, LorebookEntry};
+
+pub const DEFAULT_ANOTE_TEMPLATE: &str = "[Author's note: <|>]";
+const OPPONENT_SEPARATOR: &str = "||$||";
+
+#[derive(Deserialize)]
+pub struct KoboldWorldInfo {
+    #[serde(default, deserialize_with = "loose_string")]
+    key: String,
+    #[serde(default, deserialize_with = "loose_string")]
+    keysecondary: String,
+    #[serde(default, deserialize_with = "loose_string")]
+    content: String,
+    #[serde(default, deserialize_with = "loose_string")]
+    comment: String,
+    #[serde(default, deserialize_with = "loose_bool")]
+    selective: bool,
+    #[serde(default, deserialize_with = "loose_bool")]
+    constant: bool,
+    #[serde(default, deserialize_with = "loose_bool")]
+    widisabled: bool,
+}
+
+#[derive(Deserialize)]
+pub struct KoboldSettings {
+    #[serde(default, deserialize_with = "loose_string")]
+    chatopponent: String,
+}
+
+// Saves edited by hand or by older Lite versions write numbers and 0/1 flags where Lite now writes strings and booleans.
+fn loose_string<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(text) => text,
+        serde_json::Value::Number(number) => number.to_string(),
+        serde_json::Value::Bool(flag) => flag.to_string(),
+        _ => String::new(),
+    })
+}
+
+pub fn loose_optional_string<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    Ok(match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(text) => Some(text),
+        serde_json::Value::Number(number) => Some(number.to_string()),
+        serde_json::Value::Bool(flag) => Some(flag.to_string()),
+        _ => None,
+    })
+}
+
+fn loose_bool<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    Ok(match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::Bool(flag) => flag,
+        serde_json::Value::Number(number) => number.as_f64().is_some_and(|number| number != 0.0),
+        serde_json::Value::String(text) => matches!(text.trim().to_lowercase().as_str(), "true" | "1"),
+        _ => false,
+    })
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct ExportKoboldWorldInfo<'a> {
+    key: String,
+    keysecondary: String,
+    content: &'a str,
+    comment: &'a str,
+    folder: Option<String>,
+    selective: bool,
+    constant: bool,
+    probability: u32,
+    wigroup: &'static str,
+    widisabled: bool,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct ExportKoboldSettings<'a> {
+    chatopponent: &'a str,
+    chatname: &'static str,
+    opmode: u32,
+}
+
+#[derive(Default)]
+pub struct MemorySections {
+    pub summary: Option<String>,
+    pub personality: Option<String>,
+    pub scenario: Option<String>,
+    pub example_messages: Option<String>,
+}
+
+pub fn render_memory(character: &CharacterClass) -> String {
+    let mut sections = Vec::new();
+    if !character.summary.is_empty() {
+        sections.push(format!("Persona: {}", character.summary));
+    }
+    if !character.personality.is_empty() {
+        sections.push(format!("Personality: {}", character.personality));
+    }
+    if !character.scenario.is_empty() {
+        sections.push(format!("[Scenario: {}]", character.scenario));
+    }
+    if !character.example_messages.is_empty() {
+        if character.example_messages.starts_with("<START>") {
+            sections.push(character.example_messages.clone());
+        } else {
+            sections.push(format!("<START>\n{}", character.example_messages));
+        }
+    }
+    sections.join("\n")
+}
+
+pub fn parse_memory(memory: &str) -> MemorySections {
+    let mut sections: [Vec<&str>; 4] = Default::default();
+    let mut current = 1;
+    for line in memory.lines() {
+        if current == 3 {
+            sections[3].push(line);
+            continue;
+        }
+        if let Some(text) = line.strip_prefix("Persona: ") {
+            current = 0;
+            sections[0].push(text);
+        } else if let Some(text) = line.strip_prefix("Personality: ") {
+            current = 1;
+            sections[1].push(text);
+        } else if let Some(text) = line.strip_prefix("[Scenario: ") {
+            current = 2;
+            sections[2].push(text);
+        } else if line.starts_with("<START>") {
+            current = 3;
+            sections[3].push(line);
+        } else {
+            sections[current].push(line);
+        }
+    }
+    let [summary, personality, scenario, example_messages] = sections.map(|lines| {
+        Some(lines.join("\n")).filter(|text| !text.trim().is_empty())
+    });
+    MemorySections {
+        summary,
+        personality,
+        scenario: scenario.map(|scenario| scenario.strip_suffix(']').map(str::to_string).unwrap_or(scenario)),
+        example_messages,
+    }
+}
+
+pub fn opponent_name(settings: &KoboldSettings) -> Option<String> {
+    settings.chatopponent.split(OPPONENT_SEPARATOR).next()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+pub fn render_prompt(character: &CharacterClass) -> String {
+    if character.greeting_message.is_empty() {
+        String::new()
+    } else {
+        format!("\n{}: {}", character.name, character.greeting_message)
+    }
+}
+
+pub fn greeting_from_prompt(prompt: &str, name: &str) -> String {
+    let prompt = prompt.trim_start_matches('\n');
+    prompt.strip_prefix(&format!("{}: ", name)).unwrap_or(prompt).to_string()
+}
+
+pub fn authors_note(character: &CharacterClass) -> &str {
+    character.extensions.get("kobold")
+        .and_then(|kobold| kobold.get("authorsnote"))
+        .and_then(|note| note.as_str())
+        .unwrap_or_default()
+}
+
+pub fn authors_note_extension(authors_note: String) -> serde_json::Map<String, serde_json::Value> {
+    let mut extensions = serde_json::Map::new();
+    if !authors_note.is_empty() {
+        extensions.insert("kobold".to_string(), serde_json::json!({ "authorsnote": authors_note }));
+    }
+    extensions
+}
+
+pub fn settings(character: &CharacterClass) -> ExportKoboldSettings<'_> {
+    ExportKoboldSettings {
+        chatopponent: &character.name,
+        chatname: "User",
+        opmode: 3,
+    }
+}
+
+pub fn world_info_from_book(book: Option<&CharacterBook>) -> Vec<ExportKoboldWorldInfo<'_>> {
+    book.iter().flat_map(|book| &book.entries).map(|entry| ExportKoboldWorldInfo {
+        key: entry.keys.join(","),
+        keysecondary: entry.secondary_keys.as_deref().unwrap_or_default().join(","),
+        content: &entry.content,
+        comment: entry.comment.as_deref().or(entry.name.as_deref()).unwrap_or_default(),
+        folder: None,
+        selective: entry.selective.unwrap_or_default(),
+        constant: entry.constant.unwrap_or_default(),
+        probability: 100,
+        wigroup: "",
+        widisabled: !entry.enabled,
+    }).collect()
+}
+
+/// Kobold keys are a single comma separated string, so keys that contain a comma are split into several keys.
+pub fn book_from_world_info(world_info: Vec<KoboldWorldInfo>) -> Option<CharacterBook> {
+    if world_info.is_empty() {
+        return None;
+    }
+    let split_keys = |keys: &str| keys.split(',').map(|key| key.trim().to_string()).filter(|key| !key.is_empty()).collect::<Vec<String>>();
+    Some(CharacterBook {
+        entries: world_info.into_iter().enumerate().map(|(index, item)| LorebookEntry {
+            keys: split_keys(&item.key),
+            secondary_keys: Some(split_keys(&item.keysecondary)).filter(|keys| !keys.is_empty()),
+            content: item.content,
+            comment: Some(item.comment).filter(|comment| !comment.is_empty()),
+            selective: Some(true).filter(|_| item.selective),
+            constant: Some(true).filter(|_| item.constant),
+            enabled: !item.widisabled,
+            insertion_order: index as i64,
+            ..LorebookEntry::default()
+        }).collect(),
+        ..CharacterBook::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn character() -> CharacterClass {
+        CharacterClass::default()
+            .with_name("Ann")
+            .with_summary("A girl")
+            .with_personality("Kind\nShy")
+            .with_scenario("A town")
+            .with_example_messages("{{user}}: Hi\n{{char}}: Hello")
+            .with_greeting_message("Hi")
+    }
+
+    #[test]
+    fn memory_round_trip() {
+        let character = character();
+        let memory = render_memory(&character);
+        assert_eq!(memory, "Persona: A girl\nPersonality: Kind\nShy\n[Scenario: A town]\n<START>\n{{user}}: Hi\n{{char}}: Hello");
+        let sections = parse_memory(&memory);
+        assert_eq!(sections.summary.as_deref(), Some("A girl"));
+        assert_eq!(sections.personality.as_deref(), Some("Kind\nShy"));
+        assert_eq!(sections.scenario.as_deref(), Some("A town"));
+        assert_eq!(sections.example_messages.as_deref(), Some("<START>\n{{user}}: Hi\n{{char}}: Hello"));
+        assert_eq!(greeting_from_prompt(&render_prompt(&character), "Ann"), "Hi");
+    }
+
+    #[test]
+    fn unlabelled_memory_becomes_personality() {
+        let sections = parse_memory("Ann is kind.\n\n");
+        assert_eq!(sections.personality.as_deref(), Some("Ann is kind.\n"));
+        assert!(sections.summary.is_none() && sections.scenario.is_none() && sections.example_messages.is_none());
+        assert!(parse_memory("").personality.is_none());
+        assert_eq!(parse_memory("[Scenario: unclosed").scenario.as_deref(), Some("unclosed"));
+        assert_eq!(greeting_from_prompt("Once upon a time", "Ann"), "Once upon a time");
+    }
+
+    #[test]
+    fn opponent_name_uses_first_opponent() {
+        let settings: KoboldSettings = serde_json::from_str(r#"{"chatopponent": " Ann ||$||Bob"}"#).unwrap();
+        assert_eq!(opponent_name(&settings).as_deref(), Some("Ann"));
+        let settings: KoboldSettings = serde_json::from_str("{}").unwrap();
+        assert!(opponent_name(&settings).is_none());
+        let settings: KoboldSettings = serde_json::from_str(r#"{"chatopponent": 7}"#).unwrap();
+        assert_eq!(opponent_name(&settings).as_deref(), Some("7"));
+        let settings: KoboldSettings = serde_json::from_str(r#"{"chatopponent": null}"#).unwrap();
+        assert!(opponent_name(&settings).is_none());
+    }
+
+    #[test]
+    fn world_info_accepts_numeric_flags_and_values() {
+        let world_info: Vec<KoboldWorldInfo> = serde_json::from_str(r#"[
+            {"key": 1999, "content": "A year", "selective": 1, "constant": "true", "widisabled": 0, "comment": null}
+        ]"#).unwrap();
+        let book = book_from_world_info(world_info).unwrap();
+        assert_eq!(book.entries[0].keys, ["1999"]);
+        assert_eq!(book.entries[0].selective, Some(true));
+        assert_eq!(book.entries[0].constant, Some(true));
+        assert!(book.entries[0].enabled);
+        assert!(book.entries[0].comment.is_none());
+
+        let char_data: crate::LoadCharacterClass = serde_json::from_str(r#"{"authorsnote": 42, "prompt": null, "worldinfo": [{"key": 1, "content": "One"}]}"#).unwrap();
+        assert_eq!(char_data.authorsnote.as_deref(), Some("42"));
+        assert!(char_data.prompt.is_none());
+        let char_data: crate::LoadCharacterClass = serde_json::from_str(r#"{"authorsnote": null, "prompt": 7}"#).unwrap();
+        assert!(char_data.authorsnote.is_none());
+        assert_eq!(char_data.prompt.as_deref(), Some("7"));
+    }
+
+    #[test]
+    fn world_info_round_trip() {
+        let world_info: Vec<KoboldWorldInfo> = serde_json::from_str(r#"[
+            {"key": "town, city", "keysecondary": "night", "content": "A town", "comment": "Town", "selective": true, "widisabled": true},
+            {"key": "", "content": "Always", "constant": true}
+        ]"#).unwrap();
+        let book = book_from_world_info(world_info).unwrap();
+        assert_eq!(book.entries[0].keys, ["town", "city"]);
+        assert_eq!(book.entries[0].secondary_keys.as_deref(), Some(&["night".to_string()][..]));
+        assert!(!book.entries[0].enabled);
+        assert_eq!(book.entries[1].constant, Some(true));
+        assert!(book.entries[1].keys.is_empty());
+
+        let exported = serde_json::to_value(world_info_from_book(Some(&book))).unwrap();
+        assert_eq!(exported[0]["key"], "town,city");
+        assert_eq!(exported[0]["keysecondary"], "night");
+        assert_eq!(exported[0]["comment"], "Town");
+        assert_eq!(exported[0]["widisabled"], true);
+        assert_eq!(exported[1]["constant"], true);
+        assert!(book_from_world_info(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn authors_note_is_kept_in_extensions() {
+        assert!(authors_note_extension(String::new()).is_empty());
+        let character = character().with_extensions(authors_note_extension("Be brief".to_string()));
+        assert_eq!(authors_note(&character), "Be brief");
+    }
+}
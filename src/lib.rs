@@ -22,14 +22,49 @@
     SOFTWARE.
 */
 
+#![allow(non_local_definitions)]
+
 use pyo3::prelude::*;
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 use base64::{Engine, engine::GeneralPurpose, engine::GeneralPurposeConfig, alphabet::STANDARD};
 use png::Decoder;
 use chrono::Utc;
 use std::{fs, fs::File};
-use std::io::{Read, Write, Cursor, BufReader, Seek, SeekFrom};
-
+use std::io::{Read, Write, BufReader};
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+
+mod tokens;
+mod validation;
+mod schema;
+mod convert;
+mod comparison;
+mod three_way;
+mod history;
+mod duplicates;
+mod library;
+mod batch;
+mod card_reader;
+mod agnai;
+mod persona;
+mod backyard;
+mod kobold;
+mod chub;
+mod markdown;
+mod chat_log;
+mod dataset;
+mod losses;
+mod risu;
+#[cfg(test)]
+mod test_util;
+
+
+const SUPPORTED_FORMATS: &str = "'tavernai', 'sillytavern', 'textgenerationwebui', 'pygmalion', 'aicompanion', 'chara_card_v2', 'risuai', 'agnaistic', 'backyard', 'koboldai', 'neutral'";
+
+fn unsupported_format() -> PyErr {
+    pyo3::exceptions::PyValueError::new_err(format!("Format not supported, supported formats: {}", SUPPORTED_FORMATS))
+}
 
 static PROGRAM_INFO: ProgramInfo = ProgramInfo {
     name: "aichar Python library",
@@ -38,9 +73,9 @@ static PROGRAM_INFO: ProgramInfo = ProgramInfo {
 };
 
 
-#[derive(Clone)]
-#[pyclass]
-struct CharacterClass {
+#[derive(Clone, Default)]
+#[pyclass(module = "aichar")]
+pub struct CharacterClass {
     name: String,
     summary: String,
     personality: String,
@@ -49,47 +84,299 @@ struct CharacterClass {
     example_messages: String,
     image_path: Option<String>, 
     created_time: Option<i64>,
+    modified_time: Option<i64>,
+    metadata_version: Option<u32>,
+    source: Option<String>,
+    tools: Vec<ToolInfo>,
+    tags: Vec<String>,
+    alternate_greetings: Vec<String>,
+    group_only_greetings: Vec<String>,
+    nickname: Option<String>,
+    creator: String,
+    character_version: String,
+    creator_notes: String,
+    system_prompt: String,
+    post_history_instructions: String,
+    character_book: Option<CharacterBook>,
+    extensions: serde_json::Map<String, serde_json::Value>,
+    assets: Vec<serde_json::Value>,
+    embedded_assets: BTreeMap<String, String>,
+    history: Vec<history::Revision>,
+    revision_author: Option<String>,
+    declared_spec: DeclaredSpec,
+}
+
+/// The `spec` and `spec_version` a loaded card declared, kept so validation can check them.
+#[derive(Clone, Default)]
+pub struct DeclaredSpec {
+    pub spec: Option<serde_json::Value>,
+    pub spec_version: Option<serde_json::Value>,
+}
+
+// The declared spec describes the file the character came from rather than the character, so equality and hashing leave it out.
+// Both destructure every field, so a new field has to be added to them.
+impl PartialEq for CharacterClass {
+    fn eq(&self, other: &Self) -> bool {
+        let CharacterClass {
+            name, summary, personality, scenario, greeting_message, example_messages, image_path, created_time, modified_time, metadata_version, source, tools, tags, alternate_greetings, group_only_greetings, nickname, creator, character_version, creator_notes, system_prompt, post_history_instructions, character_book, extensions, assets, embedded_assets, history, revision_author,
+            declared_spec: _,
+        } = self;
+        *name == other.name
+            && *summary == other.summary
+            && *personality == other.personality
+            && *scenario == other.scenario
+            && *greeting_message == other.greeting_message
+            && *example_messages == other.example_messages
+            && *image_path == other.image_path
+            && *created_time == other.created_time
+            && *modified_time == other.modified_time
+            && *metadata_version == other.metadata_version
+            && *source == other.source
+            && *tools == other.tools
+            && *tags == other.tags
+            && *alternate_greetings == other.alternate_greetings
+            && *group_only_greetings == other.group_only_greetings
+            && *nickname == other.nickname
+            && *creator == other.creator
+            && *character_version == other.character_version
+            && *creator_notes == other.creator_notes
+            && *system_prompt == other.system_prompt
+            && *post_history_instructions == other.post_history_instructions
+            && *character_book == other.character_book
+            && *extensions == other.extensions
+            && *assets == other.assets
+            && *embedded_assets == other.embedded_assets
+            && *history == other.history
+            && *revision_author == other.revision_author
+    }
+}
+
+impl CharacterClass {
+    fn compared_fields(&self) -> serde_json::Value {
+        let CharacterClass {
+            name, summary, personality, scenario, greeting_message, example_messages, image_path, created_time, modified_time, metadata_version, source, tools, tags, alternate_greetings, group_only_greetings, nickname, creator, character_version, creator_notes, system_prompt, post_history_instructions, character_book, extensions, assets, embedded_assets, history, revision_author,
+            declared_spec: _,
+        } = self;
+        serde_json::json!({
+            "name": name,
+            "summary": summary,
+            "personality": personality,
+            "scenario": scenario,
+            "greeting_message": greeting_message,
+            "example_messages": example_messages,
+            "image_path": image_path,
+            "created_time": created_time,
+            "modified_time": modified_time,
+            "metadata_version": metadata_version,
+            "source": source,
+            "tools": tools,
+            "tags": tags,
+            "alternate_greetings": alternate_greetings,
+            "group_only_greetings": group_only_greetings,
+            "nickname": nickname,
+            "creator": creator,
+            "character_version": character_version,
+            "creator_notes": creator_notes,
+            "system_prompt": system_prompt,
+            "post_history_instructions": post_history_instructions,
+            "character_book": character_book,
+            "extensions": extensions,
+            "assets": assets,
+            "embedded_assets": embedded_assets,
+            "history": history,
+            "revision_author": revision_author,
+        })
+    }
 }
 
 #[pymethods]
 impl CharacterClass {
+    #[new]
+    #[pyo3(signature = (name = "", summary = "", personality = "", scenario = "", greeting_message = "", example_messages = "", image_path = None, tags = None, alternate_greetings = None, character_book = None, source = None, extensions = None, assets = None,
+        group_only_greetings = None, nickname = None, creator = "", character_version = "", creator_notes = "", system_prompt = "", post_history_instructions = ""))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(name: &str, summary: &str, personality: &str, scenario: &str, greeting_message: &str, example_messages: &str, image_path: Option<&str>,
+        tags: Option<Vec<String>>, alternate_greetings: Option<Vec<String>>, character_book: Option<&PyAny>, source: Option<String>,
+        extensions: Option<&PyAny>, assets: Option<&PyAny>, group_only_greetings: Option<Vec<String>>, nickname: Option<String>,
+        creator: &str, character_version: &str, creator_notes: &str, system_prompt: &str, post_history_instructions: &str) -> PyResult<Self> {
+        let character_book = character_book.map(character_book_from_py).transpose()?;
+        let extensions = extensions.map(extensions_from_py).transpose()?;
+        let assets = assets.map(assets_from_py).transpose()?;
+        CharacterClass::default()
+            .with_name(name)
+            .with_summary(summary)
+            .with_personality(personality)
+            .with_scenario(scenario)
+            .with_greeting_message(greeting_message)
+            .with_example_messages(example_messages)
+            .with_tags(tags.unwrap_or_default())
+            .with_alternate_greetings(alternate_greetings.unwrap_or_default())
+            .with_group_only_greetings(group_only_greetings.unwrap_or_default())
+            .with_nickname(nickname)
+            .with_creator(creator)
+            .with_character_version(character_version)
+            .with_creator_notes(creator_notes)
+            .with_system_prompt(system_prompt)
+            .with_post_history_instructions(post_history_instructions)
+            .with_character_book(character_book)
+            .with_source(source)
+            .with_extensions(extensions.unwrap_or_default())
+            .with_assets(assets.unwrap_or_default())
+            .with_image_path(image_path.unwrap_or_default())
+    }
+
     #[getter]
-    fn name(&self) -> &String {
+    pub fn name(&self) -> &String {
         &self.name
     }
 
     #[getter]
-    fn summary(&self) -> &String {
+    pub fn summary(&self) -> &String {
         &self.summary
     }
 
     #[getter]
-    fn personality(&self) -> &String {
+    pub fn personality(&self) -> &String {
         &self.personality
     }
 
     #[getter]
-    fn scenario(&self) -> &String {
+    pub fn scenario(&self) -> &String {
         &self.scenario
     }
 
     #[getter]
-    fn greeting_message(&self) -> &String {
+    pub fn greeting_message(&self) -> &String {
         &self.greeting_message
     }
 
     #[getter]
-    fn example_messages(&self) -> &String {
+    pub fn example_messages(&self) -> &String {
         &self.example_messages
     }
 
     #[getter]
-    fn image_path(&self) -> Option<&String> {
+    pub fn image_path(&self) -> Option<&String> {
         self.image_path.as_ref()
     }
 
     #[getter]
-    fn data_summary(&self) -> String {
+    pub fn tags(&self) -> Vec<String> {
+        self.tags.clone()
+    }
+
+    #[getter]
+    pub fn alternate_greetings(&self) -> Vec<String> {
+        self.alternate_greetings.clone()
+    }
+
+    #[getter]
+    pub fn group_only_greetings(&self) -> Vec<String> {
+        self.group_only_greetings.clone()
+    }
+
+    #[getter]
+    pub fn nickname(&self) -> Option<&String> {
+        self.nickname.as_ref()
+    }
+
+    #[getter]
+    pub fn creator(&self) -> &String {
+        &self.creator
+    }
+
+    #[getter]
+    pub fn character_version(&self) -> &String {
+        &self.character_version
+    }
+
+    #[getter]
+    pub fn creator_notes(&self) -> &String {
+        &self.creator_notes
+    }
+
+    #[getter]
+    pub fn system_prompt(&self) -> &String {
+        &self.system_prompt
+    }
+
+    #[getter]
+    pub fn post_history_instructions(&self) -> &String {
+        &self.post_history_instructions
+    }
+
+    #[getter]
+    pub fn character_book(&self, py: Python) -> PyObject {
+        match &self.character_book {
+            Some(book) => convert::value_to_py(py, &serde_json::to_value(book).expect("Error while serializing character book")),
+            None => py.None(),
+        }
+    }
+
+    #[getter]
+    pub fn extensions(&self, py: Python) -> PyObject {
+        convert::value_to_py(py, &serde_json::Value::Object(self.extensions.clone()))
+    }
+
+    #[getter]
+    pub fn chub(&self) -> Option<chub::ChubInfo> {
+        chub::chub_from_extensions(&self.extensions)
+    }
+
+    #[getter]
+    pub fn assets(&self, py: Python) -> PyObject {
+        convert::value_to_py(py, &serde_json::Value::Array(self.assets.clone()))
+    }
+
+    #[getter]
+    pub fn embedded_assets(&self, py: Python) -> PyResult<PyObject> {
+        let engine = GeneralPurpose::new(&STANDARD, GeneralPurposeConfig::new());
+        let assets = pyo3::types::PyDict::new(py);
+        for (uri, data) in &self.embedded_assets {
+            let data = engine.decode(data)
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Embedded asset '{}' is not valid base64: {}", uri, e)))?;
+            assets.set_item(uri, pyo3::types::PyBytes::new(py, &data))?;
+        }
+        Ok(assets.into())
+    }
+
+    #[getter]
+    pub fn created_time(&self) -> Option<i64> {
+        self.created_time
+    }
+
+    #[getter]
+    pub fn modified_time(&self) -> Option<i64> {
+        self.modified_time
+    }
+
+    #[getter]
+    pub fn metadata_version(&self) -> Option<u32> {
+        self.metadata_version
+    }
+
+    #[getter]
+    pub fn source(&self) -> Option<&String> {
+        self.source.as_ref()
+    }
+
+    #[getter]
+    pub fn tools(&self) -> Vec<ToolInfo> {
+        self.tools.clone()
+    }
+
+    #[getter]
+    pub fn history(&self) -> Vec<history::Revision> {
+        self.history.clone()
+    }
+
+    #[getter]
+    pub fn revision_author(&self) -> Option<String> {
+        self.revision_author.clone()
+    }
+
+    #[getter]
+    pub fn data_summary(&self) -> String {
         let mut character_str = String::new();
 
         character_str.push_str(&format!("Name: {}\n", self.name));
@@ -136,173 +423,394 @@ impl CharacterClass {
         self.example_messages = example_messages.to_string();
     }
 
+    #[setter]
+    fn set_tags(&mut self, tags: Vec<String>) {
+        self.tags = tags;
+    }
+
+    #[setter]
+    fn set_alternate_greetings(&mut self, alternate_greetings: Vec<String>) {
+        self.alternate_greetings = alternate_greetings;
+    }
+
+    #[setter]
+    fn set_group_only_greetings(&mut self, group_only_greetings: Vec<String>) {
+        self.group_only_greetings = group_only_greetings;
+    }
+
+    #[setter]
+    fn set_nickname(&mut self, nickname: Option<String>) {
+        self.nickname = nickname;
+    }
+
+    #[setter]
+    fn set_creator(&mut self, creator: &str) {
+        self.creator = creator.to_string();
+    }
+
+    #[setter]
+    fn set_character_version(&mut self, character_version: &str) {
+        self.character_version = character_version.to_string();
+    }
+
+    #[setter]
+    fn set_creator_notes(&mut self, creator_notes: &str) {
+        self.creator_notes = creator_notes.to_string();
+    }
+
+    #[setter]
+    fn set_system_prompt(&mut self, system_prompt: &str) {
+        self.system_prompt = system_prompt.to_string();
+    }
+
+    #[setter]
+    fn set_post_history_instructions(&mut self, post_history_instructions: &str) {
+        self.post_history_instructions = post_history_instructions.to_string();
+    }
+
+    #[setter]
+    fn set_character_book(&mut self, character_book: Option<&PyAny>) -> PyResult<()> {
+        self.character_book = character_book.map(character_book_from_py).transpose()?;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_extensions(&mut self, extensions: &PyAny) -> PyResult<()> {
+        self.extensions = extensions_from_py(extensions)?;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_chub(&mut self, chub: Option<chub::ChubInfo>) {
+        chub::update_extensions(&mut self.extensions, chub.as_ref());
+    }
+
+    #[setter]
+    fn set_assets(&mut self, assets: &PyAny) -> PyResult<()> {
+        self.assets = assets_from_py(assets)?;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_embedded_assets(&mut self, embedded_assets: BTreeMap<String, Vec<u8>>) {
+        let engine = GeneralPurpose::new(&STANDARD, GeneralPurposeConfig::new());
+        self.embedded_assets = embedded_assets.into_iter().map(|(uri, data)| (uri, engine.encode(data))).collect();
+    }
+
+    fn apply_risu_module(&mut self, module: &[u8]) -> PyResult<()> {
+        risu::apply_module(self, risu::parse_module(module)?);
+        Ok(())
+    }
+
+    #[setter]
+    fn set_revision_author(&mut self, revision_author: Option<String>) {
+        self.revision_author = revision_author;
+    }
+
+    #[setter]
+    fn set_source(&mut self, source: Option<String>) {
+        self.source = source;
+    }
+
     #[setter]
     fn set_image_path(&mut self, image_path: &str) -> PyResult<()> {
-        if image_path.to_lowercase().ends_with(".png") {
-            if let Ok(metadata) = fs::metadata(image_path) {
-                if metadata.is_file() {
-                    self.image_path = Some(image_path.to_string());
-                    return Ok(());
-                } else {
-                    return Err(pyo3::exceptions::PyValueError::new_err("Specified path is not a file"));
-                }
-            } else {
-                return Err(pyo3::exceptions::PyValueError::new_err(format!("'{}' file does not exist", image_path)));
-            }
+        self.image_path = Some(validate_image_path(image_path)?);
+        Ok(())
+    }
+
+    fn token_counts(&self, tokenizer_path: &str) -> PyResult<HashMap<String, usize>> {
+        tokens::count_tokens(self, tokenizer_path)
+    }
+
+    #[pyo3(signature = (summary, author = None))]
+    fn add_revision(&mut self, summary: &str, author: Option<String>) {
+        let revision = history::new_revision(self, author, summary.to_string(), Utc::now().timestamp_millis());
+        self.history.push(revision);
+    }
+
+    fn fingerprint(&self) -> String {
+        duplicates::fingerprint(self)
+    }
+
+    fn validate(&self) -> Vec<validation::Diagnostic> {
+        validation::validate_character(self)
+    }
+
+    #[pyo3(signature = (format_type = None, metadata_mode = "update", reproducible = false, timestamp = None))]
+    fn to_dict(&self, py: Python, format_type: Option<&str>, metadata_mode: &str, reproducible: bool, timestamp: Option<i64>) -> PyResult<PyObject> {
+        let value = export_as_value(self, format_type, &ExportOptions::new(metadata_mode, reproducible, timestamp)?)?;
+        Ok(convert::value_to_py(py, &value))
+    }
+
+    #[getter]
+    pub fn personality_attributes(&self, py: Python) -> PyResult<PyObject> {
+        let dict = pyo3::types::PyDict::new(py);
+        for (key, values) in persona::parse_attributes(&self.personality) {
+            dict.set_item(key, values)?;
+        }
+        Ok(dict.into_py(py))
+    }
+
+    #[pyo3(signature = (attributes, style = "wpp"))]
+    fn set_personality_attributes(&mut self, attributes: &pyo3::types::PyDict, style: &str) -> PyResult<()> {
+        let attributes = attributes.iter()
+            .map(|(key, values)| Ok((key.extract::<String>()?, values.extract::<Vec<String>>()?)))
+            .collect::<PyResult<persona::Attributes>>()?;
+        self.personality = persona::render_attributes(&self.name, &attributes, style)?;
+        Ok(())
+    }
+
+    fn personality_as(&self, style: &str) -> PyResult<String> {
+        let attributes = persona::parse_attributes(&self.personality);
+        if attributes.is_empty() {
+            return Err(pyo3::exceptions::PyValueError::new_err("Personality is not written in W++, square bracket or Boostyle format"));
         }
-        Err(pyo3::exceptions::PyValueError::new_err("Invalid file extension. Image file must have a .png extension"))
+        persona::render_attributes(&self.name, &attributes, style)
     }
 
-    fn export_json(&self, format_type: &str) -> PyResult<String> {
-        export_as_json(self, format_type)
+    fn __repr__(&self) -> String {
+        format!("<CharacterClass {:?}>", self.name)
     }
 
-    fn export_json_file(&self, format_type: &str, export_json_path: &str) -> PyResult<()> {
-        let json_string = export_as_json(self, format_type)?;
+    fn __eq__(&self, other: &Self) -> bool {
+        self == other
+    }
+
+    fn __hash__(&self) -> u64 {
+        // serde_json maps keep their keys sorted, so equal characters serialize the same way.
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.compared_fields().to_string().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &PyAny) -> Self {
+        self.clone()
+    }
+
+    fn __getstate__(&self) -> PyResult<String> {
+        character_state(self)
+    }
+
+    fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        *self = character_from_state(state)?;
+        Ok(())
+    }
+
+    #[pyo3(signature = (format_type, metadata_mode = "update", reproducible = false, timestamp = None))]
+    fn export_json(&self, format_type: &str, metadata_mode: &str, reproducible: bool, timestamp: Option<i64>) -> PyResult<String> {
+        export_as_json(self, format_type, &ExportOptions::new(metadata_mode, reproducible, timestamp)?)
+    }
+
+    #[pyo3(signature = (format_type, export_json_path, metadata_mode = "update", reproducible = false, timestamp = None))]
+    fn export_json_file(&self, format_type: &str, export_json_path: &str, metadata_mode: &str, reproducible: bool, timestamp: Option<i64>) -> PyResult<()> {
+        let json_string = export_as_json(self, format_type, &ExportOptions::new(metadata_mode, reproducible, timestamp)?)?;
         let mut file = File::create(export_json_path)?;
         file.write_all(json_string.as_bytes()).expect("Error while writing to json file");
         Ok(())
     }
 
-    fn export_neutral_json(&self) -> PyResult<String> {
-        export_as_neutral_json(self)
+    #[pyo3(signature = (metadata_mode = "update", reproducible = false, timestamp = None))]
+    fn export_neutral_json(&self, metadata_mode: &str, reproducible: bool, timestamp: Option<i64>) -> PyResult<String> {
+        export_as_json(self, "neutral", &ExportOptions::new(metadata_mode, reproducible, timestamp)?)
     }
 
-    fn export_neutral_json_file(&self, export_json_path: &str) -> PyResult<()> {
-        let json_string = export_as_neutral_json(self)?;
+    #[pyo3(signature = (export_json_path, metadata_mode = "update", reproducible = false, timestamp = None))]
+    fn export_neutral_json_file(&self, export_json_path: &str, metadata_mode: &str, reproducible: bool, timestamp: Option<i64>) -> PyResult<()> {
+        let json_string = export_as_json(self, "neutral", &ExportOptions::new(metadata_mode, reproducible, timestamp)?)?;
         let mut file = File::create(export_json_path)?;
         file.write_all(json_string.as_bytes())?;
         Ok(())
     }
 
-    fn export_yaml(&self, format_type: &str) -> PyResult<String> {
-        export_as_yaml(self, format_type)
+    #[pyo3(signature = (format_type, metadata_mode = "update", reproducible = false, timestamp = None))]
+    fn export_yaml(&self, format_type: &str, metadata_mode: &str, reproducible: bool, timestamp: Option<i64>) -> PyResult<String> {
+        export_as_yaml(self, format_type, &ExportOptions::new(metadata_mode, reproducible, timestamp)?)
     }
 
-    fn export_yaml_file(&self, format_type: &str, export_yaml_path: &str) -> PyResult<()> {
-        let yaml_string = export_as_yaml(self, format_type)?;
+    #[pyo3(signature = (format_type, export_yaml_path, metadata_mode = "update", reproducible = false, timestamp = None))]
+    fn export_yaml_file(&self, format_type: &str, export_yaml_path: &str, metadata_mode: &str, reproducible: bool, timestamp: Option<i64>) -> PyResult<()> {
+        let yaml_string = export_as_yaml(self, format_type, &ExportOptions::new(metadata_mode, reproducible, timestamp)?)?;
         let mut file = File::create(export_yaml_path)?;
         file.write_all(yaml_string.as_bytes()).expect("Error while writing to yaml file");
         Ok(())
     }
 
-    fn export_neutral_yaml(&self) -> PyResult<String> {
-        export_as_neutral_yaml(self)
+    #[pyo3(signature = (metadata_mode = "update", reproducible = false, timestamp = None))]
+    fn export_neutral_yaml(&self, metadata_mode: &str, reproducible: bool, timestamp: Option<i64>) -> PyResult<String> {
+        export_as_yaml(self, "neutral", &ExportOptions::new(metadata_mode, reproducible, timestamp)?)
     }
 
-    fn export_neutral_yaml_file(&self, export_yaml_path: &str) -> PyResult<()> {
-        let yaml_string = export_as_neutral_yaml(self)?;
+    #[pyo3(signature = (export_yaml_path, metadata_mode = "update", reproducible = false, timestamp = None))]
+    fn export_neutral_yaml_file(&self, export_yaml_path: &str, metadata_mode: &str, reproducible: bool, timestamp: Option<i64>) -> PyResult<()> {
+        let yaml_string = export_as_yaml(self, "neutral", &ExportOptions::new(metadata_mode, reproducible, timestamp)?)?;
         let mut file = File::create(export_yaml_path)?;
         file.write_all(yaml_string.as_bytes())?;
         Ok(())
     }
 
-    fn export_card(&self, format_type: &str) -> PyResult<Vec<u8>> {
-        export_as_card(self, format_type)
+    #[pyo3(signature = (format_type, metadata_mode = "update", reproducible = false, timestamp = None))]
+    fn export_toml(&self, format_type: &str, metadata_mode: &str, reproducible: bool, timestamp: Option<i64>) -> PyResult<String> {
+        export_as_toml(self, Some(format_type), &ExportOptions::new(metadata_mode, reproducible, timestamp)?)
+    }
+
+    #[pyo3(signature = (format_type, export_toml_path, metadata_mode = "update", reproducible = false, timestamp = None))]
+    fn export_toml_file(&self, format_type: &str, export_toml_path: &str, metadata_mode: &str, reproducible: bool, timestamp: Option<i64>) -> PyResult<()> {
+        let toml_string = export_as_toml(self, Some(format_type), &ExportOptions::new(metadata_mode, reproducible, timestamp)?)?;
+        let mut file = File::create(export_toml_path)?;
+        file.write_all(toml_string.as_bytes())?;
+        Ok(())
+    }
+
+    #[pyo3(signature = (metadata_mode = "update", reproducible = false, timestamp = None))]
+    fn export_neutral_toml(&self, metadata_mode: &str, reproducible: bool, timestamp: Option<i64>) -> PyResult<String> {
+        export_as_toml(self, None, &ExportOptions::new(metadata_mode, reproducible, timestamp)?)
+    }
+
+    #[pyo3(signature = (export_toml_path, metadata_mode = "update", reproducible = false, timestamp = None))]
+    fn export_neutral_toml_file(&self, export_toml_path: &str, metadata_mode: &str, reproducible: bool, timestamp: Option<i64>) -> PyResult<()> {
+        let toml_string = export_as_toml(self, None, &ExportOptions::new(metadata_mode, reproducible, timestamp)?)?;
+        let mut file = File::create(export_toml_path)?;
+        file.write_all(toml_string.as_bytes())?;
+        Ok(())
+    }
+
+    #[pyo3(signature = (metadata_mode = "update", reproducible = false, timestamp = None))]
+    fn export_markdown(&self, metadata_mode: &str, reproducible: bool, timestamp: Option<i64>) -> PyResult<String> {
+        Ok(export_as_markdown(self, &ExportOptions::new(metadata_mode, reproducible, timestamp)?))
+    }
+
+    #[pyo3(signature = (export_markdown_path, metadata_mode = "update", reproducible = false, timestamp = None))]
+    fn export_markdown_file(&self, export_markdown_path: &str, metadata_mode: &str, reproducible: bool, timestamp: Option<i64>) -> PyResult<()> {
+        let markdown_string = export_as_markdown(self, &ExportOptions::new(metadata_mode, reproducible, timestamp)?);
+        let mut file = File::create(export_markdown_path)?;
+        file.write_all(markdown_string.as_bytes())?;
+        Ok(())
+    }
+
+    #[pyo3(signature = (logs, format_type, macros = None, substitute_macros = true, include_examples = false))]
+    fn export_dataset(&self, logs: Vec<chat_log::ChatLog>, format_type: &str, macros: Option<HashMap<String, String>>, substitute_macros: bool, include_examples: bool) -> PyResult<String> {
+        let options = dataset::DatasetOptions { macros: macros.unwrap_or_default(), substitute_macros, include_examples };
+        dataset::export_dataset(self, &logs, format_type, &options)
+    }
+
+    #[pyo3(signature = (logs, format_type, export_dataset_path, macros = None, substitute_macros = true, include_examples = false))]
+    #[allow(clippy::too_many_arguments)]
+    fn export_dataset_file(&self, logs: Vec<chat_log::ChatLog>, format_type: &str, export_dataset_path: &str, macros: Option<HashMap<String, String>>, substitute_macros: bool, include_examples: bool) -> PyResult<()> {
+        let options = dataset::DatasetOptions { macros: macros.unwrap_or_default(), substitute_macros, include_examples };
+        let dataset_string = dataset::export_dataset(self, &logs, format_type, &options)?;
+        let mut file = File::create(export_dataset_path)?;
+        file.write_all(dataset_string.as_bytes())?;
+        Ok(())
+    }
+
+    #[pyo3(signature = (format_type, metadata_mode = "update", reproducible = false, timestamp = None))]
+    fn export_card(&self, format_type: &str, metadata_mode: &str, reproducible: bool, timestamp: Option<i64>) -> PyResult<Vec<u8>> {
+        export_as_card(self, format_type, &ExportOptions::new(metadata_mode, reproducible, timestamp)?)
     }
 
-    fn export_card_file(&self, format_type: &str, export_card_path: &str) -> PyResult<()> {
-        let bytes = export_as_card(self, format_type)?;
+    #[pyo3(signature = (format_type, export_card_path, metadata_mode = "update", reproducible = false, timestamp = None))]
+    fn export_card_file(&self, format_type: &str, export_card_path: &str, metadata_mode: &str, reproducible: bool, timestamp: Option<i64>) -> PyResult<()> {
+        let bytes = export_as_card(self, format_type, &ExportOptions::new(metadata_mode, reproducible, timestamp)?)?;
         let mut file = File::create(export_card_path)?;
         file.write_all(&bytes)?;
         Ok(())
     }
 
-    fn export_neutral_card(&self) -> PyResult<Vec<u8>> {
-        export_as_card(self, "neutral")
+    #[pyo3(signature = (metadata_mode = "update", reproducible = false, timestamp = None))]
+    fn export_byaf(&self, metadata_mode: &str, reproducible: bool, timestamp: Option<i64>) -> PyResult<Vec<u8>> {
+        backyard::export_byaf(self, &ExportOptions::new(metadata_mode, reproducible, timestamp)?)
+    }
+
+    #[pyo3(signature = (export_byaf_path, metadata_mode = "update", reproducible = false, timestamp = None))]
+    fn export_byaf_file(&self, export_byaf_path: &str, metadata_mode: &str, reproducible: bool, timestamp: Option<i64>) -> PyResult<()> {
+        let bytes = backyard::export_byaf(self, &ExportOptions::new(metadata_mode, reproducible, timestamp)?)?;
+        let mut file = File::create(export_byaf_path)?;
+        file.write_all(&bytes)?;
+        Ok(())
+    }
+
+    fn export_losses(&self, format_type: &str) -> PyResult<Vec<String>> {
+        losses::export_losses(self, format_type)
+    }
+
+    #[pyo3(signature = (metadata_mode = "update", reproducible = false, timestamp = None))]
+    fn export_neutral_card(&self, metadata_mode: &str, reproducible: bool, timestamp: Option<i64>) -> PyResult<Vec<u8>> {
+        export_as_card(self, "neutral", &ExportOptions::new(metadata_mode, reproducible, timestamp)?)
     }
 
-    fn export_neutral_card_file(&self, export_card_path: &str) -> PyResult<()> {
-        let bytes = export_as_card(self, "neutral")?;
+    #[pyo3(signature = (export_card_path, metadata_mode = "update", reproducible = false, timestamp = None))]
+    fn export_neutral_card_file(&self, export_card_path: &str, metadata_mode: &str, reproducible: bool, timestamp: Option<i64>) -> PyResult<()> {
+        let bytes = export_as_card(self, "neutral", &ExportOptions::new(metadata_mode, reproducible, timestamp)?)?;
         let mut file = File::create(export_card_path)?;
         file.write_all(&bytes)?;
         Ok(())
     }
 }
 
-fn export_as_yaml(character: &CharacterClass, format_type: &str) -> PyResult<String> {
-    let current_time = Utc::now().timestamp_millis();
-    match format_type.to_lowercase().as_str() {
-        "tavernai" | "sillytavern" => {
-            let export: ExportTavernAi = ExportTavernAi {
-                name: &character.name,
-                description: &character.summary,
-                personality: &character.personality,
-                scenario: &character.scenario,
-                first_mes: &character.greeting_message,
-                mes_example: &character.example_messages,
-                metadata: Metadata {
-                    version: 1,
-                    created: &character.created_time.unwrap_or(current_time),
-                    modified: current_time,
-                    source: None,
-                    tool: Tooldata {
-                        name: PROGRAM_INFO.name,
-                        version: PROGRAM_INFO.version,
-                        url: PROGRAM_INFO.url,
-                    }
-                },
-            };
-            Ok(serde_yaml::to_string(&export).expect("Error while serializing YAML"))
-        },
-        "textgenerationwebui" | "pygmalion" => {
-            let export: ExportTextGenerationWebuiPygmalion = ExportTextGenerationWebuiPygmalion {
-                char_name: &character.name,
-                char_persona: if character.personality.is_empty() {
-                    &character.summary
-                } else {
-                    &character.personality
-                },
-                world_scenario: &character.scenario,
-                char_greeting: &character.greeting_message,
-                example_dialogue: &character.example_messages,
-                metadata: Metadata {
-                    version: 1,
-                    created: &character.created_time.unwrap_or(current_time),
-                    modified: current_time,
-                    source: None,
-                    tool: Tooldata {
-                        name: PROGRAM_INFO.name,
-                        version: PROGRAM_INFO.version,
-                        url: PROGRAM_INFO.url,
-                    }
-                },
-            };
-            Ok(serde_yaml::to_string(&export).expect("Error while serializing YAML"))
-        },
-        "aicompanion" => {
-            let export: ExportAiCompanion = ExportAiCompanion {
-                name: &character.name,
-                description: if character.personality.is_empty() {
-                    &character.summary
-                } else {
-                    &character.personality
-                },
-                first_mes: &character.greeting_message,
-                mes_example: &character.example_messages,
-                metadata: Metadata {
-                    version: 1,
-                    created: &character.created_time.unwrap_or(current_time),
-                    modified: current_time,
-                    source: None,
-                    tool: Tooldata {
-                        name: PROGRAM_INFO.name,
-                        version: PROGRAM_INFO.version,
-                        url: PROGRAM_INFO.url,
-                    }
-                },
-            };
-            Ok(serde_yaml::to_string(&export).expect("Error while serializing YAML"))
-        },
-        _ => {
-            Err(pyo3::exceptions::PyValueError::new_err("Format not supported, supported formats: 'tavernai', 'sillytavern', 'textgenerationwebui', 'pygmalion', 'aicompanion'"))
+fn export_as_yaml(character: &CharacterClass, format_type: &str, options: &ExportOptions) -> PyResult<String> {
+    Ok(serde_yaml::to_string(&format_export(character, format_type, options)?).expect("Error while serializing YAML"))
+}
+
+fn export_as_toml(character: &CharacterClass, format_type: Option<&str>, options: &ExportOptions) -> PyResult<String> {
+    let mut user_data = vec![
+        ("extensions".to_string(), serde_json::Value::Object(character.extensions.clone())),
+        ("assets".to_string(), serde_json::Value::from(character.assets.clone())),
+    ];
+    if let Some(book) = &character.character_book {
+        user_data.push(("character_book.extensions".to_string(), serde_json::Value::Object(book.extensions.clone())));
+        for (index, entry) in book.entries.iter().enumerate() {
+            user_data.push((format!("character_book.entries[{}].extensions", index), serde_json::Value::Object(entry.extensions.clone())));
+        }
+    }
+    for (path, value) in user_data {
+        if let Some(path) = find_null(&value, path) {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!("TOML has no null value, but '{}' is null", path)));
         }
     }
+    let value = without_nulls(export_as_value(character, format_type, options)?);
+    toml::to_string_pretty(&value)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Error while serializing TOML: {}", e)))
+}
+
+fn find_null(value: &serde_json::Value, path: String) -> Option<String> {
+    match value {
+        serde_json::Value::Null => Some(path),
+        serde_json::Value::Object(map) => map.iter().find_map(|(key, value)| find_null(value, format!("{}.{}", path, key))),
+        serde_json::Value::Array(items) => items.iter().enumerate().find_map(|(index, value)| find_null(value, format!("{}[{}]", path, index))),
+        _ => None,
+    }
 }
 
-fn export_as_neutral_yaml(character: &CharacterClass) -> PyResult<String> {
-    let current_time = Utc::now().timestamp_millis();
-    let export_class: ExportAllCharacterClass = ExportAllCharacterClass {
+fn without_nulls(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => serde_json::Value::Object(map.into_iter()
+            .filter(|(_, value)| !value.is_null())
+            .map(|(key, value)| (key, without_nulls(value)))
+            .collect()),
+        serde_json::Value::Array(items) => serde_json::Value::Array(items.into_iter()
+            .filter(|value| !value.is_null())
+            .map(without_nulls)
+            .collect()),
+        value => value,
+    }
+}
+
+fn export_as_markdown(character: &CharacterClass, options: &ExportOptions) -> String {
+    let current_time = options.current_time(character);
+    markdown::render_markdown(character, export_metadata(character, current_time, "markdown", options))
+}
+
+fn export_as_json(character: &CharacterClass, format_type: &str, options: &ExportOptions) -> PyResult<String> {
+    Ok(serde_json::to_string_pretty(&format_export(character, format_type, options)?).expect("Error while serializing JSON"))
+}
+
+fn neutral_export<'a>(character: &'a CharacterClass, options: &ExportOptions) -> ExportAllCharacterClass<'a> {
+    let current_time = options.current_time(character);
+    ExportAllCharacterClass {
         char_name: &character.name,
         char_persona: if character.personality.is_empty() {
             &character.summary
@@ -318,135 +826,182 @@ fn export_as_neutral_yaml(character: &CharacterClass) -> PyResult<String> {
         scenario: &character.scenario,
         first_mes: &character.greeting_message,
         mes_example: &character.example_messages,
-        metadata: Metadata {
-            version: 1,
-            created: &character.created_time.unwrap_or(current_time),
-            modified: current_time,
-            source: None,
-            tool: Tooldata {
-                name: PROGRAM_INFO.name,
-                version: PROGRAM_INFO.version,
-                url: PROGRAM_INFO.url,
-            }
-        },
+        tags: &character.tags,
+        alternate_greetings: &character.alternate_greetings,
+        group_only_greetings: &character.group_only_greetings,
+        nickname: character.nickname.as_deref(),
+        creator: &character.creator,
+        character_version: &character.character_version,
+        creator_notes: &character.creator_notes,
+        system_prompt: &character.system_prompt,
+        post_history_instructions: &character.post_history_instructions,
+        character_book: character.character_book.as_ref(),
+        extensions: Some(&character.extensions).filter(|extensions| !extensions.is_empty()),
+        assets: &character.assets,
+        metadata: export_metadata(character, current_time, "neutral", options),
+    }
+}
+
+fn export_as_value(character: &CharacterClass, format_type: Option<&str>, options: &ExportOptions) -> PyResult<serde_json::Value> {
+    let export = format_export(character, format_type.unwrap_or("neutral"), options)?;
+    Ok(serde_json::to_value(export).expect("Error while serializing JSON"))
+}
+
+// Every export format goes through this one dispatch. Serializing the returned export directly keeps the field order of the
+// export structs, which a serde_json::Value would sort alphabetically.
+fn format_export<'a>(character: &'a CharacterClass, format_type: &str, options: &ExportOptions) -> PyResult<FormatExport<'a>> {
+    let current_time = options.current_time(character);
+    let export = match format_type.to_lowercase().as_str() {
+        "neutral" => FormatExport::Neutral(neutral_export(character, options)),
+        "tavernai" | "sillytavern" => FormatExport::TavernAi(ExportTavernAi {
+            name: &character.name,
+            description: &character.summary,
+            personality: &character.personality,
+            scenario: &character.scenario,
+            first_mes: &character.greeting_message,
+            mes_example: &character.example_messages,
+            metadata: export_metadata(character, current_time, format_type, options),
+        }),
+        "textgenerationwebui" | "pygmalion" => FormatExport::TextGenerationWebuiPygmalion(ExportTextGenerationWebuiPygmalion {
+            char_name: &character.name,
+            char_persona: if character.personality.is_empty() {
+                &character.summary
+            } else {
+                &character.personality
+            },
+            world_scenario: &character.scenario,
+            char_greeting: &character.greeting_message,
+            example_dialogue: &character.example_messages,
+            metadata: export_metadata(character, current_time, format_type, options),
+        }),
+        "chara_card_v2" => FormatExport::CharaCardV2(chara_card_v2_export(character, current_time, format_type, options)),
+        "risuai" => FormatExport::RisuAi(risuai_export(character, current_time, format_type, options)),
+        "agnaistic" | "agnai" => FormatExport::Agnai(agnai_export(character, current_time, format_type, options)),
+        "backyard" | "faraday" => FormatExport::Backyard(backyard_export(character, current_time, format_type, options)),
+        "koboldai" | "kobold" | "koboldcpp" => FormatExport::Kobold(kobold_export(character, current_time, format_type, options)),
+        "aicompanion" => FormatExport::AiCompanion(ExportAiCompanion {
+            name: &character.name,
+            description: if character.personality.is_empty() {
+                &character.summary
+            } else {
+                &character.personality
+            },
+            first_mes: &character.greeting_message,
+            mes_example: &character.example_messages,
+            metadata: export_metadata(character, current_time, format_type, options),
+        }),
+        _ => return Err(unsupported_format()),
     };
-    Ok(serde_yaml::to_string(&export_class).expect("Error while serializing YAML"))
-}
-
-fn export_as_json(character: &CharacterClass, format_type: &str) -> PyResult<String> {
-    let current_time = Utc::now().timestamp_millis();
-    match format_type.to_lowercase().as_str() {
-        "tavernai" | "sillytavern" => {
-            let export: ExportTavernAi = ExportTavernAi {
-                name: &character.name,
-                description: &character.summary,
-                personality: &character.personality,
-                scenario: &character.scenario,
-                first_mes: &character.greeting_message,
-                mes_example: &character.example_messages,
-                metadata: Metadata {
-                    version: 1,
-                    created: &character.created_time.unwrap_or(current_time),
-                    modified: current_time,
-                    source: None,
-                    tool: Tooldata {
-                        name: PROGRAM_INFO.name,
-                        version: PROGRAM_INFO.version,
-                        url: PROGRAM_INFO.url,
-                    }
-                },
-            };
-            Ok(serde_json::to_string_pretty(&export).expect("Error while serializing JSON"))
-        },
-        "textgenerationwebui" | "pygmalion" => {
-            let export: ExportTextGenerationWebuiPygmalion = ExportTextGenerationWebuiPygmalion {
-                char_name: &character.name,
-                char_persona: if character.personality.is_empty() {
-                    &character.summary
-                } else {
-                    &character.personality
-                },
-                world_scenario: &character.scenario,
-                char_greeting: &character.greeting_message,
-                example_dialogue: &character.example_messages,
-                metadata: Metadata {
-                    version: 1,
-                    created: &character.created_time.unwrap_or(current_time),
-                    modified: current_time,
-                    source: None,
-                    tool: Tooldata {
-                        name: PROGRAM_INFO.name,
-                        version: PROGRAM_INFO.version,
-                        url: PROGRAM_INFO.url,
-                    }
-                },
-            };
-            Ok(serde_json::to_string_pretty(&export).expect("Error while serializing JSON"))
-        },
-        "aicompanion" => {
-            let export: ExportAiCompanion = ExportAiCompanion {
-                name: &character.name,
-                description: if character.personality.is_empty() {
-                    &character.summary
-                } else {
-                    &character.personality
-                },
-                first_mes: &character.greeting_message,
-                mes_example: &character.example_messages,
-                metadata: Metadata {
-                    version: 1,
-                    created: &character.created_time.unwrap_or(current_time),
-                    modified: current_time,
-                    source: None,
-                    tool: Tooldata {
-                        name: PROGRAM_INFO.name,
-                        version: PROGRAM_INFO.version,
-                        url: PROGRAM_INFO.url,
-                    }
-                },
-            };
-            Ok(serde_json::to_string_pretty(&export).expect("Error while serializing JSON"))
+    Ok(export)
+}
+
+fn risuai_export<'a>(character: &'a CharacterClass, current_time: i64, format_type: &str, options: &ExportOptions) -> ExportRisuAi<'a> {
+    let assets = if character.assets.is_empty() {
+        vec![serde_json::json!({ "type": "icon", "uri": "ccdefault:", "name": "main", "ext": "png" })]
+    } else {
+        character.assets.clone()
+    };
+    ExportRisuAi {
+        spec: "chara_card_v3",
+        spec_version: "3.0",
+        data: ExportRisuAiData {
+            name: &character.name,
+            description: &character.summary,
+            personality: &character.personality,
+            scenario: &character.scenario,
+            first_mes: &character.greeting_message,
+            mes_example: &character.example_messages,
+            creator_notes: &character.creator_notes,
+            system_prompt: &character.system_prompt,
+            post_history_instructions: &character.post_history_instructions,
+            alternate_greetings: &character.alternate_greetings,
+            group_only_greetings: &character.group_only_greetings,
+            character_book: character.character_book.clone().map(|mut book| {
+                for entry in &mut book.entries {
+                    entry.use_regex.get_or_insert(false);
+                }
+                book
+            }),
+            tags: &character.tags,
+            creator: &character.creator,
+            character_version: &character.character_version,
+            nickname: character.nickname.as_deref(),
+            extensions: &character.extensions,
+            assets,
         },
-        _ => {
-            Err(pyo3::exceptions::PyValueError::new_err("Format not supported, supported formats: 'tavernai', 'sillytavern', 'textgenerationwebui', 'pygmalion', 'aicompanion'"))
-        }
+        metadata: export_metadata(character, current_time, format_type, options),
     }
 }
 
-fn export_as_neutral_json(character: &CharacterClass) -> PyResult<String> {
-    let current_time = Utc::now().timestamp_millis();
-    let export_class: ExportAllCharacterClass = ExportAllCharacterClass {
-        char_name: &character.name,
-        char_persona: if character.personality.is_empty() {
-            &character.summary
-        } else {
-            &character.personality
+fn chara_card_v2_export<'a>(character: &'a CharacterClass, current_time: i64, format_type: &str, options: &ExportOptions) -> ExportCharaCardV2<'a> {
+    ExportCharaCardV2 {
+        spec: "chara_card_v2",
+        spec_version: "2.0",
+        data: ExportCharaCardV2Data {
+            name: &character.name,
+            description: &character.summary,
+            personality: &character.personality,
+            scenario: &character.scenario,
+            first_mes: &character.greeting_message,
+            mes_example: &character.example_messages,
+            creator_notes: &character.creator_notes,
+            system_prompt: &character.system_prompt,
+            post_history_instructions: &character.post_history_instructions,
+            alternate_greetings: &character.alternate_greetings,
+            character_book: character.character_book.as_ref(),
+            tags: &character.tags,
+            creator: &character.creator,
+            character_version: &character.character_version,
+            extensions: &character.extensions,
         },
-        world_scenario: &character.scenario,
-        char_greeting: &character.greeting_message,
-        example_dialogue: &character.example_messages,
+        metadata: export_metadata(character, current_time, format_type, options),
+    }
+}
+
+fn agnai_export<'a>(character: &'a CharacterClass, current_time: i64, format_type: &str, options: &ExportOptions) -> ExportAgnai<'a> {
+    ExportAgnai {
+        kind: "character",
         name: &character.name,
         description: &character.summary,
-        personality: &character.personality,
+        persona: agnai::export_persona(&character.personality),
         scenario: &character.scenario,
-        first_mes: &character.greeting_message,
-        mes_example: &character.example_messages,
-        metadata: Metadata {
-            version: 1,
-            created: &character.created_time.unwrap_or(current_time),
-            modified: current_time,
-            source: None,
-            tool: Tooldata {
-                name: PROGRAM_INFO.name,
-                version: PROGRAM_INFO.version,
-                url: PROGRAM_INFO.url,
-            }
-        },
-    };
-    Ok(serde_json::to_string_pretty(&export_class).expect("Error while serializing JSON"))
+        greeting: &character.greeting_message,
+        sample_chat: &character.example_messages,
+        alternate_greetings: &character.alternate_greetings,
+        tags: &character.tags,
+        system_prompt: &character.system_prompt,
+        post_history_instructions: &character.post_history_instructions,
+        creator: &character.creator,
+        character_version: &character.character_version,
+        character_book: character.character_book.as_ref().map(agnai::memory_from_book),
+        extensions: Some(&character.extensions).filter(|extensions| !extensions.is_empty()),
+        metadata: export_metadata(character, current_time, format_type, options),
+    }
+}
+
+fn backyard_export<'a>(character: &'a CharacterClass, current_time: i64, format_type: &str, options: &ExportOptions) -> ExportBackyard<'a> {
+    ExportBackyard {
+        character: backyard::backyard_character(character),
+        version: 1,
+        metadata: export_metadata(character, current_time, format_type, options),
+    }
+}
+
+fn kobold_export<'a>(character: &'a CharacterClass, current_time: i64, format_type: &str, options: &ExportOptions) -> ExportKobold<'a> {
+    ExportKobold {
+        gamestarted: !character.greeting_message.is_empty(),
+        prompt: kobold::render_prompt(character),
+        memory: kobold::render_memory(character),
+        authorsnote: kobold::authors_note(character),
+        anotetemplate: kobold::DEFAULT_ANOTE_TEMPLATE,
+        actions: Vec::new(),
+        worldinfo: kobold::world_info_from_book(character.character_book.as_ref()),
+        savedsettings: kobold::settings(character),
+        metadata: export_metadata(character, current_time, format_type, options),
+    }
 }
 
-fn export_as_card(character: &CharacterClass, format_type: &str) -> PyResult<Vec<u8>> {
+fn export_as_card(character: &CharacterClass, format_type: &str, options: &ExportOptions) -> PyResult<Vec<u8>> {
     let character_image = match &character.image_path {
         Some(v) => v,
         None => {
@@ -461,19 +1016,39 @@ fn export_as_card(character: &CharacterClass, format_type: &str) -> PyResult<Vec
     let info = reader.next_frame(&mut buf).map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Failed to read PNG frame from file '{}' (Check if it is a valid, working PNG file): {}", character_image, e)))?;
     let bytes = &buf[..info.buffer_size()];
 
+    let (png_character, asset_chunks) = if format_type.eq_ignore_ascii_case("risuai") || format_type.eq_ignore_ascii_case("neutral") {
+        risu::png_assets(character)
+    } else {
+        (character.clone(), Vec::new())
+    };
+    let character = &png_character;
+
     let mut encoded_data = Vec::new();
     {
         let mut encoder = png::Encoder::new(&mut encoded_data, info.width, info.height);
         encoder.set_color(info.color_type);
         encoder.set_depth(info.bit_depth);
+        encoder.set_compression(png::Compression::Default);
+        encoder.set_filter(png::FilterType::Sub);
+        encoder.set_adaptive_filter(png::AdaptiveFilterType::NonAdaptive);
         let engine = GeneralPurpose::new(&STANDARD, GeneralPurposeConfig::new());
 
-        let character_base64 = if format_type == "neutral" {
-            engine.encode(export_as_neutral_json(character)?)
+        for (keyword, data) in asset_chunks {
+            encoder.add_text_chunk(keyword, data)
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Failed to add text chunk: {}", e)))?;
+        }
+
+        let character_base64 = engine.encode(export_as_json(character, format_type, options)?);
+
+        let character_base64 = if format_type.eq_ignore_ascii_case("risuai") {
+            encoder.add_text_chunk(
+                "ccv3".to_string(),
+                character_base64,
+            ).map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Failed to add text chunk: {}", e)))?;
+            engine.encode(export_as_json(character, "chara_card_v2", options)?)
         } else {
-            engine.encode(export_as_json(character, format_type)?)
+            character_base64
         };
-
         encoder.add_text_chunk(
             "chara".to_string(),
             character_base64,
@@ -485,29 +1060,345 @@ fn export_as_card(character: &CharacterClass, format_type: &str) -> PyResult<Vec
     Ok(encoded_data)
 }
 
+fn export_metadata<'a>(character: &'a CharacterClass, current_time: i64, format_type: &str, options: &ExportOptions) -> Metadata<'a> {
+    let program_tool = Tooldata {
+        name: PROGRAM_INFO.name,
+        version: PROGRAM_INFO.version,
+        url: PROGRAM_INFO.url,
+    };
+    let original_tools: Vec<Tooldata> = character.tools.iter().map(|tool| Tooldata {
+        name: &tool.name,
+        version: &tool.version,
+        url: &tool.url,
+    }).collect();
+
+    let (modified, tool) = match options.metadata_mode {
+        MetadataMode::Update => (current_time, ToolField::Single(program_tool)),
+        MetadataMode::Keep => {
+            let tool = match original_tools.len() {
+                0 => ToolField::Single(program_tool),
+                1 => ToolField::Single(original_tools.into_iter().next().expect("Tool list is not empty")),
+                _ => ToolField::Multiple(original_tools),
+            };
+            (character.modified_time.unwrap_or(current_time), tool)
+        },
+        MetadataMode::Append => {
+            let mut tools = original_tools;
+            let already_listed = tools.last().is_some_and(|tool| tool.name == program_tool.name && tool.version == program_tool.version);
+            if !already_listed {
+                tools.push(program_tool);
+            }
+            (current_time, ToolField::Multiple(tools))
+        },
+    };
+
+    Metadata {
+        version: character.metadata_version.unwrap_or(1),
+        created: character.created_time.unwrap_or(current_time),
+        modified,
+        source: character.source.as_deref(),
+        tool,
+        history: if options.record_history {
+            history::history_for_export(character, &exported_content_hash(character, format_type), character.revision_author.clone(), format!("Exported as {}", format_type.to_lowercase()), current_time)
+        } else {
+            character.history.clone()
+        },
+    }
+}
+
+// Hashes the character as it loads back from the chosen format, so saving and reloading through a lossy format
+// does not look like an edit. Formats that cannot be loaded back from their JSON export hash the full content.
+fn exported_content_hash(character: &CharacterClass, format_type: &str) -> String {
+    let options = ExportOptions { metadata_mode: MetadataMode::Keep, reproducible: true, timestamp: Some(0), record_history: false };
+    let without_history = CharacterClass { history: Vec::new(), ..character.clone() };
+    export_as_value(&without_history, Some(format_type), &options).ok()
+        .and_then(|value| serde_json::from_value::<LoadCharacterClass>(value).ok())
+        .map(|char_data| history::content_hash(&character_from_data(char_data, None)))
+        .unwrap_or_else(|| history::content_hash(character))
+}
+
+struct ExportOptions {
+    metadata_mode: MetadataMode,
+    reproducible: bool,
+    timestamp: Option<i64>,
+    record_history: bool,
+}
+
+impl ExportOptions {
+    fn new(metadata_mode: &str, reproducible: bool, timestamp: Option<i64>) -> PyResult<Self> {
+        let metadata_mode = match metadata_mode.to_lowercase().as_str() {
+            "update" => MetadataMode::Update,
+            "keep" => MetadataMode::Keep,
+            "append" => MetadataMode::Append,
+            _ => {
+                return Err(pyo3::exceptions::PyValueError::new_err("Metadata mode not supported, supported modes: 'update', 'keep', 'append'"));
+            }
+        };
+        Ok(ExportOptions { metadata_mode, reproducible, timestamp, record_history: true })
+    }
+
+    fn current_time(&self, character: &CharacterClass) -> i64 {
+        match self.timestamp {
+            Some(timestamp) => timestamp,
+            None if self.reproducible => character.modified_time.or(character.created_time).unwrap_or(0),
+            None => Utc::now().timestamp_millis(),
+        }
+    }
+}
+
+enum MetadataMode {
+    Update,
+    Keep,
+    Append,
+}
+
+
 struct ProgramInfo {
     name: &'static str,
     version: &'static str,
     url: &'static str,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 struct Metadata<'a> {
     version: u32,
-    created: &'a i64,
+    created: i64,
     modified: i64,
-    source: Option<String>,
-    tool: Tooldata<'a>,
+    source: Option<&'a str>,
+    tool: ToolField<'a>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    history: Vec<history::Revision>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 struct Tooldata<'a> {
     name: &'a str,
     version: &'a str,
     url: &'a str,
 }
 
+#[derive(Serialize, JsonSchema)]
+#[serde(untagged)]
+enum ToolField<'a> {
+    Single(Tooldata<'a>),
+    Multiple(Vec<Tooldata<'a>>),
+}
+
+impl CharacterClass {
+    /// Every text field by name. Diffs, content hashes and duplicate fingerprints all read fields from here and from `list_fields`.
+    pub(crate) fn text_fields(&self) -> [(&'static str, &str); 12] {
+        [
+            ("name", &self.name),
+            ("summary", &self.summary),
+            ("personality", &self.personality),
+            ("scenario", &self.scenario),
+            ("greeting_message", &self.greeting_message),
+            ("example_messages", &self.example_messages),
+            ("nickname", self.nickname.as_deref().unwrap_or_default()),
+            ("creator", &self.creator),
+            ("character_version", &self.character_version),
+            ("creator_notes", &self.creator_notes),
+            ("system_prompt", &self.system_prompt),
+            ("post_history_instructions", &self.post_history_instructions),
+        ]
+    }
+
+    pub(crate) fn list_fields(&self) -> [(&'static str, &[String]); 3] {
+        [
+            ("tags", &self.tags),
+            ("alternate_greetings", &self.alternate_greetings),
+            ("group_only_greetings", &self.group_only_greetings),
+        ]
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn with_summary(mut self, summary: impl Into<String>) -> Self {
+        self.summary = summary.into();
+        self
+    }
+
+    pub fn with_personality(mut self, personality: impl Into<String>) -> Self {
+        self.personality = personality.into();
+        self
+    }
+
+    pub fn with_scenario(mut self, scenario: impl Into<String>) -> Self {
+        self.scenario = scenario.into();
+        self
+    }
+
+    pub fn with_greeting_message(mut self, greeting_message: impl Into<String>) -> Self {
+        self.greeting_message = greeting_message.into();
+        self
+    }
+
+    pub fn with_example_messages(mut self, example_messages: impl Into<String>) -> Self {
+        self.example_messages = example_messages.into();
+        self
+    }
+
+    pub fn with_tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    pub fn with_alternate_greetings(mut self, alternate_greetings: Vec<String>) -> Self {
+        self.alternate_greetings = alternate_greetings;
+        self
+    }
+
+    pub fn with_group_only_greetings(mut self, group_only_greetings: Vec<String>) -> Self {
+        self.group_only_greetings = group_only_greetings;
+        self
+    }
+
+    pub fn with_nickname(mut self, nickname: Option<String>) -> Self {
+        self.nickname = nickname;
+        self
+    }
+
+    pub fn with_creator(mut self, creator: impl Into<String>) -> Self {
+        self.creator = creator.into();
+        self
+    }
+
+    pub fn with_character_version(mut self, character_version: impl Into<String>) -> Self {
+        self.character_version = character_version.into();
+        self
+    }
+
+    pub fn with_creator_notes(mut self, creator_notes: impl Into<String>) -> Self {
+        self.creator_notes = creator_notes.into();
+        self
+    }
+
+    pub fn with_system_prompt(mut self, system_prompt: impl Into<String>) -> Self {
+        self.system_prompt = system_prompt.into();
+        self
+    }
+
+    pub fn with_post_history_instructions(mut self, post_history_instructions: impl Into<String>) -> Self {
+        self.post_history_instructions = post_history_instructions.into();
+        self
+    }
+
+    pub fn with_character_book(mut self, character_book: Option<CharacterBook>) -> Self {
+        self.character_book = character_book;
+        self
+    }
+
+    pub fn with_source(mut self, source: Option<String>) -> Self {
+        self.source = source;
+        self
+    }
+
+    pub fn with_extensions(mut self, extensions: serde_json::Map<String, serde_json::Value>) -> Self {
+        self.extensions = extensions;
+        self
+    }
+
+    pub fn with_assets(mut self, assets: Vec<serde_json::Value>) -> Self {
+        self.assets = assets;
+        self
+    }
+
+    pub fn with_image_path(mut self, image_path: &str) -> PyResult<Self> {
+        self.image_path = match image_path {
+            "" => None,
+            path => Some(validate_image_path(path)?),
+        };
+        Ok(self)
+    }
+}
+
+fn validate_image_path(image_path: &str) -> PyResult<String> {
+    if image_path.to_lowercase().ends_with(".png") {
+        if let Ok(metadata) = fs::metadata(image_path) {
+            if metadata.is_file() {
+                return Ok(image_path.to_string());
+            } else {
+                return Err(pyo3::exceptions::PyValueError::new_err("Specified path is not a file"));
+            }
+        } else {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!("'{}' file does not exist", image_path)));
+        }
+    }
+    Err(pyo3::exceptions::PyValueError::new_err("Invalid file extension. Image file must have a .png extension"))
+}
+
+fn character_book_from_py(character_book: &PyAny) -> PyResult<CharacterBook> {
+    serde_json::from_value(convert::py_to_value(character_book)?)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid character book: {}", e)))
+}
+
+fn extensions_from_py(extensions: &PyAny) -> PyResult<serde_json::Map<String, serde_json::Value>> {
+    match convert::py_to_value(extensions)? {
+        serde_json::Value::Object(map) => Ok(map),
+        _ => Err(pyo3::exceptions::PyTypeError::new_err("Extensions must be a dict")),
+    }
+}
+
+fn assets_from_py(assets: &PyAny) -> PyResult<Vec<serde_json::Value>> {
+    match convert::py_to_value(assets)? {
+        serde_json::Value::Array(items) => Ok(items),
+        _ => Err(pyo3::exceptions::PyTypeError::new_err("Assets must be a list")),
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[pyclass]
+pub struct ToolInfo {
+    #[pyo3(get)]
+    #[serde(default)]
+    name: String,
+    #[pyo3(get)]
+    #[serde(default)]
+    version: String,
+    #[pyo3(get)]
+    #[serde(default)]
+    url: String,
+}
+
+#[pymethods]
+impl ToolInfo {
+    fn __repr__(&self) -> String {
+        format!("<ToolInfo {} {}>", self.name, self.version)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LoadToolField {
+    Multiple(Vec<ToolInfo>),
+    Single(ToolInfo),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LoadMemoryField {
+    Text(String),
+    Book(agnai::AgnaiMemoryBook),
+}
+
 #[derive(Serialize)]
+#[serde(untagged)]
+enum FormatExport<'a> {
+    Neutral(ExportAllCharacterClass<'a>),
+    TavernAi(ExportTavernAi<'a>),
+    TextGenerationWebuiPygmalion(ExportTextGenerationWebuiPygmalion<'a>),
+    AiCompanion(ExportAiCompanion<'a>),
+    CharaCardV2(ExportCharaCardV2<'a>),
+    RisuAi(ExportRisuAi<'a>),
+    Agnai(ExportAgnai<'a>),
+    Backyard(ExportBackyard<'a>),
+    Kobold(ExportKobold<'a>),
+}
+
+#[derive(Serialize, JsonSchema)]
 struct ExportAllCharacterClass<'a> {
     char_name: &'a str,
     char_persona: &'a str,
@@ -520,11 +1411,37 @@ struct ExportAllCharacterClass<'a> {
     scenario: &'a str,
     first_mes: &'a str,
     mes_example: &'a str,
+    #[serde(default, skip_serializing_if = "<[String]>::is_empty")]
+    tags: &'a [String],
+    #[serde(default, skip_serializing_if = "<[String]>::is_empty")]
+    alternate_greetings: &'a [String],
+    #[serde(default, skip_serializing_if = "<[String]>::is_empty")]
+    group_only_greetings: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nickname: Option<&'a str>,
+    #[serde(default, skip_serializing_if = "str::is_empty")]
+    creator: &'a str,
+    #[serde(default, skip_serializing_if = "str::is_empty")]
+    character_version: &'a str,
+    #[serde(default, skip_serializing_if = "str::is_empty")]
+    creator_notes: &'a str,
+    #[serde(default, skip_serializing_if = "str::is_empty")]
+    system_prompt: &'a str,
+    #[serde(default, skip_serializing_if = "str::is_empty")]
+    post_history_instructions: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    character_book: Option<&'a CharacterBook>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    extensions: Option<&'a serde_json::Map<String, serde_json::Value>>,
+    #[serde(default, skip_serializing_if = "<[serde_json::Value]>::is_empty")]
+    assets: &'a [serde_json::Value],
     metadata: Metadata<'a>,
 }
 
 #[derive(Deserialize)]
 struct LoadCharacterClass {
+    spec: Option<serde_json::Value>,
+    spec_version: Option<serde_json::Value>,
     char_name: Option<String>,
     char_persona: Option<String>,
     world_scenario: Option<String>,
@@ -537,15 +1454,184 @@ struct LoadCharacterClass {
     scenario: Option<String>,
     first_mes: Option<String>,
     mes_example: Option<String>,
-    metadata: Option<TimeMetadata>
+    tags: Option<Vec<String>>,
+    #[serde(alias = "alternateGreetings")]
+    alternate_greetings: Option<Vec<String>>,
+    group_only_greetings: Option<Vec<String>>,
+    nickname: Option<String>,
+    creator: Option<String>,
+    #[serde(alias = "characterVersion")]
+    character_version: Option<String>,
+    creator_notes: Option<String>,
+    #[serde(alias = "systemPrompt")]
+    system_prompt: Option<String>,
+    #[serde(alias = "postHistoryInstructions")]
+    post_history_instructions: Option<String>,
+    character_book: Option<CharacterBook>,
+    #[serde(default, deserialize_with = "lenient")]
+    persona: Option<agnai::AgnaiPersona>,
+    greeting: Option<String>,
+    #[serde(rename = "sampleChat")]
+    sample_chat: Option<String>,
+    #[serde(rename = "characterBook", default, deserialize_with = "lenient")]
+    memory_book: Option<agnai::AgnaiMemoryBook>,
+    #[serde(default, deserialize_with = "lenient")]
+    memory: Option<LoadMemoryField>,
+    #[serde(default, deserialize_with = "kobold::loose_optional_string")]
+    authorsnote: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    worldinfo: Option<Vec<kobold::KoboldWorldInfo>>,
+    #[serde(default, deserialize_with = "kobold::loose_optional_string")]
+    prompt: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    savedsettings: Option<kobold::KoboldSettings>,
+    #[serde(rename = "aiName")]
+    ai_name: Option<String>,
+    #[serde(rename = "aiDisplayName")]
+    ai_display_name: Option<String>,
+    #[serde(rename = "aiPersona")]
+    ai_persona: Option<String>,
+    #[serde(rename = "basePrompt")]
+    base_prompt: Option<String>,
+    #[serde(rename = "customDialogue")]
+    custom_dialogue: Option<String>,
+    #[serde(rename = "firstMessage")]
+    first_message: Option<String>,
+    #[serde(rename = "loreItems", default, deserialize_with = "lenient")]
+    lore_items: Option<Vec<backyard::BackyardLoreItem>>,
+    character: Option<Box<LoadCharacterClass>>,
+    extensions: Option<serde_json::Map<String, serde_json::Value>>,
+    assets: Option<Vec<serde_json::Value>>,
+    #[serde(default, deserialize_with = "lenient")]
+    metadata: Option<TimeMetadata>,
+    data: Option<Box<LoadCharacterClass>>,
 }
 
-#[derive(Deserialize)]
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CharacterBook {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scan_depth: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_budget: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recursive_scanning: Option<bool>,
+    #[serde(default)]
+    pub extensions: serde_json::Map<String, serde_json::Value>,
+    #[serde(default)]
+    pub entries: Vec<LorebookEntry>,
+}
+
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct LorebookEntry {
+    #[serde(default)]
+    pub keys: Vec<String>,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub extensions: serde_json::Map<String, serde_json::Value>,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub insertion_order: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub case_sensitive: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "lenient")]
+    pub id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selective: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secondary_keys: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub constant: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "lenient")]
+    pub position: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub use_regex: Option<bool>,
+}
+
+impl LoadCharacterClass {
+    fn with_data_fallback(mut self) -> Self {
+        if let Some(data) = self.data.take() {
+            self.name = self.name.or(data.name);
+            self.description = self.description.or(data.description);
+            self.personality = self.personality.or(data.personality);
+            self.scenario = self.scenario.or(data.scenario);
+            self.first_mes = self.first_mes.or(data.first_mes);
+            self.mes_example = self.mes_example.or(data.mes_example);
+            self.tags = self.tags.or(data.tags);
+            self.alternate_greetings = self.alternate_greetings.or(data.alternate_greetings);
+            self.group_only_greetings = self.group_only_greetings.or(data.group_only_greetings);
+            self.nickname = self.nickname.or(data.nickname);
+            self.creator = self.creator.or(data.creator);
+            self.character_version = self.character_version.or(data.character_version);
+            self.creator_notes = self.creator_notes.or(data.creator_notes);
+            self.system_prompt = self.system_prompt.or(data.system_prompt);
+            self.post_history_instructions = self.post_history_instructions.or(data.post_history_instructions);
+            self.character_book = self.character_book.or(data.character_book);
+            self.extensions = match (self.extensions, data.extensions) {
+                (Some(extensions), Some(data_extensions)) => Some(merge_extensions(extensions, data_extensions)),
+                (extensions, data_extensions) => extensions.or(data_extensions),
+            };
+            self.assets = self.assets.or(data.assets);
+        }
+        if let Some(character) = self.character.take() {
+            self.ai_name = self.ai_name.or(character.ai_name);
+            self.ai_display_name = self.ai_display_name.or(character.ai_display_name);
+            self.ai_persona = self.ai_persona.or(character.ai_persona);
+            self.base_prompt = self.base_prompt.or(character.base_prompt);
+            self.custom_dialogue = self.custom_dialogue.or(character.custom_dialogue);
+            self.first_message = self.first_message.or(character.first_message);
+            self.scenario = self.scenario.or(character.scenario);
+            self.lore_items = self.lore_items.or(character.lore_items);
+        }
+        self
+    }
+}
+
+fn merge_extensions(mut extensions: serde_json::Map<String, serde_json::Value>, other: serde_json::Map<String, serde_json::Value>) -> serde_json::Map<String, serde_json::Value> {
+    for (key, value) in other {
+        extensions.entry(key).or_insert(value);
+    }
+    extensions
+}
+
+fn default_true() -> bool {
+    true
+}
+
+// Fields other frontends own are best effort: a value of the wrong type is dropped instead of failing the load.
+fn lenient<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::de::DeserializeOwned,
+{
+    let value = Option::<serde_json::Value>::deserialize(deserializer)?;
+    Ok(value.and_then(|value| T::deserialize(value).ok()))
+}
+
+#[derive(Default, Deserialize)]
 struct TimeMetadata {
+    #[serde(default, deserialize_with = "lenient")]
+    version: Option<u32>,
     created: Option<i64>,
+    modified: Option<i64>,
+    source: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    tool: Option<LoadToolField>,
+    history: Option<Vec<history::Revision>>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 struct ExportTavernAi<'a> {
     name: &'a str,
     description: &'a str,
@@ -556,7 +1642,123 @@ struct ExportTavernAi<'a> {
     metadata: Metadata<'a>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
+struct ExportCharaCardV2<'a> {
+    #[schemars(schema_with = "schema::spec_v2")]
+    spec: &'static str,
+    #[schemars(schema_with = "schema::spec_version_v2")]
+    spec_version: &'static str,
+    data: ExportCharaCardV2Data<'a>,
+    metadata: Metadata<'a>,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct ExportCharaCardV2Data<'a> {
+    name: &'a str,
+    description: &'a str,
+    personality: &'a str,
+    scenario: &'a str,
+    first_mes: &'a str,
+    mes_example: &'a str,
+    creator_notes: &'a str,
+    system_prompt: &'a str,
+    post_history_instructions: &'a str,
+    alternate_greetings: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    character_book: Option<&'a CharacterBook>,
+    tags: &'a [String],
+    creator: &'a str,
+    character_version: &'a str,
+    extensions: &'a serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct ExportRisuAi<'a> {
+    #[schemars(schema_with = "schema::spec_v3")]
+    spec: &'static str,
+    #[schemars(schema_with = "schema::spec_version_v3")]
+    spec_version: &'static str,
+    data: ExportRisuAiData<'a>,
+    metadata: Metadata<'a>,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct ExportRisuAiData<'a> {
+    name: &'a str,
+    description: &'a str,
+    personality: &'a str,
+    scenario: &'a str,
+    first_mes: &'a str,
+    mes_example: &'a str,
+    creator_notes: &'a str,
+    system_prompt: &'a str,
+    post_history_instructions: &'a str,
+    alternate_greetings: &'a [String],
+    group_only_greetings: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    character_book: Option<CharacterBook>,
+    tags: &'a [String],
+    creator: &'a str,
+    character_version: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nickname: Option<&'a str>,
+    extensions: &'a serde_json::Map<String, serde_json::Value>,
+    assets: Vec<serde_json::Value>,
+}
+
+#[derive(Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct ExportAgnai<'a> {
+    // Files written before the discriminator was added don't have it, so it isn't required.
+    #[serde(default)]
+    #[schemars(schema_with = "schema::kind_character")]
+    kind: &'static str,
+    name: &'a str,
+    description: &'a str,
+    persona: agnai::ExportAgnaiPersona,
+    scenario: &'a str,
+    greeting: &'a str,
+    sample_chat: &'a str,
+    #[serde(default, skip_serializing_if = "<[String]>::is_empty")]
+    alternate_greetings: &'a [String],
+    #[serde(default, skip_serializing_if = "<[String]>::is_empty")]
+    tags: &'a [String],
+    #[serde(default, skip_serializing_if = "str::is_empty")]
+    system_prompt: &'a str,
+    #[serde(default, skip_serializing_if = "str::is_empty")]
+    post_history_instructions: &'a str,
+    #[serde(default, skip_serializing_if = "str::is_empty")]
+    creator: &'a str,
+    #[serde(default, skip_serializing_if = "str::is_empty")]
+    character_version: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    character_book: Option<agnai::ExportAgnaiMemoryBook<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    extensions: Option<&'a serde_json::Map<String, serde_json::Value>>,
+    metadata: Metadata<'a>,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct ExportBackyard<'a> {
+    character: backyard::ExportBackyardCharacter<'a>,
+    version: u32,
+    metadata: Metadata<'a>,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct ExportKobold<'a> {
+    gamestarted: bool,
+    prompt: String,
+    memory: String,
+    authorsnote: &'a str,
+    anotetemplate: &'static str,
+    actions: Vec<String>,
+    worldinfo: Vec<kobold::ExportKoboldWorldInfo<'a>>,
+    savedsettings: kobold::ExportKoboldSettings<'a>,
+    metadata: Metadata<'a>,
+}
+
+#[derive(Serialize, JsonSchema)]
 struct ExportTextGenerationWebuiPygmalion<'a> {
     char_name: &'a str,
     char_persona: &'a str,
@@ -566,7 +1768,7 @@ struct ExportTextGenerationWebuiPygmalion<'a> {
     metadata: Metadata<'a>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 struct ExportAiCompanion<'a> {
     name: &'a str,
     description: &'a str,
@@ -577,54 +1779,50 @@ struct ExportAiCompanion<'a> {
 
 #[pyfunction]
 fn create_character(name: &str, summary: &str, personality: &str, scenario: &str, greeting_message: &str, example_messages: &str, image_path: &str) -> PyResult<CharacterClass> {
-    Ok(CharacterClass {
-        name: name.to_string(),
-        summary: summary.to_string(),
-        personality: personality.to_string(),
-        scenario: scenario.to_string(),
-        greeting_message: greeting_message.to_string(),
-        example_messages: example_messages.to_string(),
-        image_path: Some(image_path.to_string()),
-        created_time: None,
-    })
+    CharacterClass::default()
+        .with_name(name)
+        .with_summary(summary)
+        .with_personality(personality)
+        .with_scenario(scenario)
+        .with_greeting_message(greeting_message)
+        .with_example_messages(example_messages)
+        .with_image_path(image_path)
 }
 
 #[pyfunction]
-fn load_character_json(json: &str) -> PyResult<CharacterClass> {
-    let char_data: LoadCharacterClass = serde_json::from_str(json).expect("Error while parsing json string");
-    Ok(CharacterClass {
-        name: char_data.char_name.unwrap_or(char_data.name.unwrap_or(String::from(""))),
-        summary: char_data.summary.unwrap_or(char_data.description.unwrap_or(String::from(""))),
-        personality: char_data.char_persona.unwrap_or(char_data.personality.unwrap_or(String::from(""))),
-        scenario: char_data.world_scenario.unwrap_or(char_data.scenario.unwrap_or(String::from(""))),
-        greeting_message: char_data.char_greeting.unwrap_or(char_data.first_mes.unwrap_or(String::from(""))),
-        example_messages: char_data.example_dialogue.unwrap_or(char_data.mes_example.unwrap_or(String::from(""))),
-        image_path: None,
-        created_time: char_data.metadata.and_then(|time_metadata| time_metadata.created),
-    })
+#[pyo3(signature = (json, strict = false, format_type = None))]
+fn load_character_json(json: &str, strict: bool, format_type: Option<&str>) -> PyResult<CharacterClass> {
+    let char_data: LoadCharacterClass = if strict {
+        let document: serde_json::Value = serde_json::from_str(json)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Error while parsing json string: {}", e)))?;
+        schema::validate_strict(&document, format_type)?;
+        serde_json::from_value(document)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Error while parsing json string: {}", e)))?
+    } else {
+        serde_json::from_str(json).expect("Error while parsing json string")
+    };
+    Ok(character_from_data(char_data, None))
 }
 
 #[pyfunction]
-fn load_character_json_file(path: &str) -> PyResult<CharacterClass> {
+#[pyo3(signature = (path, strict = false, format_type = None))]
+fn load_character_json_file(path: &str, strict: bool, format_type: Option<&str>) -> PyResult<CharacterClass> {
     let mut file = File::open(path)?;
     let mut buf = String::new();
     file.read_to_string(&mut buf)?;
-    load_character_json(&buf)
+    load_character_json(&buf, strict, format_type)
+}
+
+#[pyfunction]
+fn json_schema(format_type: &str) -> PyResult<String> {
+    let schema = schema::json_schema(format_type)?;
+    Ok(serde_json::to_string_pretty(&schema).expect("Error while serializing JSON"))
 }
 
 #[pyfunction]
 fn load_character_yaml(json: &str) -> PyResult<CharacterClass> {
     let char_data: LoadCharacterClass = serde_yaml::from_str(json).expect("Error while parsing yaml string");
-    Ok(CharacterClass {
-        name: char_data.char_name.unwrap_or(char_data.name.unwrap_or(String::from(""))),
-        summary: char_data.summary.unwrap_or(char_data.description.unwrap_or(String::from(""))),
-        personality: char_data.char_persona.unwrap_or(char_data.personality.unwrap_or(String::from(""))),
-        scenario: char_data.world_scenario.unwrap_or(char_data.scenario.unwrap_or(String::from(""))),
-        greeting_message: char_data.char_greeting.unwrap_or(char_data.first_mes.unwrap_or(String::from(""))),
-        example_messages: char_data.example_dialogue.unwrap_or(char_data.mes_example.unwrap_or(String::from(""))),
-        image_path: None,
-        created_time: char_data.metadata.and_then(|time_metadata| time_metadata.created),
-    })
+    Ok(character_from_data(char_data, None))
 }
 
 #[pyfunction]
@@ -636,148 +1834,290 @@ fn load_character_yaml_file(path: &str) -> PyResult<CharacterClass> {
 }
 
 #[pyfunction]
-fn load_character_card(bytes: &[u8]) -> PyResult<CharacterClass> {
-    let decoder = png::Decoder::new(Cursor::new(bytes));
-    let reader = decoder.read_info().map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Failed to read PNG info: {}", e)))?;
-    let character_base64_option: Option<String> = reader.info().uncompressed_latin1_text.iter()
-        .filter(|text_chunk| text_chunk.keyword == "chara")
-        .map(|text_chunk| text_chunk.text.clone())
-        .next();
-    let character_base64: String = match character_base64_option {
-        Some(v) => v,
-        None => {
-            let text_chunk_start = bytes.windows(9).position(|window| window == b"tEXtchara").ok_or_else(|| 
-                pyo3::exceptions::PyValueError::new_err(
-                    r#"Failed to find 'chara' metadata (tEXt chunk) in the PNG file. This may occur due to:
-            1. The file is not a valid character card (Tavern Card V1 format).
-            2. The file may be corrupted or incomplete.
-            3. The character data might be stored in a different format.
-            
-            Please ensure that:
-            - You are using a file created by a compatible character creation tool.
-            - The file hasn't been modified or damaged.
-            - You are using the correct file format for your character data.
-            
-            If the problem persists, try re-exporting the character from its original creation tool."#
-                ))?;
-            let text_chunk_end = bytes.windows(4).rposition(|window| window == b"IEND").ok_or_else(|| 
-                pyo3::exceptions::PyValueError::new_err(
-                    r#"Failed to find 'chara' metadata (tEXt chunk) in the PNG file. This may occur due to:
-            1. The file is not a valid character card (Tavern Card V1 format).
-            2. The file may be corrupted or incomplete.
-            3. The character data might be stored in a different format.
-            
-            Please ensure that:
-            - You are using a file created by a compatible character creation tool.
-            - The file hasn't been modified or damaged.
-            - You are using the correct file format for your character data.
-            
-            If the problem persists, try re-exporting the character from its original creation tool."#
-                ))?;
-            String::from_utf8_lossy(&bytes[text_chunk_start + 10..text_chunk_end - 8]).to_string()
+fn load_character_toml(toml: &str) -> PyResult<CharacterClass> {
+    let char_data: LoadCharacterClass = toml::from_str(toml)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Error while parsing toml string: {}", e)))?;
+    Ok(character_from_data(char_data, None))
+}
+
+#[pyfunction]
+fn load_character_toml_file(path: &str) -> PyResult<CharacterClass> {
+    load_character_toml(&fs::read_to_string(path)?)
+}
+
+#[pyfunction]
+fn load_character_markdown(markdown: &str) -> PyResult<CharacterClass> {
+    let char_data: LoadCharacterClass = serde_json::from_value(markdown::parse_markdown(markdown)?)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Error while parsing markdown string: {}", e)))?;
+    Ok(character_from_data(char_data, None))
+}
+
+#[pyfunction]
+fn load_character_markdown_file(path: &str) -> PyResult<CharacterClass> {
+    load_character_markdown(&fs::read_to_string(path)?)
+}
+
+#[pyfunction]
+#[pyo3(signature = (bytes, limits = None))]
+fn load_character_card(bytes: &[u8], limits: Option<card_reader::LoadLimits>) -> PyResult<CharacterClass> {
+    character_from_card(card_reader::read_card(bytes, &limits.unwrap_or_default())?, None)
+}
+
+#[pyfunction]
+#[pyo3(signature = (path, limits = None))]
+fn load_character_card_file(path: &str, limits: Option<card_reader::LoadLimits>) -> PyResult<CharacterClass> {
+    let limits = limits.unwrap_or_default();
+    let file = File::open(path)?;
+    if let Some(max_file_size) = limits.max_file_size {
+        if file.metadata()?.len() > max_file_size {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!("PNG file is larger than the limit of {} bytes", max_file_size)));
         }
+    }
+    character_from_card(card_reader::read_card(BufReader::new(file), &limits)?, Some(path.to_string()))
+}
+
+#[pyfunction]
+#[pyo3(signature = (stream, limits = None))]
+fn load_character_card_stream(stream: &PyAny, limits: Option<card_reader::LoadLimits>) -> PyResult<CharacterClass> {
+    character_from_card(card_reader::read_card(card_reader::PyStreamReader::new(stream), &limits.unwrap_or_default())?, None)
+}
+
+fn character_from_card(card: card_reader::Card, image_path: Option<String>) -> PyResult<CharacterClass> {
+    let char_data: LoadCharacterClass = serde_json::from_str(&card.json)
+        .map_err(|_| pyo3::exceptions::PyValueError::new_err("Your image file does not contain correct json data"))?;
+    let mut character = character_from_data(char_data, image_path);
+    character.embedded_assets = card.assets;
+    Ok(character)
+}
+
+fn character_from_data(char_data: LoadCharacterClass, image_path: Option<String>) -> CharacterClass {
+    let char_data = char_data.with_data_fallback();
+    let metadata = char_data.metadata.unwrap_or_default();
+    let name = char_data.char_name.or(char_data.name).or(char_data.ai_display_name).or(char_data.ai_name)
+        .or(char_data.savedsettings.as_ref().and_then(kobold::opponent_name)).unwrap_or_default();
+    let persona = char_data.persona.map(|persona| agnai::render_persona(&name, &persona));
+    let (memory_book, kobold_memory) = match char_data.memory {
+        Some(LoadMemoryField::Book(book)) => (char_data.memory_book.or(Some(book)), kobold::MemorySections::default()),
+        Some(LoadMemoryField::Text(memory)) => (char_data.memory_book, kobold::parse_memory(&memory)),
+        None => (char_data.memory_book, kobold::MemorySections::default()),
     };
-    let engine = GeneralPurpose::new(&STANDARD, GeneralPurposeConfig::new());
-    let character_bytes = match engine.decode(character_base64) {
-        Ok(b) => b,
-        Err(e) => {
-            return Err(pyo3::exceptions::PyValueError::new_err(format!(
-                "Error while decoding base64 character data from character card: {:?}",
-                e
-            )));
-        }
+    let kobold_greeting = char_data.prompt.map(|prompt| kobold::greeting_from_prompt(&prompt, &name));
+    CharacterClass {
+        name,
+        summary: char_data.summary.or(char_data.description).or(kobold_memory.summary).unwrap_or_default(),
+        personality: char_data.char_persona.or(char_data.personality).or(persona).or(char_data.ai_persona).or(kobold_memory.personality).unwrap_or_default(),
+        scenario: char_data.world_scenario.or(char_data.scenario).or(kobold_memory.scenario).unwrap_or_default(),
+        greeting_message: char_data.char_greeting.or(char_data.first_mes).or(char_data.greeting).or(char_data.first_message).or(kobold_greeting).unwrap_or_default(),
+        example_messages: char_data.example_dialogue.or(char_data.mes_example).or(char_data.sample_chat).or(char_data.custom_dialogue).or(kobold_memory.example_messages).unwrap_or_default(),
+        image_path,
+        tags: char_data.tags.unwrap_or_default(),
+        alternate_greetings: char_data.alternate_greetings.unwrap_or_default(),
+        group_only_greetings: char_data.group_only_greetings.unwrap_or_default(),
+        nickname: char_data.nickname,
+        creator: char_data.creator.unwrap_or_default(),
+        character_version: char_data.character_version.unwrap_or_default(),
+        creator_notes: char_data.creator_notes.unwrap_or_default(),
+        system_prompt: char_data.system_prompt.unwrap_or_default(),
+        post_history_instructions: char_data.post_history_instructions.unwrap_or_default(),
+        character_book: char_data.character_book.or(memory_book.map(agnai::book_from_memory))
+            .or(char_data.lore_items.and_then(backyard::book_from_lore_items))
+            .or(char_data.worldinfo.and_then(kobold::book_from_world_info)),
+        extensions: [char_data.base_prompt.map(backyard::base_prompt_extension), char_data.authorsnote.map(kobold::authors_note_extension)]
+            .into_iter().flatten().fold(char_data.extensions.unwrap_or_default(), merge_extensions),
+        assets: char_data.assets.unwrap_or_default(),
+        embedded_assets: BTreeMap::new(),
+        history: metadata.history.unwrap_or_default(),
+        revision_author: None,
+        declared_spec: DeclaredSpec { spec: char_data.spec, spec_version: char_data.spec_version },
+        metadata_version: metadata.version,
+        created_time: metadata.created,
+        modified_time: metadata.modified,
+        source: metadata.source,
+        tools: match metadata.tool {
+            Some(LoadToolField::Single(tool)) => vec![tool],
+            Some(LoadToolField::Multiple(tools)) => tools,
+            None => Vec::new(),
+        },
+    }
+}
+
+fn character_state(character: &CharacterClass) -> PyResult<String> {
+    let mut state = export_as_value(character, None, &ExportOptions::new("keep", true, None)?)?;
+    state["char_persona"] = serde_json::Value::from(character.personality.as_str());
+    state["metadata"] = serde_json::json!({
+        "version": character.metadata_version,
+        "created": character.created_time,
+        "modified": character.modified_time,
+        "source": character.source,
+        "tool": character.tools,
+        "history": character.history,
+    });
+    state["image_path"] = serde_json::Value::from(character.image_path.clone());
+    state["embedded_assets"] = serde_json::json!(character.embedded_assets);
+    state["revision_author"] = serde_json::Value::from(character.revision_author.clone());
+    if let Some(spec) = &character.declared_spec.spec {
+        state["spec"] = spec.clone();
+    }
+    if let Some(spec_version) = &character.declared_spec.spec_version {
+        state["spec_version"] = spec_version.clone();
+    }
+    Ok(serde_json::to_string(&state).expect("Error while serializing JSON"))
+}
+
+fn character_from_state(state: &str) -> PyResult<CharacterClass> {
+    let state: serde_json::Value = serde_json::from_str(state)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid character state: {}", e)))?;
+    let image_path = state.get("image_path").and_then(|path| path.as_str()).map(|path| path.to_string());
+    let embedded_assets = match state.get("embedded_assets") {
+        Some(assets) => serde_json::from_value(assets.clone())
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid character state: {}", e)))?,
+        None => BTreeMap::new(),
     };
-    let character_text: &str = match std::str::from_utf8(&character_bytes) {
-        Ok(s) => s,
-        Err(e) => {
-            return Err(pyo3::exceptions::PyValueError::new_err(format!(
-                "Error while parsing decoded base64 bytes to utf8 string: {:?}",
-                e
-            )));
-        }
+    let revision_author = state.get("revision_author").and_then(|author| author.as_str()).map(|author| author.to_string());
+    let char_data: LoadCharacterClass = serde_json::from_value(state)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid character state: {}", e)))?;
+    Ok(CharacterClass { embedded_assets, revision_author, ..character_from_data(char_data, image_path) })
+}
+
+fn load_character_path(path: &str) -> PyResult<CharacterClass> {
+    let lower_path = path.to_lowercase();
+    if lower_path.ends_with(".png") {
+        return load_character_card_file(path, None);
+    }
+    if lower_path.ends_with(".byaf") {
+        return load_character_byaf_file(path, None);
+    }
+    if lower_path.ends_with(".charx") {
+        return load_character_charx_file(path, None);
+    }
+    let text = fs::read_to_string(path)?;
+    if lower_path.ends_with(".toml") {
+        return load_character_toml(&text);
+    }
+    if lower_path.ends_with(".md") {
+        return load_character_markdown(&text);
+    }
+    let char_data: LoadCharacterClass = if lower_path.ends_with(".yaml") || lower_path.ends_with(".yml") {
+        serde_yaml::from_str(&text)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Error while parsing yaml file '{}': {}", path, e)))?
+    } else {
+        serde_json::from_str(&text)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Error while parsing json file '{}': {}", path, e)))?
     };
-    let char_data: LoadCharacterClass =
-        serde_json::from_str(character_text).expect("Your image file does not contain correct json data");
+    Ok(character_from_data(char_data, None))
+}
 
-    Ok(CharacterClass {
-        name: char_data.char_name.unwrap_or(char_data.name.unwrap_or(String::from(""))),
-        summary: char_data.summary.unwrap_or(char_data.description.unwrap_or(String::from(""))),
-        personality: char_data.char_persona.unwrap_or(char_data.personality.unwrap_or(String::from(""))),
-        scenario: char_data.world_scenario.unwrap_or(char_data.scenario.unwrap_or(String::from(""))),
-        greeting_message: char_data.char_greeting.unwrap_or(char_data.first_mes.unwrap_or(String::from(""))),
-        example_messages: char_data.example_dialogue.unwrap_or(char_data.mes_example.unwrap_or(String::from(""))),
-        image_path: None,
-        created_time: char_data.metadata.and_then(|time_metadata| time_metadata.created),
-    })
+#[pyfunction]
+#[pyo3(signature = (bytes, limits = None))]
+fn load_character_byaf(bytes: &[u8], limits: Option<card_reader::LoadLimits>) -> PyResult<CharacterClass> {
+    backyard::load_byaf(bytes, &limits.unwrap_or_default())
 }
 
 #[pyfunction]
-fn load_character_card_file(path: &str) -> PyResult<CharacterClass> {
-    let file = File::open(path)?;
-    let mut reader = BufReader::new(file);
-    
-    let decoder = png::Decoder::new(&mut reader);
-    let reader_info = decoder.read_info().map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Failed to read PNG info: {}", e)))?;
-    let character_base64 = reader_info.info().uncompressed_latin1_text.iter()
-        .find(|text_chunk| text_chunk.keyword == "chara")
-        .map(|text_chunk| text_chunk.text.clone());
-
-    let character_base64 = if let Some(base64) = character_base64 {
-        base64
-    } else {
-        reader.seek(SeekFrom::Start(0))?;
-        let mut buffer = Vec::new();
-        reader.read_to_end(&mut buffer)?;
-
-        find_chara_chunk(&buffer)
-            .ok_or_else(|| 
-                pyo3::exceptions::PyValueError::new_err(
-                    r#"Failed to find 'chara' metadata (tEXt chunk) in the PNG file. This may occur due to:
-            1. The file is not a valid character card (Tavern Card V1 format).
-            2. The file may be corrupted or incomplete.
-            3. The character data might be stored in a different format.
-            
-            Please ensure that:
-            - You are using a file created by a compatible character creation tool.
-            - The file hasn't been modified or damaged.
-            - You are using the correct file format for your character data.
-            
-            If the problem persists, try re-exporting the character from its original creation tool."#
-                ))?
+#[pyo3(signature = (path, limits = None))]
+fn load_character_byaf_file(path: &str, limits: Option<card_reader::LoadLimits>) -> PyResult<CharacterClass> {
+    let limits = limits.unwrap_or_default();
+    if let Some(max_file_size) = limits.max_file_size {
+        if fs::metadata(path)?.len() > max_file_size {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!("Backyard archive is larger than the limit of {} bytes", max_file_size)));
+        }
+    }
+    backyard::load_byaf(&fs::read(path)?, &limits)
+}
+
+#[pyfunction]
+#[pyo3(signature = (bytes, limits = None))]
+fn load_character_charx(bytes: &[u8], limits: Option<card_reader::LoadLimits>) -> PyResult<CharacterClass> {
+    risu::load_charx(bytes, &limits.unwrap_or_default())
+}
+
+#[pyfunction]
+#[pyo3(signature = (path, limits = None))]
+fn load_character_charx_file(path: &str, limits: Option<card_reader::LoadLimits>) -> PyResult<CharacterClass> {
+    let limits = limits.unwrap_or_default();
+    if let Some(max_file_size) = limits.max_file_size {
+        if fs::metadata(path)?.len() > max_file_size {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!("CHARX file is larger than the limit of {} bytes", max_file_size)));
+        }
+    }
+    risu::load_charx(&fs::read(path)?, &limits)
+}
+
+#[pyfunction]
+fn load_risu_module(bytes: &[u8]) -> PyResult<CharacterClass> {
+    let mut character = CharacterClass::default();
+    risu::apply_module(&mut character, risu::parse_module(bytes)?);
+    Ok(character)
+}
+
+#[pyfunction]
+fn from_dict(mapping: &PyAny) -> PyResult<CharacterClass> {
+    let mapping = match mapping.downcast::<pyo3::types::PyDict>() {
+        Ok(dict) => dict,
+        Err(_) => mapping.py().get_type::<pyo3::types::PyDict>().call1((mapping,))?.downcast()?,
     };
+    let char_data: LoadCharacterClass = serde_json::from_value(convert::py_to_value(mapping)?)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Mapping does not contain correct character data: {}", e)))?;
+    Ok(character_from_data(char_data, None))
+}
 
-    let engine = GeneralPurpose::new(&STANDARD, GeneralPurposeConfig::new());
-    let character_bytes = engine.decode(character_base64)
-        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Error while decoding base64 character data: {:?}", e)))?;
+#[pyfunction]
+#[pyo3(signature = (path, limits = None))]
+fn peek_card(path: &str, limits: Option<card_reader::LoadLimits>) -> PyResult<card_reader::CardInfo> {
+    card_reader::peek_card(path, &limits.unwrap_or_default())
+}
 
-    let character_text = std::str::from_utf8(&character_bytes)
-        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Error while parsing decoded base64 bytes to utf8 string: {:?}", e)))?;
+#[pyfunction]
+#[pyo3(signature = (jsonl, character = None))]
+fn load_chat_sillytavern(jsonl: &str, character: Option<&CharacterClass>) -> PyResult<chat_log::ChatLog> {
+    chat_log::load_sillytavern(jsonl, character)
+}
 
-    let char_data: LoadCharacterClass = serde_json::from_str(character_text)
-        .map_err(|_| pyo3::exceptions::PyValueError::new_err("Your image file does not contain correct json data"))?;
+#[pyfunction]
+#[pyo3(signature = (path, character = None))]
+fn load_chat_sillytavern_file(path: &str, character: Option<&CharacterClass>) -> PyResult<chat_log::ChatLog> {
+    chat_log::load_sillytavern(&fs::read_to_string(path)?, character)
+}
 
-    Ok(CharacterClass {
-        name: char_data.char_name.or(char_data.name).unwrap_or_default(),
-        summary: char_data.summary.or(char_data.description).unwrap_or_default(),
-        personality: char_data.char_persona.or(char_data.personality).unwrap_or_default(),
-        scenario: char_data.world_scenario.or(char_data.scenario).unwrap_or_default(),
-        greeting_message: char_data.char_greeting.or(char_data.first_mes).unwrap_or_default(),
-        example_messages: char_data.example_dialogue.or(char_data.mes_example).unwrap_or_default(),
-        image_path: Some(path.to_string()),
-        created_time: char_data.metadata.and_then(|time_metadata| time_metadata.created),
-    })
+#[pyfunction]
+#[pyo3(signature = (json, character = None, user_name = "You"))]
+fn load_chat_textgen(json: &str, character: Option<&CharacterClass>, user_name: &str) -> PyResult<chat_log::ChatLog> {
+    chat_log::load_textgen(json, character, user_name)
 }
 
-fn find_chara_chunk(buffer: &[u8]) -> Option<String> {
-    let chara_marker = b"tEXtchara";
-    let iend_marker = b"IEND";
+#[pyfunction]
+#[pyo3(signature = (path, character = None, user_name = "You"))]
+fn load_chat_textgen_file(path: &str, character: Option<&CharacterClass>, user_name: &str) -> PyResult<chat_log::ChatLog> {
+    chat_log::load_textgen(&fs::read_to_string(path)?, character, user_name)
+}
 
-    let start = buffer.windows(chara_marker.len())
-        .position(|window| window == chara_marker)?;
-    let end = buffer.windows(iend_marker.len())
-        .rposition(|window| window == iend_marker)?;
+#[pyfunction]
+fn diff(a: &CharacterClass, b: &CharacterClass) -> comparison::CharacterDiff {
+    comparison::diff_characters(a, b)
+}
 
-    Some(String::from_utf8_lossy(&buffer[start + chara_marker.len() + 1..end - 8]).into_owned())
+#[pyfunction]
+fn merge(base: &CharacterClass, ours: &CharacterClass, theirs: &CharacterClass) -> three_way::MergeResult {
+    three_way::merge_characters(base, ours, theirs)
+}
+
+#[pyfunction]
+#[pyo3(signature = (paths, threshold = 0.8))]
+fn find_duplicates(paths: Vec<String>, threshold: f64) -> (Vec<duplicates::DuplicateGroup>, Vec<(String, String)>) {
+    duplicates::find_duplicates(paths, threshold)
+}
+
+#[pyfunction]
+#[pyo3(signature = (inputs, output_dir, format_type, output_kind, metadata_mode = "update", reproducible = false, timestamp = None))]
+#[allow(clippy::too_many_arguments)]
+fn convert_many(py: Python, inputs: Vec<String>, output_dir: &str, format_type: &str, output_kind: &str,
+    metadata_mode: &str, reproducible: bool, timestamp: Option<i64>) -> PyResult<Vec<batch::ConversionResult>> {
+    batch::convert_many(py, inputs, output_dir, format_type, output_kind, &ExportOptions::new(metadata_mode, reproducible, timestamp)?)
+}
+
+#[pyfunction]
+fn lint(path: &str) -> PyResult<Vec<validation::Diagnostic>> {
+    validation::lint_file(path)
 }
 
 #[pyfunction]
@@ -810,13 +2150,571 @@ fn license() -> &'static str {
 #[pymodule]
 fn aichar(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<CharacterClass>()?;
+    m.add_class::<validation::Diagnostic>()?;
+    m.add_class::<comparison::CharacterDiff>()?;
+    m.add_class::<comparison::FieldChange>()?;
+    m.add_class::<three_way::MergeResult>()?;
+    m.add_class::<three_way::MergeConflict>()?;
+    m.add_class::<history::Revision>()?;
+    m.add_class::<ToolInfo>()?;
+    m.add_class::<duplicates::DuplicateGroup>()?;
+    m.add_class::<library::CharacterLibrary>()?;
+    m.add_class::<library::LibraryEntry>()?;
+    m.add_class::<batch::ConversionResult>()?;
+    m.add_class::<card_reader::LoadLimits>()?;
+    m.add_class::<card_reader::CardInfo>()?;
+    m.add_class::<chub::ChubInfo>()?;
+    m.add_class::<chat_log::ChatMessage>()?;
+    m.add_class::<chat_log::ChatLog>()?;
     m.add_function(wrap_pyfunction!(create_character, m)?)?;
     m.add_function(wrap_pyfunction!(load_character_json, m)?)?;
     m.add_function(wrap_pyfunction!(load_character_json_file, m)?)?;
     m.add_function(wrap_pyfunction!(load_character_yaml, m)?)?;
     m.add_function(wrap_pyfunction!(load_character_yaml_file, m)?)?;
+    m.add_function(wrap_pyfunction!(load_character_toml, m)?)?;
+    m.add_function(wrap_pyfunction!(load_character_toml_file, m)?)?;
+    m.add_function(wrap_pyfunction!(load_character_markdown, m)?)?;
+    m.add_function(wrap_pyfunction!(load_character_markdown_file, m)?)?;
     m.add_function(wrap_pyfunction!(load_character_card, m)?)?;
     m.add_function(wrap_pyfunction!(load_character_card_file, m)?)?;
+    m.add_function(wrap_pyfunction!(load_character_card_stream, m)?)?;
+    m.add_function(wrap_pyfunction!(load_character_byaf, m)?)?;
+    m.add_function(wrap_pyfunction!(load_character_byaf_file, m)?)?;
+    m.add_function(wrap_pyfunction!(load_character_charx, m)?)?;
+    m.add_function(wrap_pyfunction!(load_character_charx_file, m)?)?;
+    m.add_function(wrap_pyfunction!(load_risu_module, m)?)?;
+    m.add_function(wrap_pyfunction!(from_dict, m)?)?;
+    m.add_function(wrap_pyfunction!(peek_card, m)?)?;
+    m.add_function(wrap_pyfunction!(json_schema, m)?)?;
+    m.add_function(wrap_pyfunction!(load_chat_sillytavern, m)?)?;
+    m.add_function(wrap_pyfunction!(load_chat_sillytavern_file, m)?)?;
+    m.add_function(wrap_pyfunction!(load_chat_textgen, m)?)?;
+    m.add_function(wrap_pyfunction!(load_chat_textgen_file, m)?)?;
+    m.add_function(wrap_pyfunction!(diff, m)?)?;
+    m.add_function(wrap_pyfunction!(merge, m)?)?;
+    m.add_function(wrap_pyfunction!(find_duplicates, m)?)?;
+    m.add_function(wrap_pyfunction!(convert_many, m)?)?;
+    m.add_function(wrap_pyfunction!(lint, m)?)?;
     m.add_function(wrap_pyfunction!(license, m)?)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempPath;
+    use pyo3::types::IntoPyDict;
+
+    fn test_image(name: &str) -> TempPath {
+        let path = TempPath::new(&format!("{}.png", name));
+        let mut encoder = png::Encoder::new(File::create(path.path()).unwrap(), 1, 1);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header().unwrap().write_image_data(&[0, 0, 0, 255]).unwrap();
+        path
+    }
+
+    fn options() -> ExportOptions {
+        ExportOptions::new("keep", true, Some(1_700_000_000_000)).unwrap()
+    }
+
+    fn v3_character() -> CharacterClass {
+        CharacterClass {
+            name: "Ann".to_string(),
+            greeting_message: "Hi".to_string(),
+            alternate_greetings: vec!["Yo".to_string()],
+            group_only_greetings: vec!["Hi all".to_string()],
+            nickname: Some("Annie".to_string()),
+            creator: "someone".to_string(),
+            character_version: "1.2".to_string(),
+            creator_notes: "Notes".to_string(),
+            system_prompt: "System".to_string(),
+            post_history_instructions: "Stay in character".to_string(),
+            character_book: Some(CharacterBook {
+                entries: vec![LorebookEntry { keys: vec!["key".to_string()], content: "lore".to_string(), enabled: true, ..LorebookEntry::default() }],
+                ..CharacterBook::default()
+            }),
+            ..CharacterClass::default()
+        }
+    }
+
+    fn png_text_chunks(bytes: &[u8]) -> Vec<(String, String)> {
+        let reader = Decoder::new(bytes).read_info().unwrap();
+        reader.info().uncompressed_latin1_text.iter().map(|chunk| (chunk.keyword.clone(), chunk.text.clone())).collect()
+    }
+
+    fn decode_chunk(text: &str) -> serde_json::Value {
+        let engine = GeneralPurpose::new(&STANDARD, GeneralPurposeConfig::new());
+        serde_json::from_slice(&engine.decode(text).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn risuai_export_has_required_v3_fields() {
+        let value = export_as_value(&v3_character(), Some("risuai"), &options()).unwrap();
+        let data = &value["data"];
+        for field in ["creator_notes", "system_prompt", "post_history_instructions", "alternate_greetings", "group_only_greetings", "tags", "creator", "character_version", "extensions", "assets"] {
+            assert!(data.get(field).is_some(), "missing {}", field);
+        }
+        assert_eq!(data["nickname"], "Annie");
+        assert_eq!(data["character_book"]["entries"][0]["use_regex"], false);
+    }
+
+    #[test]
+    fn v3_fields_round_trip() {
+        let character = v3_character();
+        for format_type in ["risuai", "neutral"] {
+            let json = serde_json::to_string(&export_as_value(&character, Some(format_type), &options()).unwrap()).unwrap();
+            let loaded = load_character_json(&json, false, None).unwrap();
+            assert_eq!(loaded.group_only_greetings, character.group_only_greetings, "{}", format_type);
+            assert_eq!(loaded.nickname, character.nickname, "{}", format_type);
+            assert_eq!(loaded.creator, character.creator, "{}", format_type);
+            assert_eq!(loaded.character_version, character.character_version, "{}", format_type);
+            assert_eq!(loaded.creator_notes, character.creator_notes, "{}", format_type);
+            assert_eq!(loaded.system_prompt, character.system_prompt, "{}", format_type);
+            assert_eq!(loaded.post_history_instructions, character.post_history_instructions, "{}", format_type);
+        }
+    }
+
+    #[test]
+    fn chara_card_v2_export_keeps_v2_fields() {
+        let value = export_as_value(&v3_character(), Some("chara_card_v2"), &options()).unwrap();
+        assert_eq!(value["spec"], "chara_card_v2");
+        assert_eq!(value["spec_version"], "2.0");
+        assert_eq!(value["data"]["system_prompt"], "System");
+        assert!(value["data"].get("group_only_greetings").is_none());
+        assert_eq!(losses::export_losses(&v3_character(), "chara_card_v2").unwrap(), ["group_only_greetings", "nickname"]);
+    }
+
+    #[test]
+    fn risuai_card_writes_v2_chara_chunk() {
+        let image_path = test_image("risuai_card");
+        let character = CharacterClass { image_path: Some(image_path.as_str().to_string()), ..v3_character() };
+        let bytes = export_as_card(&character, "risuai", &options()).unwrap();
+
+        let chunks = png_text_chunks(&bytes);
+        let ccv3 = chunks.iter().find(|(keyword, _)| keyword == "ccv3").map(|(_, text)| decode_chunk(text)).unwrap();
+        let chara = chunks.iter().find(|(keyword, _)| keyword == "chara").map(|(_, text)| decode_chunk(text)).unwrap();
+        assert_eq!(ccv3["spec"], "chara_card_v3");
+        assert_eq!(chara["spec"], "chara_card_v2");
+        assert_eq!(chara["spec_version"], "2.0");
+
+        let loaded = load_character_card(&bytes, None).unwrap();
+        assert_eq!(loaded.nickname.as_deref(), Some("Annie"));
+        assert_eq!(loaded.group_only_greetings, ["Hi all"]);
+    }
+
+    #[test]
+    fn embedded_assets_round_trip_through_risuai_card() {
+        let image_path = test_image("embedded_assets");
+        let mut character = CharacterClass { image_path: Some(image_path.as_str().to_string()), ..v3_character() };
+        character.assets = vec![serde_json::json!({"type": "x-risu-asset", "uri": "embeded://assets/icon.png", "name": "icon", "ext": "png"})];
+        character.embedded_assets = BTreeMap::from([
+            ("__asset:0".to_string(), "AAEC".to_string()),
+            ("embeded://assets/icon.png".to_string(), "AwQF".to_string()),
+        ]);
+        let bytes = export_as_card(&character, "risuai", &options()).unwrap();
+        let neutral = export_as_card(&character, "neutral", &options()).unwrap();
+        assert!(export_as_card(&character, "Neutral", &options()).unwrap() == neutral);
+
+        let keywords: Vec<String> = png_text_chunks(&bytes).into_iter().map(|(keyword, _)| keyword).collect();
+        assert_eq!(keywords, ["chara-ext-asset_:0", "chara-ext-asset_:1", "ccv3", "chara"]);
+        let keywords: Vec<String> = png_text_chunks(&neutral).into_iter().map(|(keyword, _)| keyword).collect();
+        assert_eq!(keywords, ["chara-ext-asset_:0", "chara-ext-asset_:1", "chara"]);
+        let loaded = load_character_card(&bytes, None).unwrap();
+        assert_eq!(loaded.embedded_assets, BTreeMap::from([
+            ("__asset:0".to_string(), "AAEC".to_string()),
+            ("__asset:1".to_string(), "AwQF".to_string()),
+        ]));
+        assert_eq!(loaded.assets[0]["uri"], "__asset:1");
+    }
+
+    #[test]
+    fn embedded_assets_are_reported_as_losses() {
+        let character = CharacterClass { embedded_assets: BTreeMap::from([("__asset:0".to_string(), "AAEC".to_string())]), ..CharacterClass::default() };
+        assert_eq!(losses::export_losses(&character, "tavernai").unwrap(), ["embedded_assets"]);
+        assert!(losses::export_losses(&character, "risuai").unwrap().is_empty());
+    }
+
+    #[test]
+    fn state_keeps_embedded_assets() {
+        let character = CharacterClass { embedded_assets: BTreeMap::from([("__asset:0".to_string(), "AAEC".to_string())]), ..v3_character() };
+        let restored = character_from_state(&character_state(&character).unwrap()).unwrap();
+        assert_eq!(restored.embedded_assets, character.embedded_assets);
+    }
+
+    #[test]
+    fn tavernai_export_keeps_its_original_fields() {
+        let value = export_as_value(&v3_character(), Some("tavernai"), &options()).unwrap();
+        for field in ["tags", "alternate_greetings", "character_book"] {
+            assert!(value.get(field).is_none(), "{}", field);
+        }
+        let losses = losses::export_losses(&v3_character(), "tavernai").unwrap();
+        assert!(["alternate_greetings", "character_book"].iter().all(|field| losses.iter().any(|loss| loss == field)), "{:?}", losses);
+    }
+
+    #[test]
+    fn exports_record_revision_author() {
+        let character = CharacterClass { revision_author: Some("Alice".to_string()), ..v3_character() };
+        let value = export_as_value(&character, Some("tavernai"), &ExportOptions::new("update", true, Some(5)).unwrap()).unwrap();
+        assert_eq!(value["metadata"]["history"][0]["author"], "Alice");
+        let restored = character_from_state(&character_state(&character).unwrap()).unwrap();
+        assert_eq!(restored.revision_author.as_deref(), Some("Alice"));
+    }
+
+    #[test]
+    fn reloading_through_lossy_formats_adds_no_revision() {
+        for format_type in ["tavernai", "textgenerationwebui", "backyard", "koboldai", "neutral"] {
+            let mut character = CharacterClass { summary: "Tall".to_string(), personality: "kind".to_string(), tags: vec!["knight".to_string()], ..v3_character() };
+            let mut revisions = 0;
+            for edit in ["", "", "brave", ""] {
+                if !edit.is_empty() {
+                    character.personality = edit.to_string();
+                }
+                let json = export_as_json(&character, format_type, &options()).unwrap();
+                character = load_character_json(&json, false, None).unwrap();
+                revisions += usize::from(revisions == 0 || !edit.is_empty());
+                assert_eq!(character.history.len(), revisions, "{}", format_type);
+            }
+        }
+    }
+
+    fn card_with_metadata() -> CharacterClass {
+        let json = r#"{"name": "Ann", "first_mes": "Hi", "metadata": {"version": 2, "created": 100, "modified": 200, "source": "hub",
+            "tool": {"name": "Other tool", "version": "0.9", "url": "https://example.com"}}}"#;
+        load_character_json(json, false, None).unwrap()
+    }
+
+    #[test]
+    fn metadata_fields_are_loaded() {
+        let character = card_with_metadata();
+        assert_eq!((character.metadata_version, character.created_time, character.modified_time), (Some(2), Some(100), Some(200)));
+        assert_eq!(character.source.as_deref(), Some("hub"));
+        assert_eq!(character.tools.len(), 1);
+        assert_eq!((character.tools[0].name.as_str(), character.tools[0].version.as_str()), ("Other tool", "0.9"));
+
+        let tools = r#"{"name": "Ann", "metadata": {"tool": [{"name": "a"}, {"name": "b", "version": "2"}]}}"#;
+        let character = load_character_json(tools, false, None).unwrap();
+        assert_eq!(character.tools.iter().map(|tool| tool.name.as_str()).collect::<Vec<&str>>(), ["a", "b"]);
+    }
+
+    #[test]
+    fn metadata_modes() {
+        let character = card_with_metadata();
+        let metadata = |mode: &str| export_as_value(&character, Some("tavernai"), &ExportOptions::new(mode, false, Some(500)).unwrap()).unwrap()["metadata"].clone();
+
+        let keep = metadata("keep");
+        assert_eq!((keep["version"].as_u64(), keep["created"].as_i64(), keep["modified"].as_i64()), (Some(2), Some(100), Some(200)));
+        assert_eq!(keep["source"], "hub");
+        assert_eq!(keep["tool"]["name"], "Other tool");
+
+        let update = metadata("update");
+        assert_eq!((update["created"].as_i64(), update["modified"].as_i64()), (Some(100), Some(500)));
+        assert_eq!(update["source"], "hub");
+        assert_eq!(update["tool"]["name"], PROGRAM_INFO.name);
+
+        let append = metadata("append");
+        assert_eq!(append["modified"], 500);
+        let tools: Vec<&str> = append["tool"].as_array().unwrap().iter().map(|tool| tool["name"].as_str().unwrap()).collect();
+        assert_eq!(tools, ["Other tool", PROGRAM_INFO.name]);
+
+        assert!(ExportOptions::new("replace", false, None).is_err());
+    }
+
+    #[test]
+    fn appending_twice_lists_aichar_once() {
+        let options = ExportOptions::new("append", false, Some(500)).unwrap();
+        let json = export_as_json(&card_with_metadata(), "tavernai", &options).unwrap();
+        let value = export_as_value(&load_character_json(&json, false, None).unwrap(), Some("tavernai"), &options).unwrap();
+        assert_eq!(value["metadata"]["tool"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn json_yaml_and_values_come_from_one_dispatch() {
+        pyo3::prepare_freethreaded_python();
+        let character = full_character();
+        let options = options();
+        for format_type in ["tavernai", "sillytavern", "textgenerationwebui", "pygmalion", "aicompanion", "chara_card_v2", "risuai", "agnaistic", "backyard", "koboldai", "neutral"] {
+            let value = export_as_value(&character, Some(format_type), &options).unwrap();
+            let json: serde_json::Value = serde_json::from_str(&export_as_json(&character, format_type, &options).unwrap()).unwrap();
+            let yaml: serde_json::Value = serde_yaml::from_str(&export_as_yaml(&character, format_type, &options).unwrap()).unwrap();
+            assert_eq!(json, value, "{}", format_type);
+            assert_eq!(yaml, value, "{}", format_type);
+        }
+        assert_eq!(export_as_value(&character, None, &options).unwrap(), export_as_value(&character, Some("NEUTRAL"), &options).unwrap());
+
+        let json = export_as_json(&character, "tavernai", &options).unwrap();
+        assert!(json.find("\"name\"").unwrap() < json.find("\"description\"").unwrap(), "exports keep the field order of their structs");
+        for error in [export_as_json(&character, "xml", &options).err(), export_as_yaml(&character, "xml", &options).err()] {
+            assert!(error.unwrap().to_string().contains(SUPPORTED_FORMATS));
+        }
+    }
+
+    #[test]
+    fn reproducible_exports_are_byte_identical() {
+        let character = card_with_metadata();
+        let reproducible = ExportOptions::new("update", true, None).unwrap();
+        for format_type in ["tavernai", "chara_card_v2", "risuai", "agnaistic", "koboldai"] {
+            assert_eq!(export_as_json(&character, format_type, &reproducible).unwrap(), export_as_json(&character, format_type, &reproducible).unwrap(), "{}", format_type);
+            assert_eq!(export_as_yaml(&character, format_type, &reproducible).unwrap(), export_as_yaml(&character, format_type, &reproducible).unwrap(), "{}", format_type);
+        }
+        assert_eq!(export_as_json(&character, "neutral", &reproducible).unwrap(), export_as_json(&character, "neutral", &reproducible).unwrap());
+
+        let image_path = test_image("reproducible");
+        let character = CharacterClass { image_path: Some(image_path.as_str().to_string()), ..character };
+        let first = export_as_card(&character, "chara_card_v2", &reproducible).unwrap();
+        let second = export_as_card(&character, "chara_card_v2", &reproducible).unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn reproducible_clock() {
+        let character = card_with_metadata();
+        let modified = |options: ExportOptions| export_as_value(&character, Some("tavernai"), &options).unwrap()["metadata"]["modified"].clone();
+        assert_eq!(modified(ExportOptions::new("update", true, None).unwrap()), 200);
+        assert_eq!(modified(ExportOptions::new("update", true, Some(42)).unwrap()), 42);
+        assert_eq!(modified(ExportOptions::new("update", false, Some(42)).unwrap()), 42);
+        let new_character = CharacterClass { name: "New".to_string(), ..CharacterClass::default() };
+        let value = export_as_value(&new_character, Some("tavernai"), &ExportOptions::new("update", true, None).unwrap()).unwrap();
+        assert_eq!((value["metadata"]["created"].as_i64(), value["metadata"]["modified"].as_i64()), (Some(0), Some(0)));
+    }
+
+    fn full_character() -> CharacterClass {
+        CharacterClass {
+            summary: "Summary".to_string(),
+            personality: "Kind".to_string(),
+            scenario: "Castle".to_string(),
+            example_messages: "<START>".to_string(),
+            image_path: Some("card.png".to_string()),
+            tags: vec!["fantasy".to_string()],
+            extensions: serde_json::Map::from_iter([("risuai".to_string(), serde_json::json!({"customScripts": []}))]),
+            assets: vec![serde_json::json!({"type": "icon", "uri": "ccdefault:", "name": "main", "ext": "png"})],
+            embedded_assets: BTreeMap::from([("__asset:0".to_string(), "AAEC".to_string())]),
+            created_time: Some(100),
+            modified_time: Some(200),
+            metadata_version: Some(2),
+            source: Some("hub".to_string()),
+            tools: vec![ToolInfo { name: "Other".to_string(), version: "1".to_string(), url: String::new() }],
+            history: vec![history::new_revision(&v3_character(), Some("Alice".to_string()), "Initial".to_string(), 100)],
+            revision_author: Some("Alice".to_string()),
+            ..v3_character()
+        }
+    }
+
+    #[test]
+    fn state_round_trip_keeps_every_field() {
+        let character = full_character();
+        assert!(character == character_from_state(&character_state(&character).unwrap()).unwrap());
+        let empty = CharacterClass::default();
+        assert!(empty == character_from_state(&character_state(&empty).unwrap()).unwrap());
+        assert!(character_from_state("not json").is_err());
+    }
+
+    #[test]
+    fn equality_and_repr() {
+        let character = full_character();
+        let copy = character.__copy__();
+        assert!(character.__eq__(&copy));
+        let renamed = CharacterClass { name: "Bob".to_string(), ..character.clone() };
+        assert!(!character.__eq__(&renamed));
+        assert_eq!(renamed.__repr__(), "<CharacterClass \"Bob\">");
+    }
+
+    #[test]
+    fn equal_characters_hash_the_same() {
+        let character = full_character();
+        let mut reloaded = character.clone();
+        reloaded.declared_spec.spec = Some(serde_json::json!("chara_card_v2"));
+        assert!(character == reloaded);
+        assert_eq!(character.__hash__(), reloaded.__hash__());
+        assert_ne!(character.__hash__(), CharacterClass { name: "Bob".to_string(), ..character.clone() }.__hash__());
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let character = Py::new(py, full_character()).unwrap();
+            assert!(character.as_ref(py).hash().is_ok());
+        });
+    }
+
+    #[test]
+    fn dicts_round_trip_for_every_format() {
+        pyo3::prepare_freethreaded_python();
+        let character = full_character();
+        Python::with_gil(|py| {
+            for format_type in [None, Some("tavernai"), Some("textgenerationwebui"), Some("aicompanion"), Some("chara_card_v2"), Some("risuai"), Some("agnaistic"), Some("backyard"), Some("koboldai")] {
+                let dict = character.to_dict(py, format_type, "keep", true, None).unwrap();
+                let loaded = from_dict(dict.as_ref(py)).unwrap();
+                assert_eq!(loaded.name, character.name, "{:?}", format_type);
+                assert_eq!(loaded.greeting_message, character.greeting_message, "{:?}", format_type);
+            }
+            let dict = character.to_dict(py, None, "keep", true, None).unwrap();
+            let loaded = from_dict(dict.as_ref(py)).unwrap();
+            assert!(loaded.character_book == character.character_book);
+            assert_eq!(loaded.extensions, character.extensions);
+
+            let items = py.eval("[('name', 'Ann'), ('first_mes', 'Hi')]", None, None).unwrap();
+            assert_eq!(from_dict(items).unwrap().greeting_message, "Hi");
+            assert!(from_dict(py.eval("{'name': 1}", None, None).unwrap()).is_err());
+        });
+    }
+
+    #[test]
+    fn builder_sets_fields_and_validates_image_path() {
+        pyo3::prepare_freethreaded_python();
+        let image_path = test_image("builder");
+        let character = CharacterClass::default()
+            .with_name("Ann")
+            .with_greeting_message("Hi")
+            .with_tags(vec!["tag".to_string()])
+            .with_nickname(Some("Annie".to_string()))
+            .with_system_prompt("System")
+            .with_image_path(image_path.as_str())
+            .unwrap();
+        assert_eq!(character.name(), "Ann");
+        assert_eq!(character.greeting_message(), "Hi");
+        assert_eq!(character.tags(), ["tag"]);
+        assert_eq!(character.nickname().map(String::as_str), Some("Annie"));
+        assert_eq!(character.system_prompt(), "System");
+        assert_eq!(character.image_path().map(String::as_str), Some(image_path.as_str()));
+
+        assert!(CharacterClass::default().with_image_path("").unwrap().image_path().is_none());
+        let missing = image_path.as_str().to_string();
+        drop(image_path);
+        assert!(CharacterClass::default().with_image_path(&missing).is_err());
+        assert!(CharacterClass::default().with_image_path("avatar.jpg").is_err());
+    }
+
+    #[test]
+    fn keyword_constructor_defaults_every_field() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let class = py.get_type::<CharacterClass>();
+            let character: CharacterClass = class.call((), Some([("name", "Ann"), ("creator", "someone")].into_py_dict(py))).unwrap().extract().unwrap();
+            assert!(character == CharacterClass::default().with_name("Ann").with_creator("someone"));
+            assert!(class.call((), Some([("image_path", "missing.png")].into_py_dict(py))).is_err());
+        });
+    }
+
+    #[test]
+    fn agnai_personas_keep_their_kind_through_import_and_export() {
+        let json = r#"{"kind": "character", "name": "Ann", "description": "", "persona": {"kind": "wpp", "attributes": {"Mind": ["kind", "shy"]}}, "greeting": "Hi", "sampleChat": "", "scenario": ""}"#;
+        let character = load_character_json(json, false, None).unwrap();
+        assert_eq!(character.personality, "[character(\"Ann\") {\nMind(\"kind\" + \"shy\")\n}]");
+        let value = export_as_value(&character, Some("agnaistic"), &options()).unwrap();
+        assert_eq!(value["kind"], "character");
+        assert_eq!(value["persona"], serde_json::json!({"kind": "wpp", "attributes": {"Mind": ["kind", "shy"]}}));
+        assert!(load_character_json(json, true, Some("agnaistic")).is_ok());
+        assert!(load_character_json(&value.to_string(), true, Some("agnaistic")).is_ok());
+
+        let prose = CharacterClass { personality: "Kind and shy.".to_string(), ..character };
+        let value = export_as_value(&prose, Some("agnaistic"), &options()).unwrap();
+        assert_eq!(value["persona"], serde_json::json!({"kind": "text", "attributes": {"text": ["Kind and shy."]}}));
+    }
+
+    #[test]
+    fn mistyped_foreign_fields_do_not_fail_the_load() {
+        let json = r#"{"name": "Ann", "personality": "Kind", "persona": "plain text", "memory": 5, "characterBook": [], "savedsettings": {"chatname": 1}, "worldinfo": {}, "loreItems": "none",
+            "metadata": {"version": "1", "tool": 3, "created": 10},
+            "character_book": {"entries": [{"keys": ["castle"], "content": "A castle", "id": "castle", "position": 0}]}}"#;
+        let character = load_character_json(json, false, None).unwrap();
+        assert_eq!(character.personality, "Kind");
+        assert_eq!(character.created_time, Some(10));
+        assert!(character.tools.is_empty());
+        let entry = &character.character_book.as_ref().unwrap().entries[0];
+        assert_eq!((entry.id, entry.position.as_deref()), (None, None));
+        assert_eq!(entry.content, "A castle");
+    }
+
+    #[test]
+    fn sillytavern_export_loads_in_strict_mode() {
+        let json = r#"{"name": "Ann", "description": "A knight", "personality": "Brave", "first_mes": "Halt!", "avatar": "none", "chat": "Ann - 2024-1-1 @12h 00m 00s 000ms",
+            "mes_example": "", "scenario": "", "create_date": "2024-1-1 @12h 00m 00s 000ms", "talkativeness": "0.5", "fav": false}"#;
+        assert_eq!(load_character_json(json, true, Some("tavernai")).unwrap().personality, "Brave");
+        assert_eq!(load_character_json(json, true, None).unwrap().name, "Ann");
+    }
+
+    #[test]
+    fn personality_attributes_convert_between_styles() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let mut character = CharacterClass::default().with_name("Ann").with_personality("[Mind: kind + shy]");
+            let attributes = character.personality_attributes(py).unwrap();
+            let attributes: &pyo3::types::PyDict = attributes.downcast(py).unwrap();
+            assert_eq!(attributes.get_item("Mind").unwrap().unwrap().extract::<Vec<String>>().unwrap(), ["kind", "shy"]);
+            assert_eq!(character.personality_as("wpp").unwrap(), "[character(\"Ann\") {\nMind(\"kind\" + \"shy\")\n}]");
+
+            character.set_personality_attributes(attributes, "sbf").unwrap();
+            assert_eq!(character.personality, "[ character: \"Ann\"; Mind: \"kind\", \"shy\" ]");
+            assert!(character.set_personality_attributes([("Mind", 1)].into_py_dict(py), "wpp").is_err());
+            assert!(character.set_personality_attributes(attributes, "xml").is_err());
+            assert!(CharacterClass::default().with_personality("Kind and shy.").personality_as("wpp").is_err());
+        });
+    }
+
+    #[test]
+    fn kobold_save_round_trip() {
+        let character = CharacterClass::default()
+            .with_name("Ann")
+            .with_personality("Kind")
+            .with_scenario("A town")
+            .with_greeting_message("Hi")
+            .with_character_book(v3_character().character_book)
+            .with_extensions(kobold::authors_note_extension("Be brief".to_string()));
+        let json = export_as_json(&character, "koboldai", &options()).unwrap();
+        let loaded = load_character_json(&json, false, None).unwrap();
+        assert_eq!(loaded.name, "Ann");
+        assert_eq!(loaded.personality, "Kind");
+        assert_eq!(loaded.scenario, "A town");
+        assert_eq!(loaded.greeting_message, "Hi");
+        assert_eq!(loaded.character_book.as_ref().unwrap().entries[0].keys, ["key"]);
+        assert_eq!(kobold::authors_note(&loaded), "Be brief");
+    }
+
+    #[test]
+    fn extensions_from_every_source_are_merged() {
+        let json = r#"{"name": "Ann", "memory": "Personality: Kind", "authorsnote": "Be brief", "basePrompt": "Custom prompt", "extensions": {"depth_prompt": {"prompt": "x"}}}"#;
+        let loaded = load_character_json(json, false, None).unwrap();
+        assert_eq!(loaded.extensions["depth_prompt"]["prompt"], "x");
+        assert_eq!(kobold::authors_note(&loaded), "Be brief");
+        assert_eq!(backyard::base_prompt(&loaded), "Custom prompt");
+
+        let json = r#"{"extensions": {"kobold": {"authorsnote": "Card note"}}, "authorsnote": "Save note", "data": {"name": "Ann", "extensions": {"talkativeness": "0.5", "kobold": {}}}}"#;
+        let loaded = load_character_json(json, false, None).unwrap();
+        assert_eq!(kobold::authors_note(&loaded), "Card note");
+        assert_eq!(loaded.extensions["talkativeness"], "0.5");
+    }
+
+    #[test]
+    fn chub_metadata_survives_card_export() {
+        let image_path = test_image("chub");
+        let mut character = CharacterClass { image_path: Some(image_path.as_str().to_string()), ..v3_character() };
+        let chub = chub::ChubInfo { full_path: Some("someone/ann".to_string()), id: Some(123), related_lorebooks: vec![4], ..chub::ChubInfo::default() };
+        character.set_chub(Some(chub.clone()));
+        for format_type in ["chara_card_v2", "risuai"] {
+            let bytes = export_as_card(&character, format_type, &options()).unwrap();
+            assert!(load_character_card(&bytes, None).unwrap().chub() == Some(chub.clone()), "{}", format_type);
+        }
+        assert!(losses::export_losses(&character, "tavernai").unwrap().contains(&"extensions".to_string()));
+    }
+
+    #[test]
+    fn toml_round_trip_and_null_handling() {
+        pyo3::prepare_freethreaded_python();
+        let character = v3_character().with_personality("Kind\n\"quoted\"").with_tags(vec!["tag".to_string()]);
+        for format_type in [None, Some("tavernai"), Some("chara_card_v2"), Some("risuai")] {
+            let toml = export_as_toml(&character, format_type, &options()).unwrap();
+            let loaded = load_character_toml(&toml).unwrap();
+            assert_eq!(loaded.name, "Ann", "{:?}", format_type);
+            assert_eq!(loaded.personality, character.personality, "{:?}", format_type);
+        }
+        assert!(load_character_toml("name = ").is_err());
+
+        let mut with_null = character.clone();
+        with_null.extensions.insert("chub".to_string(), serde_json::json!({"expressions": null}));
+        let error = export_as_toml(&with_null, Some("chara_card_v2"), &options()).err().unwrap();
+        assert!(error.to_string().contains("'extensions.chub.expressions' is null"));
+
+        let mut with_null = character.clone();
+        with_null.character_book.as_mut().unwrap().entries[0].extensions.insert("depth".to_string(), serde_json::json!([1, null]));
+        let error = export_as_toml(&with_null, None, &options()).err().unwrap();
+        assert!(error.to_string().contains("'character_book.entries[0].extensions.depth[1]' is null"));
+    }
+}
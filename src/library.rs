@@ -0,0 +1,328 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use pyo3::prelude::*;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+
+use crate::{CharacterClass, load_character_path};
+use crate::duplicates::fingerprint;
+
+const CARD_EXTENSIONS: [&str; 8] = ["png", "json", "yaml", "yml", "toml", "md", "byaf", "charx"];
+
+#[derive(Clone)]
+#[pyclass]
+pub struct LibraryEntry {
+    #[pyo3(get)]
+    path: String,
+    #[pyo3(get)]
+    name: String,
+    #[pyo3(get)]
+    tags: Vec<String>,
+    #[pyo3(get)]
+    fingerprint: String,
+    #[pyo3(get)]
+    modified_time: i64,
+}
+
+#[pymethods]
+impl LibraryEntry {
+    fn __repr__(&self) -> String {
+        format!("<LibraryEntry {} ({})>", self.name, self.path)
+    }
+}
+
+#[pyclass]
+pub struct CharacterLibrary {
+    directory: PathBuf,
+    connection: Connection,
+}
+
+#[pymethods]
+impl CharacterLibrary {
+    #[new]
+    #[pyo3(signature = (directory, database_path = None))]
+    fn new(directory: &str, database_path: Option<&str>) -> PyResult<Self> {
+        let directory = PathBuf::from(directory);
+        if !directory.is_dir() {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!("'{}' is not a directory", directory.display())));
+        }
+        let database_path = match database_path {
+            Some(path) => PathBuf::from(path),
+            None => directory.join("aichar_library.db"),
+        };
+        let connection = Connection::open(&database_path).map_err(database_error)?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS characters (
+                path TEXT PRIMARY KEY,
+                mtime INTEGER NOT NULL,
+                size INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                summary TEXT NOT NULL,
+                personality TEXT NOT NULL,
+                scenario TEXT NOT NULL,
+                greeting_message TEXT NOT NULL,
+                example_messages TEXT NOT NULL,
+                fingerprint TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS tags (
+                path TEXT NOT NULL,
+                tag TEXT NOT NULL COLLATE NOCASE,
+                PRIMARY KEY (path, tag)
+            );
+            CREATE VIRTUAL TABLE IF NOT EXISTS characters_fts USING fts5(path UNINDEXED, personality, scenario);"
+        ).map_err(database_error)?;
+        Ok(CharacterLibrary { directory, connection })
+    }
+
+    fn scan(&mut self) -> PyResult<HashMap<String, usize>> {
+        let mut files = Vec::new();
+        collect_card_files(&self.directory, &mut files, &mut HashSet::new())?;
+
+        let mut counts: HashMap<String, usize> = ["added", "updated", "removed", "unchanged", "failed"]
+            .iter().map(|key| (key.to_string(), 0)).collect();
+        let transaction = self.connection.transaction().map_err(database_error)?;
+        let mut seen = HashSet::new();
+
+        for file in files {
+            let path = file.to_string_lossy().to_string();
+            seen.insert(path.clone());
+            // Files can vanish or become unreadable after the directory walk, they fail like files that don't load.
+            let Ok((mtime, size)) = file_stamp(&file) else {
+                *counts.get_mut("failed").expect("Count exists") += 1;
+                remove_entry(&transaction, &path)?;
+                continue;
+            };
+
+            let stored: Option<(i64, i64)> = transaction
+                .query_row("SELECT mtime, size FROM characters WHERE path = ?1", params![path], |row| Ok((row.get(0)?, row.get(1)?)))
+                .optional().map_err(database_error)?;
+            if stored == Some((mtime, size)) {
+                *counts.get_mut("unchanged").expect("Count exists") += 1;
+                continue;
+            }
+
+            let Ok(character) = load_character_path(&path) else {
+                *counts.get_mut("failed").expect("Count exists") += 1;
+                remove_entry(&transaction, &path)?;
+                continue;
+            };
+            remove_entry(&transaction, &path)?;
+            insert_entry(&transaction, &path, mtime, size, &character)?;
+            *counts.get_mut(if stored.is_some() { "updated" } else { "added" }).expect("Count exists") += 1;
+        }
+
+        let stored_paths: Vec<String> = {
+            let mut statement = transaction.prepare("SELECT path FROM characters").map_err(database_error)?;
+            let rows = statement.query_map([], |row| row.get(0)).map_err(database_error)?;
+            rows.collect::<Result<_, _>>().map_err(database_error)?
+        };
+        for path in stored_paths.iter().filter(|path| !seen.contains(*path)) {
+            remove_entry(&transaction, path)?;
+            *counts.get_mut("removed").expect("Count exists") += 1;
+        }
+
+        transaction.commit().map_err(database_error)?;
+        Ok(counts)
+    }
+
+    #[pyo3(signature = (query = None, tags = None, limit = None))]
+    fn search(&self, query: Option<&str>, tags: Option<Vec<String>>, limit: Option<i64>) -> PyResult<Vec<LibraryEntry>> {
+        let mut sql = String::from("SELECT c.path, c.name, c.fingerprint, c.mtime FROM characters c");
+        let mut values: Vec<String> = Vec::new();
+        if let Some(query) = query {
+            sql.push_str(" JOIN characters_fts f ON f.path = c.path AND characters_fts MATCH ?");
+            values.push(query.to_string());
+        }
+        let tags = tags.unwrap_or_default();
+        for (index, tag) in tags.iter().enumerate() {
+            sql.push_str(if index == 0 { " WHERE" } else { " AND" });
+            sql.push_str(" EXISTS (SELECT 1 FROM tags t WHERE t.path = c.path AND t.tag = ?)");
+            values.push(tag.clone());
+        }
+        sql.push_str(if query.is_some() { " ORDER BY bm25(characters_fts)" } else { " ORDER BY c.name, c.path" });
+        sql.push_str(&format!(" LIMIT {}", limit.unwrap_or(-1)));
+
+        let mut entries = match (self.query_entries(&sql, &values), query) {
+            // Plain text such as "knight's sword" is not valid FTS5 syntax, search for its words instead.
+            (Err(error), Some(query)) => {
+                values[0] = quote_terms(query);
+                self.query_entries(&sql, &values).map_err(|_| database_error(error))?
+            },
+            (entries, _) => entries.map_err(database_error)?,
+        };
+        for entry in &mut entries {
+            entry.tags = self.entry_tags(&entry.path)?;
+        }
+        Ok(entries)
+    }
+
+    fn tags(&self) -> PyResult<Vec<String>> {
+        let mut statement = self.connection.prepare("SELECT DISTINCT tag FROM tags ORDER BY tag").map_err(database_error)?;
+        let rows = statement.query_map([], |row| row.get(0)).map_err(database_error)?;
+        rows.collect::<Result<_, _>>().map_err(database_error)
+    }
+
+    fn load(&self, path: &str) -> PyResult<CharacterClass> {
+        load_character_path(path)
+    }
+
+    fn __len__(&self) -> PyResult<usize> {
+        let count: i64 = self.connection.query_row("SELECT COUNT(*) FROM characters", [], |row| row.get(0)).map_err(database_error)?;
+        Ok(count as usize)
+    }
+}
+
+impl CharacterLibrary {
+    fn query_entries(&self, sql: &str, values: &[String]) -> rusqlite::Result<Vec<LibraryEntry>> {
+        let mut statement = self.connection.prepare(sql)?;
+        let rows = statement.query_map(params_from_iter(values.iter()), |row| {
+            Ok(LibraryEntry {
+                path: row.get(0)?,
+                name: row.get(1)?,
+                tags: Vec::new(),
+                fingerprint: row.get(2)?,
+                modified_time: row.get(3)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    fn entry_tags(&self, path: &str) -> PyResult<Vec<String>> {
+        let mut statement = self.connection.prepare_cached("SELECT tag FROM tags WHERE path = ?1 ORDER BY rowid").map_err(database_error)?;
+        let rows = statement.query_map(params![path], |row| row.get(0)).map_err(database_error)?;
+        rows.collect::<Result<_, _>>().map_err(database_error)
+    }
+}
+
+// Directories are tracked by their canonical path, so symlinks pointing back up the tree are only walked once.
+fn collect_card_files(directory: &Path, files: &mut Vec<PathBuf>, visited: &mut HashSet<PathBuf>) -> PyResult<()> {
+    if !visited.insert(fs::canonicalize(directory)?) {
+        return Ok(());
+    }
+    let mut entries: Vec<PathBuf> = fs::read_dir(directory)?.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect();
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            collect_card_files(&path, files, visited)?;
+        } else if path.extension().and_then(|extension| extension.to_str())
+            .is_some_and(|extension| CARD_EXTENSIONS.contains(&extension.to_lowercase().as_str())) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn file_stamp(file: &Path) -> std::io::Result<(i64, i64)> {
+    let metadata = fs::metadata(file)?;
+    let mtime = metadata.modified()?.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as i64).unwrap_or_default();
+    Ok((mtime, metadata.len() as i64))
+}
+
+fn quote_terms(query: &str) -> String {
+    query.split_whitespace().map(|term| format!("\"{}\"", term.replace('"', "\"\""))).collect::<Vec<String>>().join(" ")
+}
+
+fn insert_entry(connection: &Connection, path: &str, mtime: i64, size: i64, character: &CharacterClass) -> PyResult<()> {
+    connection.execute(
+        "INSERT INTO characters (path, mtime, size, name, summary, personality, scenario, greeting_message, example_messages, fingerprint)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![path, mtime, size, character.name, character.summary, character.personality, character.scenario,
+            character.greeting_message, character.example_messages, fingerprint(character)],
+    ).map_err(database_error)?;
+    connection.execute(
+        "INSERT INTO characters_fts (path, personality, scenario) VALUES (?1, ?2, ?3)",
+        params![path, character.personality, character.scenario],
+    ).map_err(database_error)?;
+    for tag in &character.tags {
+        connection.execute("INSERT OR IGNORE INTO tags (path, tag) VALUES (?1, ?2)", params![path, tag]).map_err(database_error)?;
+    }
+    Ok(())
+}
+
+fn remove_entry(connection: &Connection, path: &str) -> PyResult<()> {
+    for table in ["characters", "characters_fts", "tags"] {
+        connection.execute(&format!("DELETE FROM {} WHERE path = ?1", table), params![path]).map_err(database_error)?;
+    }
+    Ok(())
+}
+
+fn database_error(error: rusqlite::Error) -> PyErr {
+    pyo3::exceptions::PyValueError::new_err(format!("Character library database error: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempPath;
+
+    fn library_directory(name: &str) -> TempPath {
+        let temp = TempPath::new(&format!("library_{}", name));
+        let directory = temp.path();
+        fs::create_dir_all(directory.join("nested")).unwrap();
+        fs::write(directory.join("knight.json"), r#"{"name": "Knight", "personality": "A brave knight's heart", "tags": ["fantasy"]}"#).unwrap();
+        fs::write(directory.join("nested/baker.yaml"), "name: Baker\npersonality: Bakes bread\ntags: [town]\n").unwrap();
+        fs::write(directory.join("broken.json"), "{").unwrap();
+        fs::write(directory.join("notes.txt"), "not a card").unwrap();
+        temp
+    }
+
+    fn names(entries: &[LibraryEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.name.as_str()).collect()
+    }
+
+    #[test]
+    fn scan_and_rescan() {
+        let temp = library_directory("scan");
+        let directory = temp.path();
+        let mut library = CharacterLibrary::new(temp.as_str(), None).unwrap();
+        let counts = library.scan().unwrap();
+        assert_eq!((counts["added"], counts["failed"]), (2, 1));
+        assert_eq!(library.__len__().unwrap(), 2);
+
+        fs::remove_file(directory.join("nested/baker.yaml")).unwrap();
+        let counts = library.scan().unwrap();
+        assert_eq!((counts["unchanged"], counts["removed"], counts["failed"]), (1, 1, 1));
+        assert_eq!(library.tags().unwrap(), ["fantasy"]);
+    }
+
+    #[test]
+    fn search_by_text_and_tags() {
+        let temp = library_directory("search");
+        let mut library = CharacterLibrary::new(temp.as_str(), None).unwrap();
+        library.scan().unwrap();
+        assert_eq!(names(&library.search(None, None, None).unwrap()), ["Baker", "Knight"]);
+        assert_eq!(names(&library.search(Some("bread OR brave"), None, None).unwrap()).len(), 2);
+        assert_eq!(names(&library.search(None, Some(vec!["FANTASY".to_string()]), None).unwrap()), ["Knight"]);
+        assert_eq!(names(&library.search(Some("knight's heart"), None, None).unwrap()), ["Knight"]);
+        assert_eq!(names(&library.search(Some("\"unbalanced"), None, None).unwrap()), Vec::<&str>::new());
+        assert_eq!(library.search(None, None, Some(1)).unwrap().len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_loops_are_walked_once() {
+        let temp = library_directory("symlinks");
+        let directory = temp.path();
+        std::os::unix::fs::symlink(directory, directory.join("nested/loop")).unwrap();
+        let mut files = Vec::new();
+        collect_card_files(directory, &mut files, &mut HashSet::new()).unwrap();
+        assert_eq!(files.len(), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_files_fail_without_aborting_the_scan() {
+        let temp = library_directory("dangling");
+        let directory = temp.path();
+        std::os::unix::fs::symlink(directory.join("gone.json"), directory.join("dangling.json")).unwrap();
+        let mut library = CharacterLibrary::new(temp.as_str(), None).unwrap();
+        let counts = library.scan().unwrap();
+        assert_eq!((counts["added"], counts["failed"]), (2, 2));
+    }
+
+    #[test]
+    fn terms_are_quoted() {
+        assert_eq!(quote_terms(r#"knight's "sword""#), r#""knight's" """sword""""#);
+    }
+}
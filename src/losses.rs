@@ -0,0 +1,175 @@
+use pyo3::prelude::*;
+
+use crate::{CharacterBook, CharacterClass, SUPPORTED_FORMATS};
+
+pub fn export_losses(character: &CharacterClass, format_type: &str) -> PyResult<Vec<String>> {
+    let summary_lost = !character.summary.is_empty() && !character.personality.is_empty();
+    let book = character.character_book.as_ref();
+    let format_type = format_type.to_lowercase();
+    let card_fields = [
+        ("group_only_greetings", !character.group_only_greetings.is_empty()),
+        ("nickname", character.nickname.is_some()),
+        ("creator", !character.creator.is_empty()),
+        ("character_version", !character.character_version.is_empty()),
+        ("creator_notes", !character.creator_notes.is_empty()),
+        ("system_prompt", !character.system_prompt.is_empty()),
+        ("post_history_instructions", !character.post_history_instructions.is_empty()),
+    ];
+    let (mut candidates, kept_card_fields): (Vec<(&str, bool)>, &[&str]) = match format_type.as_str() {
+        "tavernai" | "sillytavern" => (vec![
+            ("tags", !character.tags.is_empty()),
+            ("alternate_greetings", !character.alternate_greetings.is_empty()),
+            ("character_book", book.is_some()),
+            ("extensions", !character.extensions.is_empty()),
+            ("assets", !character.assets.is_empty()),
+        ], &[]),
+        "chara_card_v2" => (vec![
+            ("assets", !character.assets.is_empty()),
+        ], &["creator", "character_version", "creator_notes", "system_prompt", "post_history_instructions"]),
+        "textgenerationwebui" | "pygmalion" => (vec![
+            ("summary", summary_lost),
+            ("tags", !character.tags.is_empty()),
+            ("alternate_greetings", !character.alternate_greetings.is_empty()),
+            ("character_book", book.is_some()),
+            ("extensions", !character.extensions.is_empty()),
+            ("assets", !character.assets.is_empty()),
+        ], &[]),
+        "aicompanion" => (vec![
+            ("summary", summary_lost),
+            ("scenario", !character.scenario.is_empty()),
+            ("tags", !character.tags.is_empty()),
+            ("alternate_greetings", !character.alternate_greetings.is_empty()),
+            ("character_book", book.is_some()),
+            ("extensions", !character.extensions.is_empty()),
+            ("assets", !character.assets.is_empty()),
+        ], &[]),
+        "risuai" | "neutral" => (Vec::new(), &["group_only_greetings", "nickname", "creator", "character_version", "creator_notes", "system_prompt", "post_history_instructions"]),
+        "agnaistic" | "agnai" => (vec![
+            ("character_book", book.is_some_and(|book| has_entry_settings(book, false))),
+            ("assets", !character.assets.is_empty()),
+        ], &["creator", "character_version", "system_prompt", "post_history_instructions"]),
+        "backyard" | "faraday" | "byaf" => (vec![
+            ("summary", !character.summary.is_empty()),
+            ("tags", !character.tags.is_empty()),
+            ("alternate_greetings", format_type != "byaf" && !character.alternate_greetings.is_empty()),
+            ("character_book", book.is_some_and(|book| has_entry_settings(book, true))),
+            ("extensions", character.extensions.iter().any(|(key, value)| key != "backyard"
+                || (format_type != "byaf" && value.as_object().is_some_and(|backyard| backyard.keys().any(|key| key != "basePrompt"))))),
+            ("assets", !character.assets.is_empty()),
+        ], &[]),
+        "koboldai" | "kobold" | "koboldcpp" => (vec![
+            ("tags", !character.tags.is_empty()),
+            ("alternate_greetings", !character.alternate_greetings.is_empty()),
+            ("character_book", book.is_some_and(has_world_info_losses)),
+            ("extensions", character.extensions.keys().any(|key| key != "kobold")),
+            ("assets", !character.assets.is_empty()),
+        ], &[]),
+        _ => {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!("Format not supported, supported formats: {}, 'byaf'", SUPPORTED_FORMATS)));
+        }
+    };
+    if !matches!(format_type.as_str(), "risuai" | "neutral") {
+        candidates.push(("embedded_assets", !character.embedded_assets.is_empty()));
+    }
+    candidates.extend(card_fields.into_iter().filter(|(field, _)| !kept_card_fields.contains(field)));
+    Ok(candidates.into_iter().filter(|(_, lost)| *lost).map(|(field, _)| field.to_string()).collect())
+}
+
+fn has_entry_settings(book: &CharacterBook, keys_and_content_only: bool) -> bool {
+    let book_settings = book.scan_depth.is_some() || book.token_budget.is_some() || book.recursive_scanning.is_some() || !book.extensions.is_empty()
+        || (keys_and_content_only && (book.name.is_some() || book.description.is_some()));
+    book_settings || book.entries.iter().enumerate().any(|(index, entry)| {
+        let entry_settings = entry.case_sensitive.is_some() || entry.id.is_some() || entry.comment.is_some() || entry.selective.is_some()
+            || entry.secondary_keys.is_some() || entry.constant.is_some() || entry.position.is_some() || entry.use_regex == Some(true) || !entry.extensions.is_empty();
+        entry_settings || (keys_and_content_only && (entry.name.is_some() || entry.priority.is_some() || !entry.enabled || entry.insertion_order != index as i64))
+    })
+}
+
+fn has_world_info_losses(book: &CharacterBook) -> bool {
+    let book_settings = book.name.is_some() || book.description.is_some() || book.scan_depth.is_some() || book.token_budget.is_some()
+        || book.recursive_scanning.is_some() || !book.extensions.is_empty();
+    book_settings || book.entries.iter().enumerate().any(|(index, entry)| {
+        entry.keys.iter().chain(entry.secondary_keys.iter().flatten()).any(|key| key.contains(','))
+            || entry.case_sensitive.is_some() || entry.id.is_some() || entry.priority.is_some() || entry.position.is_some() || entry.use_regex == Some(true) || !entry.extensions.is_empty()
+            || (entry.name.is_some() && entry.comment.is_some()) || entry.insertion_order != index as i64
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LorebookEntry;
+
+    fn book(entry: LorebookEntry) -> CharacterBook {
+        CharacterBook { entries: vec![entry], ..CharacterBook::default() }
+    }
+
+    fn plain_entry() -> LorebookEntry {
+        LorebookEntry { keys: vec!["town".to_string()], content: "A town".to_string(), enabled: true, ..LorebookEntry::default() }
+    }
+
+    fn losses(character: &CharacterClass, format_type: &str) -> Vec<String> {
+        export_losses(character, format_type).unwrap()
+    }
+
+    #[test]
+    fn empty_character_loses_nothing() {
+        for format_type in ["tavernai", "chara_card_v2", "textgenerationwebui", "aicompanion", "risuai", "agnaistic", "backyard", "byaf", "koboldai", "neutral"] {
+            assert!(losses(&CharacterClass::default(), format_type).is_empty(), "{}", format_type);
+        }
+    }
+
+    #[test]
+    fn reports_fields_each_format_lacks() {
+        let character = CharacterClass::default()
+            .with_summary("Summary")
+            .with_personality("Kind")
+            .with_scenario("A town")
+            .with_tags(vec!["tag".to_string()])
+            .with_creator("someone")
+            .with_character_book(Some(book(plain_entry())));
+        assert_eq!(losses(&character, "TavernAI"), ["tags", "character_book", "creator"]);
+        assert_eq!(losses(&character, "aicompanion"), ["summary", "scenario", "tags", "character_book", "creator"]);
+        assert_eq!(losses(&character, "pygmalion"), ["summary", "tags", "character_book", "creator"]);
+        assert!(losses(&character, "chara_card_v2").is_empty());
+        assert!(losses(&character, "neutral").is_empty());
+        assert!(!losses(&character.clone().with_personality(""), "pygmalion").contains(&"summary".to_string()));
+    }
+
+    #[test]
+    fn lorebook_settings_are_checked_per_format() {
+        let character = CharacterClass::default().with_character_book(Some(book(plain_entry())));
+        for format_type in ["agnaistic", "backyard", "koboldai"] {
+            assert!(losses(&character, format_type).is_empty(), "{}", format_type);
+        }
+
+        let named = CharacterClass::default().with_character_book(Some(book(LorebookEntry { name: Some("Town".to_string()), ..plain_entry() })));
+        assert!(losses(&named, "agnaistic").is_empty());
+        assert_eq!(losses(&named, "backyard"), ["character_book"]);
+        assert!(losses(&named, "koboldai").is_empty());
+
+        let regex = CharacterClass::default().with_character_book(Some(book(LorebookEntry { use_regex: Some(true), ..plain_entry() })));
+        for format_type in ["agnaistic", "backyard", "koboldai"] {
+            assert_eq!(losses(&regex, format_type), ["character_book"], "{}", format_type);
+        }
+        let reordered = CharacterClass::default().with_character_book(Some(book(LorebookEntry { insertion_order: 3, ..plain_entry() })));
+        assert_eq!(losses(&reordered, "koboldai"), ["character_book"]);
+        let comma = CharacterClass::default().with_character_book(Some(book(LorebookEntry { keys: vec!["Smith, John".to_string()], ..plain_entry() })));
+        assert_eq!(losses(&comma, "koboldai"), ["character_book"]);
+    }
+
+    #[test]
+    fn own_extensions_are_not_losses() {
+        let backyard = CharacterClass::default().with_extensions(crate::backyard::base_prompt_extension("Custom".to_string()));
+        assert!(losses(&backyard, "backyard").is_empty());
+        assert_eq!(losses(&backyard, "koboldai"), ["extensions"]);
+        let kobold = CharacterClass::default().with_extensions(crate::kobold::authors_note_extension("Note".to_string()));
+        assert!(losses(&kobold, "koboldai").is_empty());
+        assert_eq!(losses(&kobold, "backyard"), ["extensions"]);
+    }
+
+    #[test]
+    fn unknown_formats_are_rejected() {
+        assert!(export_losses(&CharacterClass::default(), "xml").is_err());
+    }
+}
@@ -0,0 +1,372 @@
+use pyo3::prelude::*;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::CharacterClass;
+
+const SECTIONS: [&str; 9] = ["Summary", "Personality", "Scenario", "Greeting", "Alternate greetings", "Example dialogue", "Character book", "Extensions", "Assets"];
+const FRONT_MATTER_KEYS: [&str; 9] = ["tags", "nickname", "creator", "character_version", "creator_notes", "system_prompt", "post_history_instructions", "group_only_greetings", "metadata"];
+
+#[derive(Serialize)]
+struct FrontMatter<'a, M: Serialize> {
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    tags: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nickname: Option<&'a str>,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    creator: &'a str,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    character_version: &'a str,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    creator_notes: &'a str,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    system_prompt: &'a str,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    post_history_instructions: &'a str,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    group_only_greetings: &'a [String],
+    metadata: M,
+}
+
+enum Part {
+    Line(String),
+    Fence { opening: String, lines: Vec<String>, closing: String },
+}
+
+#[derive(Default)]
+struct Section {
+    parts: Vec<Part>,
+    greetings: Vec<Vec<Part>>,
+}
+
+impl Section {
+    fn push(&mut self, part: Part) {
+        match self.greetings.last_mut() {
+            Some(greeting) => greeting.push(part),
+            None => self.parts.push(part),
+        }
+    }
+}
+
+pub fn render_markdown<M: Serialize>(character: &CharacterClass, metadata: M) -> String {
+    let front_matter = FrontMatter {
+        tags: &character.tags,
+        nickname: character.nickname.as_deref(),
+        creator: &character.creator,
+        character_version: &character.character_version,
+        creator_notes: &character.creator_notes,
+        system_prompt: &character.system_prompt,
+        post_history_instructions: &character.post_history_instructions,
+        group_only_greetings: &character.group_only_greetings,
+        metadata,
+    };
+    let mut out = format!("---\n{}---\n\n# {}\n", serde_yaml::to_string(&front_matter).expect("Error while serializing YAML"), character.name);
+
+    for (heading, text) in [("Summary", &character.summary), ("Personality", &character.personality), ("Scenario", &character.scenario), ("Greeting", &character.greeting_message)] {
+        push_text(&mut out, &format!("\n## {}\n\n", heading), text);
+    }
+    if !character.alternate_greetings.is_empty() {
+        out.push_str("\n## Alternate greetings\n");
+        for (index, greeting) in character.alternate_greetings.iter().enumerate() {
+            push_text(&mut out, &format!("\n### Greeting {}\n\n", index + 1), greeting);
+        }
+    }
+    out.push_str("\n## Example dialogue\n\n");
+    push_fenced(&mut out, "", &format!("{}\n", character.example_messages));
+
+    if let Some(book) = &character.character_book {
+        out.push_str("\n## Character book\n\n");
+        push_fenced(&mut out, "yaml", &serde_yaml::to_string(book).expect("Error while serializing YAML"));
+    }
+    if !character.extensions.is_empty() {
+        out.push_str("\n## Extensions\n\n");
+        push_fenced(&mut out, "yaml", &serde_yaml::to_string(&character.extensions).expect("Error while serializing YAML"));
+    }
+    if !character.assets.is_empty() {
+        out.push_str("\n## Assets\n\n");
+        push_fenced(&mut out, "yaml", &serde_yaml::to_string(&character.assets).expect("Error while serializing YAML"));
+    }
+    out
+}
+
+pub fn parse_markdown(text: &str) -> PyResult<Value> {
+    let (front_matter, body) = split_front_matter(text)?;
+    let mut name: Option<String> = None;
+    let mut sections: Vec<(&str, Section)> = Vec::new();
+    let mut fence: Option<(usize, String, Vec<String>)> = None;
+
+    for line in body.lines() {
+        if let Some((length, opening, lines)) = fence.as_mut() {
+            if is_closing_fence(line, *length) {
+                let part = Part::Fence { opening: std::mem::take(opening), lines: std::mem::take(lines), closing: line.to_string() };
+                if let Some((_, section)) = sections.last_mut() {
+                    section.push(part);
+                }
+                fence = None;
+            } else {
+                lines.push(line.to_string());
+            }
+            continue;
+        }
+        if let Some(heading) = line.strip_prefix("## ") {
+            let heading = section_name(heading.trim())
+                .ok_or_else(|| pyo3::exceptions::PyValueError::new_err(format!("Unknown section '{}', supported sections: {}", heading.trim(), SECTIONS.join(", "))))?;
+            sections.push((heading, Section::default()));
+        } else if let Some(heading) = line.strip_prefix("### ").or(Some(line).filter(|line| line.trim() == "###")) {
+            match sections.last_mut() {
+                Some(("Alternate greetings", section)) => section.greetings.push(Vec::new()),
+                _ => return Err(pyo3::exceptions::PyValueError::new_err(format!("Subsection '{}' outside of the 'Alternate greetings' section", heading.trim()))),
+            }
+        } else if line.starts_with("# ") || line.trim() == "#" {
+            if name.is_none() && sections.is_empty() {
+                name = Some(line[1..].trim().to_string());
+            }
+        } else if let Some(length) = opening_fence(line) {
+            fence = Some((length, line.to_string(), Vec::new()));
+        } else if let Some((_, section)) = sections.last_mut() {
+            section.push(Part::Line(line.strip_prefix('\\').unwrap_or(line).to_string()));
+        }
+    }
+    if let Some((_, opening, _)) = fence {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!("Code block '{}' is not closed", opening.trim())));
+    }
+
+    let mut character = serde_json::Map::new();
+    character.insert("name".to_string(), Value::from(name.unwrap_or_default()));
+    if let Some(Value::Object(front_matter)) = front_matter {
+        for (key, value) in front_matter {
+            if FRONT_MATTER_KEYS.contains(&key.as_str()) {
+                character.insert(key, value);
+            } else if key == "name" || SECTIONS.iter().any(|section| section_key(section) == key) {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!("Front matter key '{}' is written as a heading, not in front matter", key)));
+            }
+        }
+    }
+    for (heading, section) in sections {
+        let value = match heading {
+            "Alternate greetings" => Value::from(section.greetings.iter().map(|parts| inline_text(parts)).collect::<Vec<String>>()),
+            "Example dialogue" => Value::from(single_fence(&section.parts).unwrap_or_else(|| inline_text(&section.parts))),
+            "Character book" | "Extensions" | "Assets" => parse_yaml(heading, &fence_content(&section.parts))?,
+            _ => Value::from(inline_text(&section.parts)),
+        };
+        character.insert(section_key(heading).to_string(), value);
+    }
+    Ok(Value::Object(character))
+}
+
+fn section_key(heading: &str) -> &'static str {
+    match heading {
+        "Summary" => "description",
+        "Personality" => "personality",
+        "Scenario" => "scenario",
+        "Greeting" => "first_mes",
+        "Alternate greetings" => "alternate_greetings",
+        "Example dialogue" => "mes_example",
+        "Character book" => "character_book",
+        "Extensions" => "extensions",
+        _ => "assets",
+    }
+}
+
+// Text sections keep code blocks as written, fences included.
+fn inline_text(parts: &[Part]) -> String {
+    let lines: Vec<String> = parts.iter().flat_map(|part| match part {
+        Part::Line(line) => vec![line.clone()],
+        Part::Fence { opening, lines, closing } => std::iter::once(opening).chain(lines).chain(std::iter::once(closing)).cloned().collect(),
+    }).collect();
+    join_lines(&lines)
+}
+
+// The content of a section that holds nothing but one code block, as export_markdown writes example dialogue.
+fn single_fence(parts: &[Part]) -> Option<String> {
+    let mut content = parts.iter().filter(|part| !matches!(part, Part::Line(line) if line.trim().is_empty()));
+    match (content.next(), content.next()) {
+        (Some(Part::Fence { lines, .. }), None) => Some(lines.join("\n")),
+        _ => None,
+    }
+}
+
+fn fence_content(parts: &[Part]) -> String {
+    parts.iter().flat_map(|part| match part {
+        Part::Line(line) => std::slice::from_ref(line),
+        Part::Fence { lines, .. } => lines.as_slice(),
+    }).cloned().collect::<Vec<String>>().join("\n")
+}
+
+fn push_text(out: &mut String, heading: &str, text: &str) {
+    out.push_str(heading);
+    if text.is_empty() {
+        return;
+    }
+    for line in text.split('\n') {
+        if line.starts_with(['#', '`', '\\']) {
+            out.push('\\');
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+}
+
+fn push_fenced(out: &mut String, language: &str, text: &str) {
+    let longest_run = text.split(|c| c != '`').map(str::len).max().unwrap_or_default();
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    out.push_str(&format!("{}{}\n", fence, language));
+    out.push_str(text);
+    out.push_str(&format!("{}\n", fence));
+}
+
+fn split_front_matter(text: &str) -> PyResult<(Option<Value>, &str)> {
+    let Some(rest) = text.strip_prefix("---\n").or(text.strip_prefix("---\r\n")) else {
+        return Ok((None, text));
+    };
+    let (yaml, body) = match rest.strip_prefix("---") {
+        Some(body) => ("", body),
+        None => {
+            let end = rest.find("\n---").ok_or_else(|| pyo3::exceptions::PyValueError::new_err("Front matter is not closed with '---'"))?;
+            (&rest[..end + 1], &rest[end + 4..])
+        },
+    };
+    let body = body.trim_start_matches(['\r', '\n']);
+    if yaml.trim().is_empty() {
+        return Ok((None, body));
+    }
+    let value = serde_yaml::from_str(yaml)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Error while parsing front matter: {}", e)))?;
+    Ok((Some(value), body))
+}
+
+fn section_name(heading: &str) -> Option<&'static str> {
+    let heading = heading.to_lowercase();
+    let name = match heading.as_str() {
+        "description" => "Summary",
+        "first message" => "Greeting",
+        "example messages" => "Example dialogue",
+        "lorebook" => "Character book",
+        _ => SECTIONS.iter().find(|section| section.to_lowercase() == heading)?,
+    };
+    Some(name)
+}
+
+fn opening_fence(line: &str) -> Option<usize> {
+    let length = line.len() - line.trim_start_matches('`').len();
+    (length >= 3 && !line[length..].contains('`')).then_some(length)
+}
+
+fn is_closing_fence(line: &str, length: usize) -> bool {
+    let line = line.trim_end();
+    line.len() >= length && line.chars().all(|c| c == '`')
+}
+
+fn join_lines(lines: &[String]) -> String {
+    let lines = match lines.split_first() {
+        Some((first, rest)) if first.is_empty() => rest,
+        _ => lines,
+    };
+    let lines = match lines.split_last() {
+        Some((last, rest)) if last.is_empty() => rest,
+        _ => lines,
+    };
+    lines.join("\n")
+}
+
+fn parse_yaml(heading: &str, text: &str) -> PyResult<Value> {
+    serde_yaml::from_str(text)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Error while parsing '{}' section: {}", heading, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CharacterBook, LorebookEntry, LoadCharacterClass, character_from_data};
+
+    fn round_trip(character: &CharacterClass) -> CharacterClass {
+        let markdown = render_markdown(character, serde_json::json!({}));
+        let char_data: LoadCharacterClass = serde_json::from_value(parse_markdown(&markdown).unwrap()).unwrap();
+        character_from_data(char_data, None)
+    }
+
+    #[test]
+    fn text_fields_round_trip_exactly() {
+        let texts = ["", "hello", "\n\nhello\n", "\n", "a\n\n\nb", "## Heading\n# Title\n```\n\\escaped", "trailing\n\n"];
+        for text in texts {
+            let character = CharacterClass::default()
+                .with_name("Ann")
+                .with_summary(text)
+                .with_personality(text)
+                .with_scenario(text)
+                .with_greeting_message(text)
+                .with_example_messages(text)
+                .with_alternate_greetings(vec![text.to_string(), "next".to_string()]);
+            let loaded = round_trip(&character);
+            assert_eq!(loaded.summary, text, "{:?}", text);
+            assert_eq!(loaded.personality, text, "{:?}", text);
+            assert_eq!(loaded.scenario, text, "{:?}", text);
+            assert_eq!(loaded.greeting_message, text, "{:?}", text);
+            assert_eq!(loaded.example_messages, text, "{:?}", text);
+            assert_eq!(loaded.alternate_greetings, [text, "next"], "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn front_matter_and_yaml_sections_round_trip() {
+        let book = CharacterBook {
+            entries: vec![LorebookEntry { keys: vec!["town".to_string()], content: "```\nA town".to_string(), enabled: true, ..LorebookEntry::default() }],
+            ..CharacterBook::default()
+        };
+        let mut character = CharacterClass::default()
+            .with_name("Ann")
+            .with_tags(vec!["tag".to_string()])
+            .with_nickname(Some("Annie".to_string()))
+            .with_system_prompt("System")
+            .with_character_book(Some(book))
+            .with_assets(vec![serde_json::json!({"type": "icon", "uri": "ccdefault:"})]);
+        character.extensions.insert("depth_prompt".to_string(), serde_json::json!({"depth": 4}));
+        let loaded = round_trip(&character);
+        assert_eq!(loaded.tags, ["tag"]);
+        assert_eq!(loaded.nickname.as_deref(), Some("Annie"));
+        assert_eq!(loaded.system_prompt, "System");
+        assert!(loaded.character_book == character.character_book);
+        assert_eq!(loaded.extensions, character.extensions);
+        assert_eq!(loaded.assets, character.assets);
+    }
+
+    #[test]
+    fn hand_written_markdown_is_accepted() {
+        let value = parse_markdown("# Ann\n## Description\nA girl\n## First message\nHi\n## Example messages\n{{user}}: Hi\n").unwrap();
+        assert_eq!(value["name"], "Ann");
+        assert_eq!(value["description"], "A girl");
+        assert_eq!(value["first_mes"], "Hi");
+        assert_eq!(value["mes_example"], "{{user}}: Hi");
+        assert_eq!(parse_markdown("").unwrap(), serde_json::json!({"name": ""}));
+    }
+
+    #[test]
+    fn code_blocks_in_text_sections_are_kept() {
+        let value = parse_markdown("# Ann
+## Summary
+Before
+```
+## not a heading
+```
+After
+## Example dialogue
+Intro
+```
+{{user}}: Hi
+```
+").unwrap();
+        assert_eq!(value["description"], "Before\n```\n## not a heading\n```\nAfter");
+        assert_eq!(value["mes_example"], "Intro\n```\n{{user}}: Hi\n```");
+    }
+
+    #[test]
+    fn malformed_markdown_is_rejected() {
+        assert!(parse_markdown("# Ann\n\n## Hobbies\n\nCats\n").is_err());
+        assert!(parse_markdown("# Ann\n\n## Summary\n\n### Greeting 1\n").is_err());
+        assert!(parse_markdown("---\ntags: [a\n").is_err());
+        assert!(parse_markdown("---\ntags: [a\n---\n# Ann\n").is_err());
+        assert!(parse_markdown("# Ann\n\n## Extensions\n\n```yaml\n: [\n```\n").is_err());
+        assert!(parse_markdown("# Ann\n\n## Example dialogue\n\n```\n{{user}}: Hi\n").is_err());
+        assert!(parse_markdown("---\ndescription: A girl\n---\n# Ann\n").is_err());
+    }
+}
@@ -0,0 +1,272 @@
+use pyo3::prelude::*;
+
+pub type Attributes = Vec<(String, Vec<String>)>;
+
+pub fn parse_attributes(text: &str) -> Attributes {
+    let trimmed = text.trim();
+    let lower = trimmed.to_lowercase();
+    if lower.contains("character(") && trimmed.contains('{') {
+        parse_wpp(trimmed)
+    } else if trimmed.starts_with('[') && lower.contains("character:") {
+        parse_sbf(trimmed)
+    } else if is_bracketed(trimmed) {
+        parse_boostyle(trimmed)
+    } else {
+        Vec::new()
+    }
+}
+
+fn is_bracketed(text: &str) -> bool {
+    text.starts_with('[') && text.ends_with(']')
+}
+
+pub fn detect_style(text: &str) -> Option<&'static str> {
+    let trimmed = text.trim();
+    let lower = trimmed.to_lowercase();
+    if lower.contains("character(") && trimmed.contains('{') {
+        return Some("wpp").filter(|_| !parse_wpp(trimmed).is_empty());
+    }
+    if trimmed.starts_with('[') && lower.contains("character:") {
+        return Some("sbf").filter(|_| !parse_sbf(trimmed).is_empty());
+    }
+    if !is_bracketed(trimmed) {
+        return None;
+    }
+    let lines: Vec<&str> = trimmed.split(['\n', ';'])
+        .map(|line| line.trim().trim_start_matches('[').trim_end_matches(']').trim())
+        .filter(|line| !line.is_empty())
+        .collect();
+    let structured = !lines.is_empty() && trimmed.contains('+') && parse_boostyle(trimmed).len() == lines.len();
+    Some("boostyle").filter(|_| structured)
+}
+
+pub fn render_attributes(name: &str, attributes: &[(String, Vec<String>)], style: &str) -> PyResult<String> {
+    let structured = !style.eq_ignore_ascii_case("prose");
+    let quoted_text = std::iter::once(name)
+        .chain(attributes.iter().flat_map(|(key, values)| std::iter::once(key.as_str()).chain(values.iter().map(String::as_str))))
+        .find(|text| text.contains('"'));
+    if let Some(text) = quoted_text.filter(|_| structured) {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!("Persona attributes can't contain double quotes: {}", text)));
+    }
+    let quoted = |values: &[String], separator: &str| values.iter().map(|value| format!("\"{}\"", value)).collect::<Vec<String>>().join(separator);
+    match style.to_lowercase().as_str() {
+        "wpp" | "w++" => {
+            let lines: Vec<String> = attributes.iter().map(|(key, values)| format!("{}({})", key, quoted(values, " + "))).collect();
+            Ok(format!("[character(\"{}\") {{\n{}\n}}]", name, lines.join("\n")))
+        },
+        "sbf" => {
+            let parts: Vec<String> = attributes.iter().map(|(key, values)| format!("{}: {}", key, quoted(values, ", "))).collect();
+            Ok(format!("[ character: \"{}\"; {} ]", name, parts.join("; ")))
+        },
+        "boostyle" => {
+            let lines: Vec<String> = attributes.iter().map(|(key, values)| format!("{}: {}", key, values.join(" + "))).collect();
+            Ok(format!("[{}]", lines.join("\n")))
+        },
+        "prose" => {
+            let subject = if name.is_empty() { "{{char}}" } else { name };
+            let sentences: Vec<String> = attributes.iter()
+                .filter(|(_, values)| !values.is_empty())
+                .map(|(key, values)| {
+                    let key = key.split_once("'s ").map(|(_, key)| key).unwrap_or(key);
+                    let key = key.to_lowercase();
+                    let verb = if is_plural(&key) { "are" } else { "is" };
+                    format!("{}'s {} {} {}.", subject, key, verb, join_prose(values))
+                })
+                .collect();
+            Ok(sentences.join(" "))
+        },
+        _ => Err(pyo3::exceptions::PyValueError::new_err("Persona style not supported, supported styles: 'wpp', 'sbf', 'boostyle', 'prose'")),
+    }
+}
+
+fn is_plural(key: &str) -> bool {
+    let word = key.rsplit(' ').next().unwrap_or(key);
+    word.ends_with('s') && !["ss", "us", "is"].iter().any(|suffix| word.ends_with(suffix)) && !["species", "series"].contains(&word)
+}
+
+fn join_prose(values: &[String]) -> String {
+    match values {
+        [] => String::new(),
+        [value] => value.clone(),
+        [rest @ .., last] => format!("{} and {}", rest.join(", "), last),
+    }
+}
+
+fn parse_wpp(text: &str) -> Attributes {
+    let Some(open) = text.find('{') else {
+        return Vec::new();
+    };
+    let close = text.rfind('}').filter(|&close| close > open).unwrap_or(text.len());
+    let body = &text[open + 1..close];
+
+    let mut attributes = Vec::new();
+    let mut rest = body;
+    while let Some(paren) = rest.find('(') {
+        let key = rest[..paren].trim().trim_matches(|c: char| c == ',' || c.is_whitespace()).to_string();
+        let Some(end) = find_outside_quotes(&rest[paren + 1..], ')') else {
+            break;
+        };
+        let values = split_values(&rest[paren + 1..paren + 1 + end], '+');
+        if !key.is_empty() {
+            attributes.push((key, values));
+        }
+        rest = &rest[paren + 1 + end + 1..];
+    }
+    attributes
+}
+
+fn parse_sbf(text: &str) -> Attributes {
+    let body = text.trim().trim_start_matches('[').trim_end_matches(']');
+    split_outside_quotes(body, ';').into_iter()
+        .filter_map(|part| {
+            let (key, values) = part.split_once(':')?;
+            let key = key.trim();
+            if key.is_empty() || key.eq_ignore_ascii_case("character") {
+                return None;
+            }
+            Some((key.to_string(), split_values(values, ',')))
+        })
+        .collect()
+}
+
+fn parse_boostyle(text: &str) -> Attributes {
+    let mut attributes = Vec::new();
+    for line in text.split(['\n', ';']) {
+        let line = line.trim().trim_start_matches('[').trim_end_matches(']').trim();
+        if line.is_empty() {
+            continue;
+        }
+        let separator = line.find([':', '=']);
+        match separator {
+            Some(position) if line[position + 1..].contains('+') || !line[..position].contains(' ') => {
+                let key = line[..position].trim();
+                let values = split_values(&line[position + 1..], '+');
+                if !key.is_empty() && !values.is_empty() {
+                    attributes.push((key.to_string(), values));
+                }
+            },
+            _ if line.contains('+') => attributes.push(("traits".to_string(), split_values(line, '+'))),
+            _ => {},
+        }
+    }
+    attributes
+}
+
+fn split_values(text: &str, separator: char) -> Vec<String> {
+    split_outside_quotes(text, separator).into_iter()
+        .map(|value| value.trim().trim_matches('"').trim().to_string())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+fn split_outside_quotes(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    for (index, c) in text.char_indices() {
+        if c == '"' {
+            in_quotes = !in_quotes;
+        } else if c == separator && !in_quotes {
+            parts.push(&text[start..index]);
+            start = index + c.len_utf8();
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+fn find_outside_quotes(text: &str, target: char) -> Option<usize> {
+    let mut in_quotes = false;
+    for (index, c) in text.char_indices() {
+        if c == '"' {
+            in_quotes = !in_quotes;
+        } else if c == target && !in_quotes {
+            return Some(index);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attributes() -> Attributes {
+        vec![
+            ("Mind".to_string(), vec!["kind".to_string(), "shy".to_string()]),
+            ("Likes".to_string(), vec!["cats".to_string()]),
+        ]
+    }
+
+    #[test]
+    fn parses_each_style() {
+        assert_eq!(parse_attributes("[character(\"Ann\") {\nMind(\"kind\" + \"shy\")\nLikes(\"cats\")\n}]"), attributes());
+        assert_eq!(parse_attributes("[character(\"Ann\"){Mind(\"kind\" + \"shy\"), Likes(\"cats\")}]"), attributes());
+        assert_eq!(parse_attributes("[ character: \"Ann\"; Mind: \"kind\", \"shy\"; Likes: \"cats\" ]"), attributes());
+        assert_eq!(parse_attributes("[Mind: kind + shy\nLikes: cats]"), attributes());
+        assert_eq!(parse_attributes("[kind + shy]"), [("traits".to_string(), vec!["kind".to_string(), "shy".to_string()])]);
+    }
+
+    #[test]
+    fn quoted_values_keep_separators() {
+        let parsed = parse_attributes("[character(\"Ann\") {\nSays(\"hi (loudly) + bye\" + \"ok\")\n}]");
+        assert_eq!(parsed, [("Says".to_string(), vec!["hi (loudly) + bye".to_string(), "ok".to_string()])]);
+        let parsed = parse_attributes("[ character: \"Ann\"; Says: \"a, b; c\", \"d\" ]");
+        assert_eq!(parsed, [("Says".to_string(), vec!["a, b; c".to_string(), "d".to_string()])]);
+    }
+
+    #[test]
+    fn malformed_input_yields_partial_or_no_attributes() {
+        assert!(parse_attributes("").is_empty());
+        assert!(parse_attributes("Ann is a kind and shy girl.").is_empty());
+        assert!(parse_attributes("Note: she is kind\nLikes: cats + dogs").is_empty());
+        assert_eq!(parse_attributes("[character(\"Ann\") {\nMind(\"kind\")\nLikes(\"cats\""), [("Mind".to_string(), vec!["kind".to_string()])]);
+        assert!(parse_attributes("[character(\"Ann\") {}]").is_empty());
+    }
+
+    #[test]
+    fn rendered_styles_parse_back() {
+        for style in ["wpp", "W++", "sbf", "boostyle"] {
+            let text = render_attributes("Ann", &attributes(), style).unwrap();
+            assert_eq!(parse_attributes(&text), attributes(), "{}", style);
+        }
+        assert_eq!(render_attributes("Ann", &attributes(), "wpp").unwrap(), "[character(\"Ann\") {\nMind(\"kind\" + \"shy\")\nLikes(\"cats\")\n}]");
+        assert_eq!(render_attributes("Ann", &attributes(), "boostyle").unwrap(), "[Mind: kind + shy\nLikes: cats]");
+    }
+
+    #[test]
+    fn structured_styles_reject_quotes() {
+        pyo3::prepare_freethreaded_python();
+        let quoted = vec![("Quote".to_string(), vec!["\"hi\"".to_string()])];
+        for style in ["wpp", "sbf", "boostyle"] {
+            let error = render_attributes("Ann", &quoted, style).expect_err(style);
+            assert!(error.to_string().contains("double quotes"), "{}", style);
+            assert!(render_attributes("\"Ann\"", &attributes(), style).is_err(), "{}", style);
+        }
+        assert_eq!(render_attributes("Ann", &quoted, "prose").unwrap(), "Ann's quote is \"hi\".");
+    }
+
+    #[test]
+    fn renders_prose_and_rejects_unknown_styles() {
+        assert_eq!(render_attributes("Ann", &attributes(), "prose").unwrap(), "Ann's mind is kind and shy. Ann's likes are cats.");
+        let attributes = vec![
+            ("Ann's Traits".to_string(), vec!["kind".to_string(), "shy".to_string(), "smart".to_string()]),
+            ("Empty".to_string(), Vec::new()),
+            ("Species".to_string(), vec!["elf".to_string()]),
+            ("Status".to_string(), vec!["married".to_string()]),
+        ];
+        assert_eq!(render_attributes("", &attributes, "prose").unwrap(), "{{char}}'s traits are kind, shy and smart. {{char}}'s species is elf. {{char}}'s status is married.");
+        assert!(render_attributes("Ann", &attributes, "xml").is_err());
+    }
+
+    #[test]
+    fn detects_structured_styles_only() {
+        assert_eq!(detect_style(&render_attributes("Ann", &attributes(), "wpp").unwrap()), Some("wpp"));
+        assert_eq!(detect_style(&render_attributes("Ann", &attributes(), "sbf").unwrap()), Some("sbf"));
+        assert_eq!(detect_style(&render_attributes("Ann", &attributes(), "boostyle").unwrap()), Some("boostyle"));
+        assert_eq!(detect_style("Ann is kind + shy.\nShe likes cats."), None);
+        assert_eq!(detect_style("[character(\"Ann\") {}]"), None);
+        assert_eq!(detect_style("Mind: kind + shy"), None);
+        assert_eq!(detect_style(""), None);
+    }
+}
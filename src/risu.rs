@@ -0,0 +1,397 @@
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use pyo3::prelude::*;
+use base64::{Engine, engine::GeneralPurpose, engine::GeneralPurposeConfig, alphabet::STANDARD};
+use serde_json::{Map, Value, json};
+use zip::ZipArchive;
+
+use crate::{CharacterBook, CharacterClass, LoadCharacterClass, LorebookEntry, character_from_data};
+use crate::card_reader::{ASSET_URI_PREFIX, LoadLimits, check_json_depth, read_archive_file};
+
+const MODULE_MAGIC: u8 = 111;
+const MODULE_VERSION: u8 = 0;
+const EMBEDDED_URI_PREFIX: &str = "embeded://";
+const MAPPED_LORE_KEYS: [&str; 8] = ["key", "secondkey", "insertorder", "comment", "content", "alwaysActive", "selective", "useRegex"];
+
+pub struct RisuModule {
+    pub module: Map<String, Value>,
+    pub assets: Vec<Vec<u8>>,
+}
+
+pub fn parse_module(bytes: &[u8]) -> PyResult<RisuModule> {
+    let mut reader = ModuleReader { bytes, position: 0 };
+    if reader.byte()? != MODULE_MAGIC {
+        return Err(pyo3::exceptions::PyValueError::new_err("Not a RisuAI module: invalid magic number"));
+    }
+    let version = reader.byte()?;
+    if version != MODULE_VERSION {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!("RisuAI module version {} is not supported", version)));
+    }
+    let payload = reader.block()?;
+    let document: Value = std::str::from_utf8(payload).ok()
+        .and_then(|text| serde_json::from_str(text).ok())
+        .ok_or_else(|| pyo3::exceptions::PyValueError::new_err("RisuAI module data is not plain JSON (RPack-encoded modules are not supported)"))?;
+    if document.get("type").and_then(Value::as_str) != Some("risuModule") {
+        return Err(pyo3::exceptions::PyValueError::new_err("RisuAI module data does not contain a 'risuModule' document"));
+    }
+    let Some(Value::Object(module)) = document.get("module").cloned() else {
+        return Err(pyo3::exceptions::PyValueError::new_err("RisuAI module data does not contain a module object"));
+    };
+
+    let mut assets = Vec::new();
+    loop {
+        match reader.byte()? {
+            0 => break,
+            1 => assets.push(reader.block()?.to_vec()),
+            marker => return Err(pyo3::exceptions::PyValueError::new_err(format!("Unexpected record type {} in RisuAI module", marker))),
+        }
+    }
+    Ok(RisuModule { module, assets })
+}
+
+struct ModuleReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ModuleReader<'a> {
+    fn take(&mut self, length: usize) -> PyResult<&'a [u8]> {
+        let data = self.position.checked_add(length)
+            .and_then(|end| self.bytes.get(self.position..end))
+            .ok_or_else(|| pyo3::exceptions::PyValueError::new_err("RisuAI module is truncated"))?;
+        self.position += length;
+        Ok(data)
+    }
+
+    fn byte(&mut self) -> PyResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn block(&mut self) -> PyResult<&'a [u8]> {
+        let length = self.take(4)?;
+        let length = u32::from_le_bytes([length[0], length[1], length[2], length[3]]) as usize;
+        self.take(length)
+    }
+}
+
+// Whatever has no place on the card, including values that conflict with the card's own, is kept in 'extensions.risuai.modules'.
+pub fn apply_module(character: &mut CharacterClass, module: RisuModule) {
+    let RisuModule { mut module, assets } = module;
+    match module.remove("lorebook") {
+        Some(Value::Array(lorebook)) => {
+            let book = character.character_book.get_or_insert_with(CharacterBook::default);
+            for lore in lorebook.iter().filter_map(Value::as_object) {
+                let entry = entry_from_lore(lore, book.entries.len());
+                if !book.entries.iter().any(|existing| existing.keys == entry.keys && existing.content == entry.content) {
+                    book.entries.push(entry);
+                }
+            }
+        },
+        Some(value) => {
+            module.insert("lorebook".to_string(), value);
+        },
+        None => {},
+    }
+
+    let risuai = character.extensions.entry("risuai").or_insert_with(|| Value::Object(Map::new()));
+    if !risuai.is_object() {
+        *risuai = Value::Object(Map::new());
+    }
+    let risuai = risuai.as_object_mut().expect("risuai extension is an object");
+    for (module_key, extension_key) in [("regex", "customScripts"), ("trigger", "triggerscript")] {
+        if let Some(Value::Array(items)) = module.get(module_key) {
+            if let Value::Array(existing) = risuai.entry(extension_key).or_insert_with(|| Value::Array(Vec::new())) {
+                existing.extend(items.iter().filter(|item| !existing.contains(item)).cloned().collect::<Vec<Value>>());
+                module.remove(module_key);
+            }
+        }
+    }
+    for (module_key, extension_key) in [("backgroundEmbedding", "backgroundHTML"), ("lowLevelAccess", "lowLevelAccess")] {
+        match (module.get(module_key), risuai.get(extension_key)) {
+            (Some(Value::Null), _) => {
+                module.remove(module_key);
+            },
+            (Some(value), None) => {
+                risuai.insert(extension_key.to_string(), value.clone());
+                module.remove(module_key);
+            },
+            (Some(value), Some(existing)) if value == existing => {
+                module.remove(module_key);
+            },
+            _ => {},
+        }
+    }
+
+    let module_assets = match module.remove("assets") {
+        Some(Value::Array(items)) => items,
+        _ => Vec::new(),
+    };
+    let engine = GeneralPurpose::new(&STANDARD, GeneralPurposeConfig::new());
+    for (info, data) in module_assets.iter().zip(assets) {
+        let uri = next_asset_uri(&character.embedded_assets);
+        character.assets.push(json!({
+            "type": "x-risu-asset",
+            "uri": uri,
+            "name": info.get(0).and_then(Value::as_str).unwrap_or_default(),
+            "ext": info.get(2).and_then(Value::as_str).unwrap_or("png"),
+        }));
+        character.embedded_assets.insert(uri, engine.encode(data));
+    }
+
+    if !module.is_empty() {
+        let modules = risuai.entry("modules").or_insert_with(|| Value::Array(Vec::new()));
+        if !modules.is_array() {
+            *modules = Value::Array(vec![modules.take()]);
+        }
+        let modules = modules.as_array_mut().expect("risuai modules is an array");
+        let module = Value::Object(module);
+        if !modules.contains(&module) {
+            modules.push(module);
+        }
+    }
+}
+
+fn entry_from_lore(lore: &Map<String, Value>, index: usize) -> LorebookEntry {
+    let text = |key: &str| lore.get(key).and_then(Value::as_str).unwrap_or_default().to_string();
+    let flag = |key: &str| lore.get(key).and_then(Value::as_bool);
+    let split_keys = |keys: String| keys.split(',').map(|key| key.trim().to_string()).filter(|key| !key.is_empty()).collect::<Vec<String>>();
+    let extra: Map<String, Value> = lore.iter()
+        .filter(|(key, _)| !MAPPED_LORE_KEYS.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    LorebookEntry {
+        keys: split_keys(text("key")),
+        secondary_keys: Some(split_keys(text("secondkey"))).filter(|keys| !keys.is_empty()),
+        content: text("content"),
+        comment: Some(text("comment")).filter(|comment| !comment.is_empty()),
+        insertion_order: lore.get("insertorder").and_then(Value::as_i64).unwrap_or(index as i64),
+        constant: flag("alwaysActive"),
+        selective: flag("selective"),
+        use_regex: flag("useRegex"),
+        enabled: true,
+        extensions: if extra.is_empty() { Map::new() } else { Map::from_iter([("risuai".to_string(), Value::Object(extra))]) },
+        ..LorebookEntry::default()
+    }
+}
+
+fn next_asset_uri(embedded_assets: &BTreeMap<String, String>) -> String {
+    let next_id = embedded_assets.keys()
+        .filter_map(|uri| uri.strip_prefix(ASSET_URI_PREFIX)?.parse::<u64>().ok())
+        .max()
+        .map_or(0, |id| id + 1);
+    format!("{}{}", ASSET_URI_PREFIX, next_id)
+}
+
+pub fn load_charx(bytes: &[u8], limits: &LoadLimits) -> PyResult<CharacterClass> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).map_err(archive_error)?;
+    let mut total_size = 0;
+    let card = read_archive_file(&mut archive, "card.json", limits, &mut total_size)?;
+    let card = String::from_utf8(card)
+        .map_err(|_| pyo3::exceptions::PyValueError::new_err("CHARX file 'card.json' is not valid UTF-8"))?;
+    check_json_depth(&card, limits)?;
+    let char_data: LoadCharacterClass = serde_json::from_str(&card)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("CHARX file 'card.json' does not contain correct json data: {}", e)))?;
+    let mut character = character_from_data(char_data, None);
+
+    let engine = GeneralPurpose::new(&STANDARD, GeneralPurposeConfig::new());
+    let names: Vec<String> = archive.file_names().map(str::to_string).collect();
+    let mut module = None;
+    for name in names.into_iter().filter(|name| !name.ends_with('/') && name != "card.json") {
+        let data = read_archive_file(&mut archive, &name, limits, &mut total_size)?;
+        if name == "module.risum" {
+            module = Some(parse_module(&data)?);
+        } else {
+            character.embedded_assets.insert(format!("{}{}", EMBEDDED_URI_PREFIX, name), engine.encode(data));
+        }
+    }
+    if let Some(module) = module {
+        apply_module(&mut character, module);
+    }
+    Ok(character)
+}
+
+// PNG cards can only carry '__asset:' chunks, assets from .charx archives get new ids and their URIs are rewritten.
+pub fn png_assets(character: &CharacterClass) -> (CharacterClass, Vec<(String, String)>) {
+    let mut character = character.clone();
+    let mut renamed = BTreeMap::new();
+    for uri in character.embedded_assets.keys().filter(|uri| !uri.starts_with(ASSET_URI_PREFIX)).cloned().collect::<Vec<String>>() {
+        let data = character.embedded_assets.remove(&uri).expect("Asset URI comes from the map");
+        let new_uri = next_asset_uri(&character.embedded_assets);
+        character.embedded_assets.insert(new_uri.clone(), data);
+        renamed.insert(uri, new_uri);
+    }
+    for asset in &mut character.assets {
+        if let Some(new_uri) = asset.get("uri").and_then(Value::as_str).and_then(|uri| renamed.get(uri)) {
+            asset["uri"] = Value::from(new_uri.as_str());
+        }
+    }
+    let chunks = character.embedded_assets.iter()
+        .map(|(uri, data)| (format!("chara-ext-asset_:{}", &uri[ASSET_URI_PREFIX.len()..]), data.clone()))
+        .collect();
+    (character, chunks)
+}
+
+fn archive_error(error: zip::result::ZipError) -> PyErr {
+    pyo3::exceptions::PyValueError::new_err(format!("CHARX archive error: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    fn module_bytes(module: &Value, assets: &[&[u8]]) -> Vec<u8> {
+        let payload = serde_json::to_vec(&json!({"type": "risuModule", "module": module})).unwrap();
+        let mut bytes = vec![MODULE_MAGIC, MODULE_VERSION];
+        bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&payload);
+        for asset in assets {
+            bytes.push(1);
+            bytes.extend_from_slice(&(asset.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(asset);
+        }
+        bytes.push(0);
+        bytes
+    }
+
+    fn sample_module() -> Value {
+        json!({
+            "name": "World",
+            "lorebook": [{"key": "castle, keep", "secondkey": "", "insertorder": 5, "comment": "Castle", "content": "A castle", "mode": "normal", "alwaysActive": true, "selective": false}],
+            "regex": [{"comment": "strip", "in": "a", "out": "b", "type": "editoutput"}],
+            "trigger": [{"comment": "start", "type": "start"}],
+            "backgroundEmbedding": "<style></style>",
+            "assets": [["bg", "", "webp"]],
+        })
+    }
+
+    fn charx(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in files {
+            writer.start_file(*name, SimpleFileOptions::default()).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn module_is_applied_to_character() {
+        let mut character = CharacterClass::default();
+        apply_module(&mut character, parse_module(&module_bytes(&sample_module(), &[b"image"])).unwrap());
+
+        let entry = &character.character_book.as_ref().unwrap().entries[0];
+        assert_eq!(entry.keys, ["castle", "keep"]);
+        assert_eq!(entry.secondary_keys, None);
+        assert_eq!(entry.insertion_order, 5);
+        assert_eq!(entry.comment.as_deref(), Some("Castle"));
+        assert_eq!(entry.constant, Some(true));
+        assert_eq!(entry.selective, Some(false));
+        assert_eq!(entry.use_regex, None);
+        assert_eq!(entry.extensions["risuai"]["mode"], "normal");
+
+        let risuai = &character.extensions["risuai"];
+        assert_eq!(risuai["customScripts"][0]["comment"], "strip");
+        assert_eq!(risuai["triggerscript"][0]["comment"], "start");
+        assert_eq!(risuai["backgroundHTML"], "<style></style>");
+        assert_eq!(character.assets[0]["uri"], "__asset:0");
+        assert_eq!(character.assets[0]["ext"], "webp");
+        assert_eq!(character.embedded_assets["__asset:0"], "aW1hZ2U=");
+    }
+
+    #[test]
+    fn applying_a_module_twice_does_not_duplicate_entries() {
+        let module = module_bytes(&sample_module(), &[]);
+        let mut character = CharacterClass::default();
+        apply_module(&mut character, parse_module(&module).unwrap());
+        apply_module(&mut character, parse_module(&module).unwrap());
+        assert_eq!(character.character_book.as_ref().unwrap().entries.len(), 1);
+        assert_eq!(character.extensions["risuai"]["customScripts"].as_array().unwrap().len(), 1);
+        assert_eq!(character.extensions["risuai"]["modules"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn unmapped_and_conflicting_module_data_is_kept() {
+        let mut module = sample_module();
+        module["id"] = json!("a1b2");
+        module["description"] = json!("A world");
+        module["cjs"] = json!("console.log(1)");
+        module["lowLevelAccess"] = json!(true);
+        module["customModuleToggle"] = json!("toggle");
+        let mut character = CharacterClass::default();
+        character.extensions.insert("risuai".to_string(), json!({"backgroundHTML": "<div></div>", "triggerscript": "legacy"}));
+        apply_module(&mut character, parse_module(&module_bytes(&module, &[b"image"])).unwrap());
+
+        let risuai = &character.extensions["risuai"];
+        assert_eq!(risuai["backgroundHTML"], "<div></div>");
+        assert_eq!(risuai["triggerscript"], "legacy");
+        assert_eq!(risuai["lowLevelAccess"], true);
+        assert_eq!(risuai["customScripts"][0]["comment"], "strip");
+        assert_eq!(risuai["modules"], json!([{
+            "name": "World",
+            "id": "a1b2",
+            "description": "A world",
+            "cjs": "console.log(1)",
+            "customModuleToggle": "toggle",
+            "backgroundEmbedding": "<style></style>",
+            "trigger": [{"comment": "start", "type": "start"}],
+        }]));
+    }
+
+    #[test]
+    fn malformed_modules_are_rejected() {
+        pyo3::prepare_freethreaded_python();
+        let module = module_bytes(&sample_module(), &[b"image"]);
+        assert!(parse_module(&[]).is_err());
+        assert!(parse_module(&[0, 0]).is_err());
+        assert!(parse_module(&[MODULE_MAGIC, 1]).is_err());
+        for length in [2, 10, module.len() - 1] {
+            assert!(parse_module(&module[..length]).is_err(), "truncated at {}", length);
+        }
+        let mut bad_marker = module.clone();
+        *bad_marker.last_mut().unwrap() = 7;
+        assert!(parse_module(&bad_marker).is_err());
+
+        let mut rpack = vec![MODULE_MAGIC, MODULE_VERSION, 3, 0, 0, 0, 0x81, 0xff, 0x10, 0];
+        let error = parse_module(&rpack).err().unwrap();
+        assert!(error.to_string().contains("RPack"));
+        rpack[6..9].copy_from_slice(b"[1]");
+        assert!(parse_module(&rpack).is_err());
+    }
+
+    #[test]
+    fn charx_archive_loads_card_module_and_assets() {
+        let card = json!({"spec": "chara_card_v3", "spec_version": "3.0", "data": {"name": "Ann", "assets": [{"type": "icon", "uri": "embeded://assets/icon/main.png", "name": "main", "ext": "png"}]}});
+        let archive = charx(&[
+            ("card.json", serde_json::to_string(&card).unwrap().as_bytes()),
+            ("module.risum", &module_bytes(&sample_module(), &[b"bg"])),
+            ("assets/icon/main.png", b"icon"),
+        ]);
+        let character = load_charx(&archive, &LoadLimits::default()).unwrap();
+        assert_eq!(character.name, "Ann");
+        assert_eq!(character.embedded_assets["embeded://assets/icon/main.png"], "aWNvbg==");
+        assert_eq!(character.embedded_assets["__asset:0"], "Ymc=");
+        assert_eq!(character.character_book.as_ref().unwrap().entries.len(), 1);
+
+        let (renamed, chunks) = png_assets(&character);
+        assert_eq!(renamed.assets[0]["uri"], "__asset:1");
+        assert_eq!(chunks, [("chara-ext-asset_:0".to_string(), "Ymc=".to_string()), ("chara-ext-asset_:1".to_string(), "aWNvbg==".to_string())]);
+    }
+
+    #[test]
+    fn charx_limits_are_enforced() {
+        pyo3::prepare_freethreaded_python();
+        let card = br#"{"spec": "chara_card_v3", "data": {"name": "Ann"}}"#;
+        assert!(load_charx(&charx(&[("assets/a.png", b"a")]), &LoadLimits::default()).is_err());
+        assert!(load_charx(b"not a zip", &LoadLimits::default()).is_err());
+
+        let archive = charx(&[("card.json", card), ("assets/big.png", &[0; 4096])]);
+        let limits = LoadLimits { max_file_size: Some(1024), ..LoadLimits::default() };
+        let error = load_charx(&archive, &limits).err().unwrap();
+        assert!(error.to_string().contains("limit of 1024 bytes"));
+        assert!(load_charx(&archive, &LoadLimits { max_file_size: None, ..LoadLimits::default() }).is_ok());
+
+        let nested = format!(r#"{{"data": {{"name": "Ann", "extensions": {}}}}}"#, "[".repeat(100) + &"]".repeat(100));
+        assert!(load_charx(&charx(&[("card.json", nested.as_bytes())]), &LoadLimits::default()).is_err());
+    }
+}
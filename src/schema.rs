@@ -0,0 +1,204 @@
+use pyo3::prelude::*;
+use jsonschema::{Draft, JSONSchema};
+use schemars::{JsonSchema, gen::{SchemaGenerator, SchemaSettings}, schema::{InstanceType, Schema, SchemaObject}};
+use serde_json::Value;
+
+use crate::{ExportAgnai, ExportAllCharacterClass, ExportAiCompanion, ExportBackyard, ExportCharaCardV2, ExportKobold, ExportRisuAi, ExportTavernAi, ExportTextGenerationWebuiPygmalion, unsupported_format};
+
+const SCHEMA_FORMATS: [&str; 9] = ["tavernai", "textgenerationwebui", "aicompanion", "chara_card_v2", "risuai", "agnaistic", "backyard", "koboldai", "neutral"];
+
+pub fn json_schema(format_type: &str) -> PyResult<Value> {
+    let schema = match format_type.to_lowercase().as_str() {
+        "tavernai" | "sillytavern" => schema_for::<ExportTavernAi>(),
+        "textgenerationwebui" | "pygmalion" => schema_for::<ExportTextGenerationWebuiPygmalion>(),
+        "aicompanion" => schema_for::<ExportAiCompanion>(),
+        "chara_card_v2" => schema_for::<ExportCharaCardV2>(),
+        "risuai" => schema_for::<ExportRisuAi>(),
+        "agnaistic" | "agnai" => schema_for::<ExportAgnai>(),
+        "backyard" | "faraday" => schema_for::<ExportBackyard>(),
+        "koboldai" | "kobold" | "koboldcpp" => schema_for::<ExportKobold>(),
+        "neutral" => schema_for::<ExportAllCharacterClass>(),
+        _ => return Err(unsupported_format()),
+    };
+    Ok(schema)
+}
+
+/// Validates `document` against the schema of `format_type`. Without a format, cards that declare a
+/// `spec` are checked against that spec's schema and anything else has to match one of the other formats.
+pub fn validate_strict(document: &Value, format_type: Option<&str>) -> PyResult<()> {
+    let formats: Vec<&str> = match (format_type, document.get("spec").and_then(Value::as_str)) {
+        (Some(format_type), _) => vec![format_type],
+        (None, Some("chara_card_v2")) => vec!["chara_card_v2"],
+        (None, Some("chara_card_v3")) => vec!["risuai"],
+        (None, _) => SCHEMA_FORMATS.to_vec(),
+    };
+    let mut reports = Vec::new();
+    for format in &formats {
+        let errors = schema_errors(&json_schema(format)?, document);
+        if errors.is_empty() {
+            return Ok(());
+        }
+        reports.push(format!("{}: {}", format, errors.join(", ")));
+    }
+    let message = match formats.as_slice() {
+        [format] => format!("JSON document does not match the {} schema:\n{}", format, reports.join("\n")),
+        _ => format!("JSON document does not match the schema of any supported format:\n{}", reports.join("\n")),
+    };
+    Err(pyo3::exceptions::PyValueError::new_err(message))
+}
+
+/// Schema for a string field that always holds `value`, such as a card's `spec`.
+fn const_string(value: &str) -> Schema {
+    Schema::Object(SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        const_value: Some(Value::from(value)),
+        ..SchemaObject::default()
+    })
+}
+
+pub fn spec_v2(_: &mut SchemaGenerator) -> Schema {
+    const_string("chara_card_v2")
+}
+
+pub fn spec_version_v2(_: &mut SchemaGenerator) -> Schema {
+    const_string("2.0")
+}
+
+pub fn spec_v3(_: &mut SchemaGenerator) -> Schema {
+    const_string("chara_card_v3")
+}
+
+pub fn spec_version_v3(_: &mut SchemaGenerator) -> Schema {
+    const_string("3.0")
+}
+
+pub fn kind_character(_: &mut SchemaGenerator) -> Schema {
+    const_string("character")
+}
+
+fn schema_for<T: JsonSchema>() -> Value {
+    let generator = SchemaSettings::draft07()
+        .with(|settings| settings.inline_subschemas = true)
+        .into_generator();
+    let mut schema = serde_json::to_value(generator.into_root_schema_for::<T>()).expect("Error while serializing JSON schema");
+    // Every export writes metadata, but cards from other tools don't have it, so documents without it are still valid.
+    if let Some(Value::Array(required)) = schema.get_mut("required") {
+        required.retain(|key| key != "metadata");
+    }
+    schema
+}
+
+fn schema_errors(schema: &Value, document: &Value) -> Vec<String> {
+    let validator = JSONSchema::options()
+        .with_draft(Draft::Draft7)
+        .compile(schema)
+        .expect("Generated JSON schemas are valid");
+    let errors = match validator.validate(document) {
+        Ok(()) => Vec::new(),
+        Err(errors) => errors.map(|error| {
+            let path = error.instance_path.to_string();
+            format!("{}: {}", if path.is_empty() { "/" } else { &path }, error)
+        }).collect(),
+    };
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn v2_card() -> Value {
+        json!({
+            "spec": "chara_card_v2",
+            "spec_version": "2.0",
+            "data": {
+                "name": "Ann", "description": "", "personality": "", "scenario": "", "first_mes": "Hi", "mes_example": "",
+                "creator_notes": "", "system_prompt": "", "post_history_instructions": "", "alternate_greetings": [],
+                "tags": ["fantasy"], "creator": "someone", "character_version": "1.0", "extensions": {"depth_prompt": {"depth": 4}},
+                "character_book": {"extensions": {}, "entries": [{
+                    "keys": ["castle"], "content": "A castle", "extensions": {}, "enabled": true, "insertion_order": 0,
+                    "case_sensitive": false, "id": 1, "position": "before_char",
+                }]},
+            },
+        })
+    }
+
+    #[test]
+    fn every_format_has_a_schema() {
+        for format in SCHEMA_FORMATS {
+            let schema = json_schema(format).unwrap();
+            assert_eq!(schema["$schema"], "http://json-schema.org/draft-07/schema#", "{}", format);
+            let required = schema["required"].as_array().unwrap();
+            assert!(!required.iter().any(|key| key == "metadata"), "{}", format);
+        }
+    }
+
+    #[test]
+    fn unknown_format_is_an_error() {
+        assert!(json_schema("unknown").is_err());
+    }
+
+    #[test]
+    fn standard_v2_card_passes_strict_validation() {
+        assert!(schema_errors(&json_schema("chara_card_v2").unwrap(), &v2_card()).is_empty());
+        assert!(validate_strict(&v2_card(), None).is_ok());
+        assert!(validate_strict(&v2_card(), Some("chara_card_v2")).is_ok());
+        let schema = json_schema("chara_card_v2").unwrap();
+        assert_eq!(schema["properties"]["spec"]["const"], "chara_card_v2");
+        assert_eq!(schema["properties"]["spec_version"]["const"], "2.0");
+    }
+
+    #[test]
+    fn third_party_cards_pass_strict_validation() {
+        let mut sillytavern = v2_card();
+        sillytavern["name"] = json!("Ann");
+        sillytavern["create_date"] = json!("2024-1-1 @12h 00m 00s 000ms");
+        sillytavern["data"]["extensions"]["fav"] = json!(false);
+        assert!(validate_strict(&sillytavern, None).is_ok());
+
+        let tavernai = json!({"name": "Ann", "description": "", "personality": "", "scenario": "", "first_mes": "Hi", "mes_example": ""});
+        assert!(validate_strict(&tavernai, None).is_ok());
+        assert!(validate_strict(&tavernai, Some("chara_card_v2")).is_err());
+    }
+
+    #[test]
+    fn sillytavern_v1_export_passes_strict_validation() {
+        let sillytavern = json!({
+            "name": "Ann", "description": "A knight", "personality": "Brave", "first_mes": "Halt!", "avatar": "none",
+            "chat": "Ann - 2024-1-1 @12h 00m 00s 000ms", "mes_example": "<START>", "scenario": "", "create_date": "2024-1-1 @12h 00m 00s 000ms",
+            "talkativeness": "0.5", "fav": false, "creator": "", "tags": [],
+        });
+        assert!(validate_strict(&sillytavern, Some("tavernai")).is_ok());
+        assert!(validate_strict(&sillytavern, None).is_ok());
+        assert!(json_schema("tavernai").unwrap().get("additionalProperties").is_none());
+    }
+
+    #[test]
+    fn metadata_allows_additional_properties() {
+        let mut card = v2_card();
+        card["metadata"] = json!({
+            "version": 1, "created": 0, "modified": 0, "source": null,
+            "tool": {"name": "aichar", "version": "1.0", "url": ""}, "editor": "someone",
+        });
+        assert!(validate_strict(&card, Some("chara_card_v2")).is_ok());
+    }
+
+    #[test]
+    fn malformed_documents_fail_strict_validation() {
+        let mut card = v2_card();
+        card["data"]["tags"] = json!("fantasy");
+        assert_eq!(schema_errors(&json_schema("chara_card_v2").unwrap(), &card), [r#"/data/tags: "fantasy" is not of type "array""#]);
+
+        let mut card = v2_card();
+        card["spec"] = json!("garbage");
+        assert!(validate_strict(&card, None).is_err());
+        assert!(validate_strict(&card, Some("chara_card_v2")).is_err());
+        card["spec"] = json!("chara_card_v2");
+        card["spec_version"] = json!("9.9");
+        assert!(validate_strict(&card, None).is_err());
+
+        assert!(validate_strict(&json!({"data": {"name": 1}}), None).is_err());
+        assert!(validate_strict(&json!([1, 2]), None).is_err());
+    }
+}
@@ -0,0 +1,289 @@
+use pyo3::prelude::*;
+
+use crate::{CharacterBook, CharacterClass};
+use crate::comparison::{diff_characters, entry_keys, matched_entry_keys, render_book, FieldChange};
+
+#[derive(Clone)]
+#[pyclass]
+pub struct MergeConflict {
+    #[pyo3(get)]
+    field: String,
+    #[pyo3(get)]
+    base: Option<String>,
+    #[pyo3(get)]
+    ours: Option<String>,
+    #[pyo3(get)]
+    theirs: Option<String>,
+}
+
+#[pymethods]
+impl MergeConflict {
+    fn __repr__(&self) -> String {
+        format!("<MergeConflict {}>", self.field)
+    }
+}
+
+#[pyclass]
+pub struct MergeResult {
+    #[pyo3(get)]
+    character: CharacterClass,
+    #[pyo3(get)]
+    conflicts: Vec<MergeConflict>,
+    #[pyo3(get)]
+    merged_fields: Vec<String>,
+}
+
+#[pymethods]
+impl MergeResult {
+    fn has_conflicts(&self) -> bool {
+        !self.conflicts.is_empty()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<MergeResult merged={} conflicts={}>", self.merged_fields.len(), self.conflicts.len())
+    }
+}
+
+pub fn merge_characters(base: &CharacterClass, ours: &CharacterClass, theirs: &CharacterClass) -> MergeResult {
+    let ours_changes = diff_characters(base, ours).changes;
+    let theirs_changes = diff_characters(base, theirs).changes;
+
+    let mut merged = base.clone();
+    merged.image_path = ours.image_path.clone().or_else(|| theirs.image_path.clone());
+    let mut conflicts = Vec::new();
+    let mut merged_fields = Vec::new();
+
+    let mut fields: Vec<&str> = ours_changes.iter().map(|change| change.field.as_str()).collect();
+    for change in &theirs_changes {
+        if !fields.contains(&change.field.as_str()) {
+            fields.push(&change.field);
+        }
+    }
+
+    // Deleting the book on one side while editing it on the other is one conflict for the whole book, which keeps the base book.
+    let deleted = |side: &CharacterClass| base.character_book.is_some() && side.character_book.is_none();
+    let edited = |side: &CharacterClass, changes: &[FieldChange]| {
+        side.character_book.is_some() && changes.iter().any(|change| is_book_field(&change.field))
+    };
+    if (deleted(ours) && edited(theirs, &theirs_changes)) || (deleted(theirs) && edited(ours, &ours_changes)) {
+        conflicts.push(MergeConflict {
+            field: "character_book".to_string(),
+            base: base.character_book.as_ref().map(render_book),
+            ours: ours.character_book.as_ref().map(render_book),
+            theirs: theirs.character_book.as_ref().map(render_book),
+        });
+        fields.retain(|field| !is_book_field(field));
+    }
+
+    let mut entry_sources = Vec::new();
+    for field in fields {
+        let ours_change = find_change(&ours_changes, field);
+        let theirs_change = find_change(&theirs_changes, field);
+        let source = match (ours_change, theirs_change) {
+            (Some(_), None) => ours,
+            (None, Some(_)) => theirs,
+            (Some(ours_change), Some(theirs_change)) => {
+                if ours_change.new == theirs_change.new {
+                    ours
+                } else {
+                    conflicts.push(MergeConflict {
+                        field: field.to_string(),
+                        base: ours_change.old.clone(),
+                        ours: ours_change.new.clone(),
+                        theirs: theirs_change.new.clone(),
+                    });
+                    continue;
+                }
+            },
+            (None, None) => continue,
+        };
+        match field.strip_prefix("character_book.entries[").and_then(|rest| rest.strip_suffix(']')) {
+            Some(key) => entry_sources.push((key, source)),
+            None => apply_field(&mut merged, field, source),
+        }
+        merged_fields.push(field.to_string());
+    }
+    apply_entries(&mut merged, &entry_sources);
+    // Removing every entry of a deleted book leaves an empty one behind, drop it like the deleting side did.
+    let book_deleted = base.character_book.is_some() && (ours.character_book.is_none() || theirs.character_book.is_none());
+    if book_deleted && merged.character_book.as_ref().is_some_and(|book| *book == CharacterBook::default()) {
+        merged.character_book = None;
+    }
+
+    MergeResult { character: merged, conflicts, merged_fields }
+}
+
+fn is_book_field(field: &str) -> bool {
+    field == "character_book" || field.starts_with("character_book.")
+}
+
+fn find_change<'a>(changes: &'a [FieldChange], field: &str) -> Option<&'a FieldChange> {
+    changes.iter().find(|change| change.field == field)
+}
+
+fn apply_field(merged: &mut CharacterClass, field: &str, source: &CharacterClass) {
+    match field {
+        "name" => merged.name = source.name.clone(),
+        "summary" => merged.summary = source.summary.clone(),
+        "personality" => merged.personality = source.personality.clone(),
+        "scenario" => merged.scenario = source.scenario.clone(),
+        "greeting_message" => merged.greeting_message = source.greeting_message.clone(),
+        "example_messages" => merged.example_messages = source.example_messages.clone(),
+        "tags" => merged.tags = source.tags.clone(),
+        "alternate_greetings" => merged.alternate_greetings = source.alternate_greetings.clone(),
+        "group_only_greetings" => merged.group_only_greetings = source.group_only_greetings.clone(),
+        "nickname" => merged.nickname = source.nickname.clone(),
+        "creator" => merged.creator = source.creator.clone(),
+        "character_version" => merged.character_version = source.character_version.clone(),
+        "creator_notes" => merged.creator_notes = source.creator_notes.clone(),
+        "system_prompt" => merged.system_prompt = source.system_prompt.clone(),
+        "post_history_instructions" => merged.post_history_instructions = source.post_history_instructions.clone(),
+        "extensions" => merged.extensions = source.extensions.clone(),
+        "assets" => merged.assets = source.assets.clone(),
+        "character_book" => {
+            let source_book = source.character_book.clone().unwrap_or_default();
+            let book = merged.character_book.get_or_insert_with(CharacterBook::default);
+            book.name = source_book.name;
+            book.description = source_book.description;
+            book.scan_depth = source_book.scan_depth;
+            book.token_budget = source_book.token_budget;
+            book.recursive_scanning = source_book.recursive_scanning;
+            book.extensions = source_book.extensions;
+        },
+        _ => {},
+    }
+}
+
+// Entry keys of id-less entries are positions in the base book, so all changes are resolved
+// against the unmodified base before removed entries are dropped.
+fn apply_entries(merged: &mut CharacterClass, entry_sources: &[(&str, &CharacterClass)]) {
+    if entry_sources.is_empty() {
+        return;
+    }
+    let book = merged.character_book.get_or_insert_with(CharacterBook::default);
+    let base_entries = book.entries.clone();
+    let base_keys = entry_keys(&base_entries);
+    let mut removed = Vec::new();
+    for (key, source) in entry_sources {
+        let source_entry = source.character_book.as_ref().and_then(|book| {
+            matched_entry_keys(&base_entries, &book.entries).iter().position(|entry_key| entry_key == key).map(|position| book.entries[position].clone())
+        });
+        match (base_keys.iter().position(|entry_key| entry_key == key), source_entry) {
+            (Some(position), Some(entry)) => book.entries[position] = entry,
+            (Some(position), None) => removed.push(position),
+            (None, Some(entry)) => book.entries.push(entry),
+            (None, None) => {},
+        }
+    }
+    removed.sort_unstable();
+    for position in removed.into_iter().rev() {
+        book.entries.remove(position);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LorebookEntry;
+
+    fn entry(content: &str) -> LorebookEntry {
+        LorebookEntry { keys: vec![content.to_string()], content: content.to_string(), enabled: true, ..LorebookEntry::default() }
+    }
+
+    fn with_entries(contents: &[&str]) -> CharacterClass {
+        CharacterClass {
+            name: "Ann".to_string(),
+            character_book: Some(CharacterBook { entries: contents.iter().map(|content| entry(content)).collect(), ..CharacterBook::default() }),
+            ..CharacterClass::default()
+        }
+    }
+
+    fn contents(character: &CharacterClass) -> Vec<&str> {
+        character.character_book.as_ref().map(|book| book.entries.iter().map(|entry| entry.content.as_str()).collect()).unwrap_or_default()
+    }
+
+    #[test]
+    fn changes_on_different_fields_are_merged() {
+        let base = CharacterClass { name: "Ann".to_string(), personality: "kind".to_string(), ..CharacterClass::default() };
+        let ours = CharacterClass { personality: "brave".to_string(), ..base.clone() };
+        let theirs = CharacterClass { tags: vec!["knight".to_string()], ..base.clone() };
+        let result = merge_characters(&base, &ours, &theirs);
+        assert!(result.conflicts.is_empty());
+        assert_eq!(result.merged_fields, ["personality", "tags"]);
+        assert_eq!(result.character.personality, "brave");
+        assert_eq!(result.character.tags, ["knight"]);
+    }
+
+    #[test]
+    fn same_change_on_both_sides_is_not_a_conflict() {
+        let base = CharacterClass { name: "Ann".to_string(), ..CharacterClass::default() };
+        let edited = CharacterClass { name: "Anna".to_string(), ..base.clone() };
+        let result = merge_characters(&base, &edited, &edited);
+        assert!(!result.has_conflicts());
+        assert_eq!(result.character.name, "Anna");
+    }
+
+    #[test]
+    fn different_changes_of_one_field_conflict() {
+        let base = CharacterClass { name: "Ann".to_string(), ..CharacterClass::default() };
+        let ours = CharacterClass { name: "Anna".to_string(), ..base.clone() };
+        let theirs = CharacterClass { name: "Annie".to_string(), ..base.clone() };
+        let result = merge_characters(&base, &ours, &theirs);
+        assert_eq!(result.character.name, "Ann");
+        assert_eq!(result.conflicts.len(), 1);
+        let conflict = &result.conflicts[0];
+        assert_eq!((conflict.field.as_str(), conflict.base.as_deref(), conflict.ours.as_deref(), conflict.theirs.as_deref()), ("name", Some("Ann"), Some("Anna"), Some("Annie")));
+    }
+
+    #[test]
+    fn entry_changes_from_both_sides_are_merged() {
+        let base = with_entries(&["one", "two", "three"]);
+        let ours = with_entries(&["one", "TWO", "three"]);
+        let theirs = with_entries(&["one", "two"]);
+        let result = merge_characters(&base, &ours, &theirs);
+        assert!(result.conflicts.is_empty());
+        assert_eq!(contents(&result.character), ["one", "TWO"]);
+
+        let ours = with_entries(&["one", "two", "three", "four"]);
+        let result = merge_characters(&base, &ours, &theirs);
+        assert_eq!(contents(&result.character), ["one", "two", "four"]);
+
+        let ours = with_entries(&["one", "two", "three", "four"]);
+        let theirs = with_entries(&["one", "two", "three", "five"]);
+        let result = merge_characters(&base, &ours, &theirs);
+        assert!(result.conflicts.is_empty());
+        assert_eq!(contents(&result.character), ["one", "two", "three", "four", "five"]);
+        let result = merge_characters(&base, &ours, &ours);
+        assert_eq!(contents(&result.character), ["one", "two", "three", "four"]);
+
+        let ours = with_entries(&["one", "two", "THREE"]);
+        let theirs = with_entries(&["one", "three"]);
+        let result = merge_characters(&base, &ours, &theirs);
+        assert!(result.conflicts.is_empty());
+        assert_eq!(contents(&result.character), ["one", "THREE"]);
+    }
+
+    #[test]
+    fn deleted_book_stays_deleted() {
+        let base = with_entries(&["one", "two"]);
+        let deleted = CharacterClass { character_book: None, ..base.clone() };
+        for (ours, theirs) in [(&deleted, &base), (&base, &deleted), (&deleted, &deleted)] {
+            let result = merge_characters(&base, ours, theirs);
+            assert!(result.conflicts.is_empty());
+            assert!(result.character.character_book.is_none());
+        }
+
+        let edited = with_entries(&["one", "two", "three"]);
+        for (ours, theirs) in [(&deleted, &edited), (&edited, &deleted)] {
+            let result = merge_characters(&base, ours, theirs);
+            assert_eq!(result.conflicts.len(), 1);
+            let conflict = &result.conflicts[0];
+            assert_eq!(conflict.field, "character_book");
+            assert!(conflict.base.as_deref().is_some_and(|book| book.contains("one") && !book.contains("three")));
+            assert_eq!(conflict.ours.is_none(), ours.character_book.is_none());
+            assert_eq!(conflict.theirs.is_none(), theirs.character_book.is_none());
+            assert!(result.merged_fields.is_empty());
+            assert_eq!(contents(&result.character), ["one", "two"]);
+        }
+    }
+}
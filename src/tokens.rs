@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use pyo3::prelude::*;
+use tokenizers::Tokenizer;
+
+use crate::CharacterClass;
+
+pub fn count_tokens(character: &CharacterClass, tokenizer_path: &str) -> PyResult<HashMap<String, usize>> {
+    let tokenizer = Tokenizer::from_file(tokenizer_path)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Failed to load tokenizer from file '{}': {}", tokenizer_path, e)))?;
+
+    let fields: [(&str, &str); 9] = [
+        ("name", &character.name),
+        ("summary", &character.summary),
+        ("personality", &character.personality),
+        ("scenario", &character.scenario),
+        ("greeting_message", &character.greeting_message),
+        ("example_messages", &character.example_messages),
+        ("system_prompt", &character.system_prompt),
+        ("post_history_instructions", &character.post_history_instructions),
+        ("creator_notes", &character.creator_notes),
+    ];
+
+    let count = |field: &str, text: &str| tokenizer.encode(text, false)
+        .map(|encoding| encoding.len())
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Failed to tokenize '{}' field: {}", field, e)));
+    let mut counts = HashMap::new();
+    for (field, text) in fields {
+        counts.insert(field.to_string(), count(field, text)?);
+    }
+    let mut alternate_greetings = 0;
+    for greeting in &character.alternate_greetings {
+        alternate_greetings += count("alternate_greetings", greeting)?;
+    }
+    counts.insert("alternate_greetings".to_string(), alternate_greetings);
+
+    let permanent = counts["personality"] + counts["scenario"];
+    let total = counts.values().sum();
+    counts.insert("permanent".to_string(), permanent);
+    counts.insert("total".to_string(), total);
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempPath;
+
+    const TOKENIZER: &str = r#"{
+        "version": "1.0",
+        "truncation": null,
+        "padding": null,
+        "added_tokens": [],
+        "normalizer": null,
+        "pre_tokenizer": { "type": "Whitespace" },
+        "post_processor": null,
+        "decoder": null,
+        "model": { "type": "WordLevel", "vocab": { "[UNK]": 0, "hello": 1, "world": 2 }, "unk_token": "[UNK]" }
+    }"#;
+
+    #[test]
+    fn counts_tokens_per_field() {
+        let path = TempPath::new("tokenizer.json");
+        std::fs::write(path.path(), TOKENIZER).unwrap();
+        let character = CharacterClass {
+            name: "hello".to_string(),
+            personality: "hello world".to_string(),
+            scenario: "world world world".to_string(),
+            greeting_message: "unknown words here".to_string(),
+            ..CharacterClass::default()
+        };
+        let counts = count_tokens(&character, path.as_str()).unwrap();
+
+        assert_eq!(counts["name"], 1);
+        assert_eq!(counts["summary"], 0);
+        assert_eq!(counts["personality"], 2);
+        assert_eq!(counts["scenario"], 3);
+        assert_eq!(counts["greeting_message"], 3);
+        assert_eq!(counts["permanent"], 5);
+        assert_eq!(counts["total"], 9);
+    }
+
+    #[test]
+    fn counts_v2_fields() {
+        let path = TempPath::new("tokenizer_v2.json");
+        std::fs::write(path.path(), TOKENIZER).unwrap();
+        let character = CharacterClass {
+            personality: "hello".to_string(),
+            system_prompt: "hello world".to_string(),
+            post_history_instructions: "world".to_string(),
+            creator_notes: "hello hello".to_string(),
+            alternate_greetings: vec!["hello".to_string(), "hello world world".to_string()],
+            ..CharacterClass::default()
+        };
+        let counts = count_tokens(&character, path.as_str()).unwrap();
+
+        assert_eq!(counts["system_prompt"], 2);
+        assert_eq!(counts["post_history_instructions"], 1);
+        assert_eq!(counts["creator_notes"], 2);
+        assert_eq!(counts["alternate_greetings"], 4);
+        assert_eq!(counts["permanent"], 1);
+        assert_eq!(counts["total"], 10);
+    }
+
+    #[test]
+    fn missing_tokenizer_is_an_error() {
+        assert!(count_tokens(&CharacterClass::default(), "/nonexistent/tokenizer.json").is_err());
+    }
+}
@@ -0,0 +1,280 @@
+use pyo3::prelude::*;
+use chrono::Utc;
+use serde_json::Value;
+
+use crate::{CharacterClass, load_character_path};
+
+const PERMANENT_FIELD_MAX_LENGTH: usize = 3000;
+const SUPPORTED_SPEC_VERSIONS: [&str; 2] = ["2.0", "3.0"];
+
+#[derive(Clone)]
+#[pyclass]
+pub struct Diagnostic {
+    #[pyo3(get)]
+    rule_id: &'static str,
+    #[pyo3(get)]
+    severity: &'static str,
+    #[pyo3(get)]
+    field: Option<&'static str>,
+    #[pyo3(get)]
+    message: String,
+}
+
+#[pymethods]
+impl Diagnostic {
+    fn __repr__(&self) -> String {
+        match self.field {
+            Some(field) => format!("{} [{}] {}: {}", self.severity, self.rule_id, field, self.message),
+            None => format!("{} [{}] {}", self.severity, self.rule_id, self.message),
+        }
+    }
+}
+
+impl Diagnostic {
+    fn error(rule_id: &'static str, field: Option<&'static str>, message: String) -> Self {
+        Diagnostic { rule_id, severity: "error", field, message }
+    }
+
+    fn warning(rule_id: &'static str, field: Option<&'static str>, message: String) -> Self {
+        Diagnostic { rule_id, severity: "warning", field, message }
+    }
+}
+
+pub fn validate_character(character: &CharacterClass) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    if character.name.trim().is_empty() {
+        diagnostics.push(Diagnostic::error("empty-name", Some("name"), "Character name is empty".to_string()));
+    }
+
+    let greetings = std::iter::once(("greeting_message", &character.greeting_message))
+        .chain(character.alternate_greetings.iter().map(|greeting| ("alternate_greetings", greeting)))
+        .chain(character.group_only_greetings.iter().map(|greeting| ("group_only_greetings", greeting)));
+    for (field, greeting) in greetings {
+        if !greeting.is_empty() && !greeting.contains("{{char}}") {
+            diagnostics.push(Diagnostic::warning("greeting-missing-char", Some(field),
+                format!("Greeting does not contain the {{{{char}}}} macro: {}", preview(greeting))));
+        }
+    }
+
+    if !character.example_messages.trim().is_empty() && !character.example_messages.contains("<START>") {
+        diagnostics.push(Diagnostic::warning("example-missing-start", Some("example_messages"),
+            "Example messages do not contain any <START> separator".to_string()));
+    }
+
+    let fields: [(&'static str, &str); 6] = [
+        ("name", &character.name),
+        ("summary", &character.summary),
+        ("personality", &character.personality),
+        ("scenario", &character.scenario),
+        ("greeting_message", &character.greeting_message),
+        ("example_messages", &character.example_messages),
+    ];
+    for (field, text) in fields {
+        if !macros_balanced(text) {
+            diagnostics.push(Diagnostic::error("unbalanced-macros", Some(field),
+                format!("Field '{}' contains unbalanced {{{{ }}}} macro braces", field)));
+        }
+    }
+
+    for (field, text) in [("personality", &character.personality), ("scenario", &character.scenario)] {
+        let length = text.chars().count();
+        if length > PERMANENT_FIELD_MAX_LENGTH {
+            diagnostics.push(Diagnostic::warning("long-permanent-field", Some(field),
+                format!("Field '{}' is {} characters long, permanent fields should stay under {} characters (use token_counts() for a token estimate)", field, length, PERMANENT_FIELD_MAX_LENGTH)));
+        }
+    }
+
+    let mut seen: Vec<&String> = Some(&character.greeting_message).filter(|greeting| !greeting.is_empty()).into_iter().collect();
+    for greeting in &character.alternate_greetings {
+        if seen.contains(&greeting) {
+            diagnostics.push(Diagnostic::warning("duplicate-alternate-greeting", Some("alternate_greetings"),
+                format!("Alternate greeting is duplicated: {}", preview(greeting))));
+        } else {
+            seen.push(greeting);
+        }
+    }
+
+    let declared = &character.declared_spec;
+    let expected_version = match declared.spec.as_ref().and_then(Value::as_str) {
+        Some("chara_card_v2") => Some("2.0"),
+        Some("chara_card_v3") => Some("3.0"),
+        _ => None,
+    };
+    if let Some(spec_version) = &declared.spec_version {
+        match expected_version {
+            Some(expected) if spec_version.as_str() != Some(expected) => {
+                diagnostics.push(Diagnostic::error("invalid-spec-version", Some("spec_version"),
+                    format!("Unsupported spec_version {} for {}, expected \"{}\"", spec_version, declared.spec.as_ref().expect("spec is declared"), expected)));
+            },
+            None if !spec_version.as_str().is_some_and(|version| SUPPORTED_SPEC_VERSIONS.contains(&version)) => {
+                diagnostics.push(Diagnostic::error("invalid-spec-version", Some("spec_version"),
+                    format!("Unsupported spec_version {}, expected one of: {}", spec_version, SUPPORTED_SPEC_VERSIONS.join(", "))));
+            },
+            _ => {},
+        }
+    } else if expected_version == Some("2.0") {
+        diagnostics.push(Diagnostic::error("invalid-spec-version", Some("spec_version"),
+            "Character card V2 is missing spec_version".to_string()));
+    }
+
+    if let Some(created) = character.created_time {
+        if created > Utc::now().timestamp_millis() {
+            diagnostics.push(Diagnostic::warning("created-in-future", Some("created"),
+                format!("Creation timestamp {} is in the future", created)));
+        }
+    }
+
+    diagnostics
+}
+
+pub fn lint_file(path: &str) -> PyResult<Vec<Diagnostic>> {
+    Ok(validate_character(&load_character_path(path)?))
+}
+
+fn macros_balanced(text: &str) -> bool {
+    let mut depth: usize = 0;
+    let mut rest = text;
+    while let Some(position) = rest.find(['{', '}']) {
+        let tail = &rest[position..];
+        if let Some(after) = tail.strip_prefix("{{") {
+            depth += 1;
+            rest = after;
+        } else if let Some(after) = tail.strip_prefix("}}") {
+            if depth == 0 {
+                return false;
+            }
+            depth -= 1;
+            rest = after;
+        } else {
+            rest = &tail[1..];
+        }
+    }
+    depth == 0
+}
+
+fn preview(text: &str) -> String {
+    let mut preview: String = text.chars().take(40).collect();
+    if preview.len() < text.len() {
+        preview.push_str("...");
+    }
+    preview
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempPath;
+
+    fn rule_ids(diagnostics: &[Diagnostic]) -> Vec<&'static str> {
+        diagnostics.iter().map(|diagnostic| diagnostic.rule_id).collect()
+    }
+
+    fn temp_file(name: &str, content: &str) -> TempPath {
+        let path = TempPath::new(&format!("validation_{}", name));
+        std::fs::write(path.path(), content).unwrap();
+        path
+    }
+
+    #[test]
+    fn valid_character_has_no_diagnostics() {
+        let character = CharacterClass {
+            name: "Ann".to_string(),
+            greeting_message: "Hi, I am {{char}}".to_string(),
+            example_messages: "<START>\n{{user}}: Hi".to_string(),
+            ..CharacterClass::default()
+        };
+        assert!(validate_character(&character).is_empty());
+    }
+
+    #[test]
+    fn reports_field_rules() {
+        let character = CharacterClass {
+            greeting_message: "Hello {{user".to_string(),
+            example_messages: "Hi".to_string(),
+            personality: "a".repeat(PERMANENT_FIELD_MAX_LENGTH + 1),
+            created_time: Some(Utc::now().timestamp_millis() + 60_000),
+            ..CharacterClass::default()
+        };
+        assert_eq!(rule_ids(&validate_character(&character)), [
+            "empty-name", "greeting-missing-char", "example-missing-start", "unbalanced-macros", "long-permanent-field", "created-in-future",
+        ]);
+    }
+
+    #[test]
+    fn reports_duplicate_alternate_greetings() {
+        let character = CharacterClass {
+            name: "Ann".to_string(),
+            greeting_message: "Hi {{char}}".to_string(),
+            alternate_greetings: vec!["Hi {{char}}".to_string(), "Yo {{char}}".to_string(), "Yo {{char}}".to_string()],
+            ..CharacterClass::default()
+        };
+        let diagnostics = validate_character(&character);
+        assert_eq!(rule_ids(&diagnostics), ["duplicate-alternate-greeting", "duplicate-alternate-greeting"]);
+    }
+
+    #[test]
+    fn checks_every_greeting() {
+        let character = CharacterClass {
+            name: "Ann".to_string(),
+            greeting_message: "Hi {{char}}".to_string(),
+            alternate_greetings: vec!["Hello".to_string()],
+            group_only_greetings: vec!["Hey all".to_string()],
+            ..CharacterClass::default()
+        };
+        let fields: Vec<_> = validate_character(&character).iter().map(|diagnostic| diagnostic.field).collect();
+        assert_eq!(fields, [Some("alternate_greetings"), Some("group_only_greetings")]);
+    }
+
+    #[test]
+    fn validate_checks_declared_spec_version() {
+        let character = crate::load_character_json(r#"{"spec": "chara_card_v2", "data": {"name": "Ann"}}"#, false, None).ok().unwrap();
+        assert_eq!(rule_ids(&validate_character(&character)), ["invalid-spec-version"]);
+        let character = crate::load_character_json(r#"{"spec": "chara_card_v3", "spec_version": 3.0, "data": {"name": "Ann"}}"#, false, None).ok().unwrap();
+        assert_eq!(rule_ids(&validate_character(&character)), ["invalid-spec-version"]);
+        let character = crate::load_character_json(r#"{"spec": "chara_card_v3", "spec_version": "3.0", "data": {"name": "Ann"}}"#, false, None).ok().unwrap();
+        assert!(validate_character(&character).is_empty());
+    }
+
+    #[test]
+    fn spec_version_has_to_match_the_spec() {
+        for (spec, version) in [("chara_card_v2", "3.0"), ("chara_card_v3", "2.0")] {
+            let json = format!(r#"{{"spec": "{}", "spec_version": "{}", "data": {{"name": "Ann"}}}}"#, spec, version);
+            let character = crate::load_character_json(&json, false, None).ok().unwrap();
+            assert_eq!(rule_ids(&validate_character(&character)), ["invalid-spec-version"], "{}", spec);
+        }
+        let character = crate::load_character_json(r#"{"spec": "chara_card_v2", "spec_version": "2.0", "data": {"name": "Ann"}}"#, false, None).ok().unwrap();
+        assert!(validate_character(&character).is_empty());
+    }
+
+    #[test]
+    fn macros_balance() {
+        assert!(macros_balanced("{{char}} and {{user}}"));
+        assert!(macros_balanced("{ single } braces"));
+        assert!(!macros_balanced("{{char}"));
+        assert!(!macros_balanced("}}{{"));
+    }
+
+    #[test]
+    fn lint_checks_spec_version() {
+        let path = temp_file("card.json", r#"{"spec": "chara_card_v2", "spec_version": "1.0", "data": {"name": "Ann", "first_mes": "Hi", "alternate_greetings": ["Hi"]}}"#);
+        let diagnostics = lint_file(path.as_str()).unwrap();
+        assert_eq!(rule_ids(&diagnostics), ["greeting-missing-char", "greeting-missing-char", "duplicate-alternate-greeting", "invalid-spec-version"]);
+    }
+
+    #[test]
+    fn lint_supports_toml_and_markdown() {
+        let toml_path = temp_file("card.toml", "name = \"\"\n");
+        let markdown_path = temp_file("card.md", "# Ann\n\n## Greeting\n\nHello\n");
+        let toml_diagnostics = lint_file(toml_path.as_str()).unwrap();
+        let markdown_diagnostics = lint_file(markdown_path.as_str()).unwrap();
+        assert_eq!(rule_ids(&toml_diagnostics), ["empty-name"]);
+        assert_eq!(rule_ids(&markdown_diagnostics), ["greeting-missing-char"]);
+    }
+
+    #[test]
+    fn lint_rejects_malformed_json() {
+        let path = temp_file("broken.json", "{\"name\": ");
+        assert!(lint_file(path.as_str()).is_err());
+    }
+}